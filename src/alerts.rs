@@ -0,0 +1,834 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    cli::ProviderArgs,
+    config::{Config, Paths},
+    error::{Error, Result},
+    location::Location,
+    network::{shared_client, DEFAULT_CONNECT_TIMEOUT},
+    providers::{Weather, WeatherProvider},
+    secrets::shell,
+    serve::shutdown_signal,
+    units::{DistanceUnit, PrecipitationUnit, SpeedUnit, TemperatureUnit},
+};
+
+/// The number of days of forecast checked when none is configured.
+const DEFAULT_FORECAST_DAYS: u32 = 1;
+
+/// The time to wait for a webhook to answer.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The alert rules and where alerts are sent, set in the `[alerts]` table of the configuration
+/// files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertSettings {
+    /// The rules checked against the weather of every place, e.g. "wind.speed > 15 m/s".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+
+    /// The number of days of forecast checked besides the current weather, 0 to check the current
+    /// weather only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forecast_days: Option<u32>,
+
+    /// A command run with the shell for every alert, with the alert in environment variables.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+
+    /// A URL every alert is posted to as JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+
+    /// Whether to show alerts as desktop notifications.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desktop: Option<bool>,
+}
+
+impl AlertSettings {
+    /// Returns whether no alert setting is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merges `other` on top of these settings, the settings of `other` are overridden. The rules
+    /// of both are kept.
+    pub fn merge(self, other: AlertSettings) -> AlertSettings {
+        let mut rules = self.rules;
+        for rule in other.rules {
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+        AlertSettings {
+            rules,
+            forecast_days: other.forecast_days.or(self.forecast_days),
+            command: other.command.or(self.command),
+            webhook: other.webhook.or(self.webhook),
+            desktop: other.desktop.or(self.desktop),
+        }
+    }
+
+    /// Removes the settings running a command or sending the alerts to another host, returning
+    /// the names of those that were set.
+    pub fn remove_untrusted(&mut self) -> Vec<&'static str> {
+        [
+            ("command", &mut self.command),
+            ("webhook", &mut self.webhook),
+        ]
+        .into_iter()
+        .filter_map(|(name, setting)| setting.take().map(|_| name))
+        .collect()
+    }
+
+    fn forecast_days(&self) -> u32 {
+        self.forecast_days.unwrap_or(DEFAULT_FORECAST_DAYS)
+    }
+}
+
+/// A field of `Weather` a rule can check, named like the columns of the CSV output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Temperature,
+    FeelsLike,
+    DewPoint,
+    Humidity,
+    Pressure,
+    UvIndex,
+    WindSpeed,
+    WindGust,
+    WindDirection,
+    RainVolume,
+    Visibility,
+    Cloudiness,
+}
+
+impl Field {
+    const ALL: [Field; 12] = [
+        Field::Temperature,
+        Field::FeelsLike,
+        Field::DewPoint,
+        Field::Humidity,
+        Field::Pressure,
+        Field::UvIndex,
+        Field::WindSpeed,
+        Field::WindGust,
+        Field::WindDirection,
+        Field::RainVolume,
+        Field::Visibility,
+        Field::Cloudiness,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::Temperature => "temperature",
+            Field::FeelsLike => "feels_like",
+            Field::DewPoint => "dew_point",
+            Field::Humidity => "humidity",
+            Field::Pressure => "pressure",
+            Field::UvIndex => "uv_index",
+            Field::WindSpeed => "wind.speed",
+            Field::WindGust => "wind.gust",
+            Field::WindDirection => "wind.deg",
+            Field::RainVolume => "rain_volume",
+            Field::Visibility => "visibility",
+            Field::Cloudiness => "cloudiness",
+        }
+    }
+
+    /// Returns the value of the field in the normalized units documented on `Weather`, or `None`
+    /// if the provider didn't report it.
+    fn value(self, weather: &Weather) -> Option<f64> {
+        match self {
+            Field::Temperature => Some(weather.temperature),
+            Field::FeelsLike => Some(weather.feels_like),
+            Field::DewPoint => weather.dew_point,
+            Field::Humidity => Some(weather.humidity as f64),
+            Field::Pressure => Some(weather.pressure),
+            Field::UvIndex => weather.uv_index,
+            Field::WindSpeed => Some(weather.wind.speed),
+            Field::WindGust => weather.wind.gust,
+            Field::WindDirection => Some(weather.wind.deg as f64),
+            Field::RainVolume => Some(weather.rain_volume),
+            Field::Visibility => weather.visibility,
+            Field::Cloudiness => Some(weather.cloudiness as f64),
+        }
+    }
+
+    /// Returns the normalized unit of the field, used when a rule gives none.
+    fn unit(self) -> Unit {
+        match self {
+            Field::Temperature | Field::FeelsLike | Field::DewPoint => {
+                Unit::Temperature(TemperatureUnit::Celsius)
+            }
+            Field::Humidity | Field::Cloudiness => Unit::Percent,
+            Field::Pressure => Unit::Hectopascal,
+            Field::UvIndex => Unit::None,
+            Field::WindSpeed | Field::WindGust => Unit::Speed(SpeedUnit::MetersPerSecond),
+            Field::WindDirection => Unit::Degrees,
+            Field::RainVolume => Unit::Precipitation(PrecipitationUnit::Millimeters),
+            Field::Visibility => Unit::Distance(DistanceUnit::Meters),
+        }
+    }
+}
+
+/// The unit the threshold of a rule is given in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unit {
+    Temperature(TemperatureUnit),
+    Speed(SpeedUnit),
+    Precipitation(PrecipitationUnit),
+    Distance(DistanceUnit),
+    Percent,
+    Hectopascal,
+    Degrees,
+    None,
+}
+
+impl Unit {
+    /// Parses a unit of the same quantity as `self`, either its symbol or its name on the command
+    /// line, e.g. "°C" or "celsius".
+    fn parse_like(self, unit: &str) -> Option<Unit> {
+        match self {
+            Unit::Temperature(_) => match unit {
+                "C" => Some(TemperatureUnit::Celsius),
+                "F" => Some(TemperatureUnit::Fahrenheit),
+                unit => parse_unit(unit),
+            }
+            .map(Unit::Temperature),
+            Unit::Speed(_) => parse_unit(unit).map(Unit::Speed),
+            Unit::Precipitation(_) => parse_unit(unit).map(Unit::Precipitation),
+            Unit::Distance(_) => parse_unit(unit).map(Unit::Distance),
+            Unit::Percent => (unit == "%").then_some(self),
+            Unit::Hectopascal => unit.eq_ignore_ascii_case("hpa").then_some(self),
+            Unit::Degrees => matches!(unit, "°" | "deg").then_some(self),
+            Unit::None => None,
+        }
+    }
+
+    /// Converts a value in the normalized unit of the quantity to this unit.
+    fn convert(self, value: f64) -> f64 {
+        match self {
+            Unit::Temperature(unit) => unit.from_celsius(value),
+            Unit::Speed(unit) => unit.from_mps(value),
+            Unit::Precipitation(unit) => unit.from_mm(value),
+            Unit::Distance(unit) => unit.from_meters(value),
+            Unit::Percent | Unit::Hectopascal | Unit::Degrees | Unit::None => value,
+        }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unit::Temperature(unit) => write!(f, " {unit}"),
+            Unit::Speed(unit) => write!(f, " {unit}"),
+            Unit::Precipitation(unit) => write!(f, " {unit}"),
+            Unit::Distance(unit) => write!(f, " {unit}"),
+            Unit::Percent => f.write_str(" %"),
+            Unit::Hectopascal => f.write_str(" hPa"),
+            Unit::Degrees => f.write_str("°"),
+            Unit::None => Ok(()),
+        }
+    }
+}
+
+/// Parses a unit by its symbol or by its name on the command line.
+fn parse_unit<T: ValueEnum + Display>(unit: &str) -> Option<T> {
+    T::value_variants()
+        .iter()
+        .find(|x| {
+            x.to_string() == unit || x.to_possible_value().is_some_and(|x| x.matches(unit, true))
+        })
+        .cloned()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+}
+
+impl Operator {
+    const ALL: [(&'static str, Operator); 5] = [
+        (">=", Operator::GreaterOrEqual),
+        ("<=", Operator::LessOrEqual),
+        (">", Operator::Greater),
+        ("<", Operator::Less),
+        ("=", Operator::Equal),
+    ];
+
+    fn symbol(self) -> &'static str {
+        Self::ALL.iter().find(|x| x.1 == self).unwrap().0
+    }
+
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Operator::Greater => value > threshold,
+            Operator::GreaterOrEqual => value >= threshold,
+            Operator::Less => value < threshold,
+            Operator::LessOrEqual => value <= threshold,
+            Operator::Equal => value == threshold,
+        }
+    }
+}
+
+/// A condition on a field of the weather, such as "wind.speed > 15 m/s" or "rain_volume > 5mm".
+/// The threshold is in the normalized unit of the field if no unit is given.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rule {
+    field: Field,
+    operator: Operator,
+    threshold: f64,
+    unit: Unit,
+}
+
+impl Rule {
+    /// Returns the value of the field in the unit of the rule if the weather meets the condition.
+    fn check(&self, weather: &Weather) -> Option<f64> {
+        let value = self.unit.convert(self.field.value(weather)?);
+        self.operator.holds(value, self.threshold).then_some(value)
+    }
+}
+
+impl FromStr for Rule {
+    type Err = Error;
+
+    fn from_str(rule: &str) -> Result<Self> {
+        let invalid = |message: String| Error::InvalidAlertRule {
+            rule: rule.to_owned(),
+            message,
+        };
+        let start = rule
+            .find(['<', '>', '='])
+            .ok_or_else(|| invalid("no operator, one of >, >=, <, <= or =".to_owned()))?;
+        let (name, rest) = rule.split_at(start);
+        let (symbol, operator) = Operator::ALL
+            .into_iter()
+            .find(|x| rest.starts_with(x.0))
+            .unwrap();
+        let name = name.trim();
+        let field = Field::ALL
+            .into_iter()
+            .find(|x| x.name() == name)
+            .ok_or_else(|| {
+                let fields: Vec<_> = Field::ALL.iter().map(|x| x.name()).collect();
+                invalid(format!(
+                    "unknown field \"{name}\", expected one of {}",
+                    fields.join(", ")
+                ))
+            })?;
+
+        let rest = rest[symbol.len()..].trim();
+        let end = rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+')))
+            .unwrap_or(rest.len());
+        let (threshold, unit) = rest.split_at(end);
+        let threshold = threshold
+            .parse()
+            .map_err(|_| invalid(format!("\"{threshold}\" is not a number")))?;
+        let unit = match unit.trim() {
+            "" => field.unit(),
+            unit => field
+                .unit()
+                .parse_like(unit)
+                .ok_or_else(|| invalid(format!("\"{unit}\" is not a unit of {}", field.name())))?,
+        };
+        Ok(Rule {
+            field,
+            operator,
+            threshold,
+            unit,
+        })
+    }
+}
+
+impl TryFrom<String> for Rule {
+    type Error = Error;
+
+    fn try_from(rule: String) -> Result<Self> {
+        rule.parse()
+    }
+}
+
+impl From<Rule> for String {
+    fn from(rule: Rule) -> Self {
+        rule.to_string()
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}{}",
+            self.field.name(),
+            self.operator.symbol(),
+            self.threshold,
+            self.unit
+        )
+    }
+}
+
+/// A rule met by the current weather or the forecast of a place.
+#[derive(Debug, PartialEq)]
+struct Alert {
+    rule: Rule,
+    /// The value of the field, in the unit of the rule.
+    value: f64,
+    /// The time of the weather meeting the rule.
+    time: DateTime<Utc>,
+    /// The time of forecast weather in the local time of the place, `None` for current weather.
+    forecast_at: Option<String>,
+}
+
+impl Alert {
+    /// Returns the key telling apart the alerts of a place that were already sent.
+    fn key(&self) -> String {
+        let kind = if self.forecast_at.is_some() {
+            "forecast"
+        } else {
+            "current"
+        };
+        format!("{kind}: {}", self.rule)
+    }
+
+    fn message(&self, place: &str) -> String {
+        let value = format!("{:.1}{}", self.value, self.rule.unit);
+        match &self.forecast_at {
+            Some(time) => format!("{place}: {} ({value} expected at {time})", self.rule),
+            None => format!("{place}: {} ({value} now)", self.rule),
+        }
+    }
+}
+
+/// Returns the alerts of a place: the rules met by the current weather, and the rules met by the
+/// forecast, at the earliest time they are.
+fn evaluate(rules: &[Rule], current: &Weather, forecast: &[Weather]) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+    for rule in rules {
+        if let Some(value) = rule.check(current) {
+            alerts.push(Alert {
+                rule: rule.clone(),
+                value,
+                time: current.time,
+                forecast_at: None,
+            });
+        }
+    }
+    for rule in rules {
+        let first = forecast
+            .iter()
+            .filter(|x| x.time > current.time)
+            .find_map(|x| Some((x, rule.check(x)?)));
+        if let Some((weather, value)) = first {
+            alerts.push(Alert {
+                rule: rule.clone(),
+                value,
+                time: weather.time,
+                forecast_at: Some(
+                    weather
+                        .local_time(weather.time)
+                        .format("%Y-%m-%d %H:%M %:z")
+                        .to_string(),
+                ),
+            });
+        }
+    }
+    alerts
+}
+
+/// The alerts sent for each place, which are not sent again until their rule stops being met.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AlertState {
+    #[serde(default)]
+    active: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl AlertState {
+    fn read(file: &Path) -> Result<Self> {
+        match std::fs::read(file) {
+            Ok(data) => Ok(serde_json::from_slice(&data).unwrap_or_default()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self, file: &Path) -> Result<()> {
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(file, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Lists the alert rules, and where alerts are sent.
+///
+/// # Arguments
+///
+/// * `paths` - The `Paths` of the configuration files.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn list_alerts(paths: &Paths) -> Result<()> {
+    let config = Config::load_or_default(paths)?;
+    let alerts = &config.alerts;
+    for rule in &alerts.rules {
+        println!("{rule}");
+    }
+    println!();
+    match alerts.forecast_days() {
+        0 => println!("Checked against the current weather"),
+        days => println!("Checked against the current weather and {days} days of forecast"),
+    }
+    let mut channels = vec!["stdout".to_owned()];
+    channels.extend(alerts.command.as_ref().map(|x| format!("command `{x}`")));
+    channels.extend(alerts.webhook.as_ref().map(|x| format!("webhook {x}")));
+    if alerts.desktop == Some(true) {
+        channels.push("desktop notifications".to_owned());
+    }
+    println!("Sent to {}", channels.join(", "));
+    Ok(())
+}
+
+/// Adds an alert rule to the written configuration file.
+///
+/// # Arguments
+///
+/// * `rule` - The `Rule` to add.
+/// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn add_alert(rule: &Rule, paths: &Paths) -> Result<()> {
+    Config::update(paths, |config| {
+        if !config.alerts.rules.contains(rule) {
+            config.alerts.rules.push(rule.clone());
+        }
+        Ok(())
+    })?;
+    println!("Alert rule saved successfully.");
+    Ok(())
+}
+
+/// Removes an alert rule from the written configuration file.
+///
+/// # Arguments
+///
+/// * `rule` - The `Rule` to remove.
+/// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the rule is not in the written file.
+pub fn remove_alert(rule: &Rule, paths: &Paths) -> Result<()> {
+    Config::update(paths, |config| {
+        let count = config.alerts.rules.len();
+        config.alerts.rules.retain(|x| x != rule);
+        if config.alerts.rules.len() == count {
+            return Err(Error::InvalidSetting {
+                key: "alerts.rules".to_owned(),
+                message: format!("\"{rule}\" is not set in the written configuration file"),
+            });
+        }
+        Ok(())
+    })?;
+    println!("Alert rule removed successfully.");
+    Ok(())
+}
+
+/// Checks the alert rules against the current weather and the forecast of places, and sends the
+/// alerts that were not sent yet, once or on an interval until interrupted.
+///
+/// # Arguments
+///
+/// * `places` - The saved places or locations to check, named as given. All saved places if
+///   empty, or the default location if there are none.
+/// * `watch` - The time between two checks, or `None` to check once.
+/// * `provider` - A `ProviderArgs` representing the provider selection made on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache, the usage counters and
+///   the sent alerts.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if there are no rules, no places, or, when checking once, if the weather of
+/// a place cannot be retrieved or an alert cannot be sent.
+pub async fn check_alerts(
+    places: &[String],
+    watch: Option<Duration>,
+    provider: &ProviderArgs,
+    paths: &Paths,
+) -> Result<()> {
+    let config = Config::load(paths)?;
+    if config.alerts.rules.is_empty() {
+        return Err(Error::InvalidSetting {
+            key: "alerts.rules".to_owned(),
+            message: "no rules, add one with `weather alerts add <RULE>`".to_owned(),
+        });
+    }
+    let places = config.named_locations(places)?;
+    let weather_api = config.build_provider(provider, paths)?;
+    let state_file = paths.data_dir.join("alerts.json");
+
+    let Some(interval) = watch else {
+        return check(&config, weather_api.as_ref(), &places, &state_file).await;
+    };
+    let stop = shutdown_signal();
+    tokio::pin!(stop);
+    loop {
+        let next = async {
+            // the failures are reported, and retried at the next check
+            let _ = check(&config, weather_api.as_ref(), &places, &state_file).await;
+            tokio::time::sleep(interval).await;
+        };
+        tokio::select! {
+            _ = next => {}
+            _ = &mut stop => return Ok(()),
+        }
+    }
+}
+
+/// Checks the places once, reporting failures on stderr and returning the last one.
+async fn check(
+    config: &Config,
+    weather_api: &dyn WeatherProvider,
+    places: &[(String, Location)],
+    state_file: &Path,
+) -> Result<()> {
+    let settings = &config.alerts;
+    let mut state = AlertState::read(state_file)?;
+    let mut failure = Ok(());
+    for (name, location) in places {
+        let alerts = match fetch(weather_api, location, settings.forecast_days()).await {
+            Ok((current, forecast)) => evaluate(&settings.rules, &current, &forecast),
+            Err(e) => {
+                eprintln!("Checking {name} failed: {e}");
+                failure = Err(e);
+                continue;
+            }
+        };
+        let sent = state.active.remove(name).unwrap_or_default();
+        let mut active = BTreeSet::new();
+        for alert in alerts {
+            let key = alert.key();
+            if !sent.contains(&key) {
+                if let Err(e) = notify(settings, name, &alert).await {
+                    eprintln!("{e}");
+                    failure = Err(e);
+                    // sent again at the next check
+                    continue;
+                }
+            }
+            active.insert(key);
+        }
+        if !active.is_empty() {
+            state.active.insert(name.clone(), active);
+        }
+    }
+    state.write(state_file)?;
+    failure
+}
+
+/// Retrieves the current weather of a location, and its forecast if any days are checked.
+async fn fetch(
+    weather_api: &dyn WeatherProvider,
+    location: &Location,
+    forecast_days: u32,
+) -> Result<(Weather, Vec<Weather>)> {
+    let current = weather_api.get_weather(location).await?;
+    let forecast = match forecast_days {
+        0 => Vec::new(),
+        days => weather_api.get_forecast(location, days).await?,
+    };
+    Ok((current, forecast))
+}
+
+/// Prints an alert, and sends it to the configured command, webhook and desktop notifications.
+async fn notify(settings: &AlertSettings, place: &str, alert: &Alert) -> Result<()> {
+    let message = alert.message(place);
+    println!("{message}");
+
+    if let Some(command) = &settings.command {
+        let error = |message: String| Error::Notification {
+            channel: "command",
+            message,
+        };
+        let status = tokio::process::Command::from(shell(command))
+            .env("WEATHER_ALERT_PLACE", place)
+            .env("WEATHER_ALERT_RULE", alert.rule.to_string())
+            .env("WEATHER_ALERT_VALUE", format!("{:.1}", alert.value))
+            .env("WEATHER_ALERT_TIME", alert.time.to_rfc3339())
+            .env(
+                "WEATHER_ALERT_FORECAST",
+                alert.forecast_at.is_some().to_string(),
+            )
+            .env("WEATHER_ALERT_MESSAGE", &message)
+            .status()
+            .await
+            .map_err(|e| error(format!("couldn't run `{command}`: {e}")))?;
+        if !status.success() {
+            return Err(error(format!("`{command}` failed with {status}")));
+        }
+    }
+
+    if let Some(url) = &settings.webhook {
+        let error = |message: String| Error::Notification {
+            channel: "webhook",
+            message,
+        };
+        let body = json!({
+            "place": place,
+            "rule": alert.rule.to_string(),
+            "value": alert.value,
+            "time": alert.time,
+            "forecast": alert.forecast_at.is_some(),
+            "message": message,
+        });
+        let response = shared_client(DEFAULT_CONNECT_TIMEOUT)
+            .post(url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| error(e.without_url().to_string()))?;
+        if !response.status().is_success() {
+            return Err(error(format!("{url} answered {}", response.status())));
+        }
+    }
+
+    if settings.desktop == Some(true) {
+        show_desktop_notification(&message).await?;
+    }
+    Ok(())
+}
+
+/// Shows a desktop notification with `notify-send` on Linux or `osascript` on macOS.
+async fn show_desktop_notification(message: &str) -> Result<()> {
+    let error = |message: String| Error::Notification {
+        channel: "desktop",
+        message,
+    };
+    let mut cmd = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {} with title \"Weather alert\"",
+            serde_json::to_string(message)?
+        );
+        let mut cmd = tokio::process::Command::new("osascript");
+        cmd.args(["-e", &script]);
+        cmd
+    } else if cfg!(unix) {
+        let mut cmd = tokio::process::Command::new("notify-send");
+        cmd.args(["Weather alert", message]);
+        cmd
+    } else {
+        return Err(error(
+            "desktop notifications are not supported on this system".to_owned(),
+        ));
+    };
+    let status = cmd
+        .status()
+        .await
+        .map_err(|e| error(format!("couldn't show the notification: {e}")))?;
+    if !status.success() {
+        return Err(error(format!(
+            "showing the notification failed with {status}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::providers::sample_weather;
+
+    fn weather(hour: u32, wind_speed: f64, rain_volume: f64) -> Weather {
+        let mut weather = sample_weather();
+        weather.time = Utc.with_ymd_and_hms(2023, 3, 15, hour, 0, 0).unwrap();
+        weather.wind.speed = wind_speed;
+        weather.rain_volume = rain_volume;
+        weather.utc_offset = Some(3600);
+        weather
+    }
+
+    #[rstest]
+    #[case("wind.speed > 15 m/s", "wind.speed > 15 m/s")]
+    #[case("rain_volume>5mm", "rain_volume > 5 mm")]
+    #[case("temperature <= -5 °C", "temperature <= -5 °C")]
+    #[case("temperature < 20F", "temperature < 20 °F")]
+    #[case("wind.gust >= 40 knots", "wind.gust >= 40 kn")]
+    #[case("humidity = 100 %", "humidity = 100 %")]
+    #[case("visibility < 1 km", "visibility < 1 km")]
+    #[case("uv_index > 7", "uv_index > 7")]
+    fn test_parse_rule(#[case] rule: &str, #[case] expected: &str) {
+        assert_eq!(rule.parse::<Rule>().unwrap().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("wind.speed 15")]
+    #[case("wind > 15")]
+    #[case("wind.speed > fast")]
+    #[case("wind.speed > 15 mm")]
+    #[case("uv_index > 7 %")]
+    fn test_parse_invalid_rule(#[case] rule: &str) {
+        assert!(matches!(
+            rule.parse::<Rule>(),
+            Err(Error::InvalidAlertRule { .. })
+        ));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let rules: Vec<Rule> = ["wind.speed > 50 km/h", "rain_volume > 5mm"]
+            .iter()
+            .map(|x| x.parse().unwrap())
+            .collect();
+        let current = weather(12, 15.0, 1.0);
+        let forecast = [
+            weather(12, 20.0, 9.0),
+            weather(15, 10.0, 6.0),
+            weather(18, 10.0, 8.0),
+        ];
+        let alerts = evaluate(&rules, &current, &forecast);
+        let messages: Vec<_> = alerts.iter().map(|x| x.message("hq")).collect();
+        assert_eq!(
+            messages,
+            [
+                "hq: wind.speed > 50 km/h (54.0 km/h now)",
+                "hq: rain_volume > 5 mm (6.0 mm expected at 2023-03-15 16:00 +01:00)",
+            ]
+        );
+        assert_eq!(alerts[0].key(), "current: wind.speed > 50 km/h");
+        assert_eq!(alerts[1].key(), "forecast: rain_volume > 5 mm");
+
+        assert!(evaluate(&rules, &weather(12, 5.0, 0.0), &[]).is_empty());
+    }
+}
@@ -1,30 +1,217 @@
-use anyhow::bail;
+use std::{process::ExitCode, time::Duration};
+
 use clap::Parser;
-use directories::ProjectDirs;
 use weather_lib::{
-    cli::{Cli, Commands},
-    configure::configure,
+    alerts::{add_alert, check_alerts, list_alerts, remove_alert},
+    cache::{clear_cache, print_cache_stats},
+    cli::{
+        AlertsCommand, CacheCommand, Cli, Commands, ConfigCommand, PlacesCommand, ProvidersCommand,
+        UsageCommand,
+    },
+    compare::compare_weather,
+    config::{Config, Paths},
+    configure::{
+        configure, configure_cache_ttl, configure_fallback, configure_limits, configure_network,
+        configure_units, list_providers, order_providers,
+    },
+    error::Result,
+    forecast::get_forecast,
+    geocoding::locate,
     get::get_weather,
+    history::get_history,
+    metrics::export_metrics,
+    places::{add_place, configure_default_place, list_places, remove_place},
+    secrets::{decrypt_api_keys, encrypt_api_keys},
+    serve::serve,
+    settings::{
+        get_setting, list_provider_settings, print_config_paths, remove_provider, set_setting,
+        show_config, unset_setting,
+    },
+    tui::show_dashboard,
+    usage::print_usage,
+    watch::watch_weather,
 };
 
 #[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    let Some(proj_dirs) = ProjectDirs::from("com", "MyOrg",  "Weather") else {
-        bail!("couldn't access user config directory.");
-    };
+    match run(&cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            if let Some(hint) = e.hint() {
+                eprintln!("Hint: {hint}");
+            }
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
 
-    let config = proj_dirs.config_dir();
-    std::fs::create_dir_all(config)?;
-    let config_file = config.join("config.json");
+async fn run(cli: &Cli) -> Result<()> {
+    let paths = Paths::from_user_dirs(cli.config.as_deref(), cli.profile.as_deref())?;
+    Config::migrate_legacy(&paths)?;
+    let cache_dir = &paths.cache_dir;
 
     match &cli.command {
-        Commands::Configure { provider } => {
-            configure(provider, &config_file).await?;
+        Commands::Configure { provider, key } => {
+            configure(*provider, key, &paths).await?;
+        }
+        Commands::Get {
+            address,
+            output,
+            pick,
+            watch: Some(interval),
+            units,
+            provider,
+            ..
+        } => {
+            watch_weather(
+                address.as_ref(),
+                Duration::from_secs(*interval),
+                *output,
+                *pick,
+                units,
+                provider,
+                &paths,
+            )
+            .await?;
+        }
+        Commands::Get {
+            address,
+            date,
+            output,
+            pick,
+            watch: None,
+            units,
+            provider,
+        } => {
+            get_weather(
+                address.as_ref(),
+                date,
+                *output,
+                *pick,
+                units,
+                provider,
+                &paths,
+            )
+            .await?;
+        }
+        Commands::Locate { query, provider } => locate(query, provider, &paths).await?,
+        Commands::Forecast {
+            address,
+            days,
+            output,
+            units,
+            provider,
+        } => {
+            get_forecast(address.as_ref(), *days, *output, units, provider, &paths).await?;
+        }
+        Commands::History {
+            address,
+            from,
+            to,
+            step,
+            output,
+            units,
+            provider,
+        } => {
+            get_history(
+                address.as_ref(),
+                *from,
+                *to,
+                *step,
+                *output,
+                units,
+                provider,
+                &paths,
+            )
+            .await?;
+        }
+        Commands::Compare {
+            address,
+            date,
+            output,
+            units,
+            request,
+        } => {
+            compare_weather(address.as_ref(), date, *output, units, request, &paths).await?;
+        }
+        Commands::Config { command } => match command {
+            ConfigCommand::Show => show_config(&paths)?,
+            ConfigCommand::Get { key } => get_setting(key, &paths)?,
+            ConfigCommand::Set { key, value } => set_setting(key, value, &paths)?,
+            ConfigCommand::Unset { key } => unset_setting(key, &paths)?,
+            ConfigCommand::Path => print_config_paths(&paths),
+            ConfigCommand::ListProviders => list_provider_settings(&paths)?,
+            ConfigCommand::RemoveProvider { provider } => remove_provider(*provider, &paths)?,
+            ConfigCommand::EncryptKeys => encrypt_api_keys(&paths)?,
+            ConfigCommand::DecryptKeys => decrypt_api_keys(&paths)?,
+        },
+        Commands::Providers { command } => match command {
+            ProvidersCommand::List => list_providers(&paths)?,
+            ProvidersCommand::Order { providers } => order_providers(providers, &paths)?,
+            ProvidersCommand::Fallback { enabled } => configure_fallback(*enabled, &paths)?,
+        },
+        Commands::Places { command } => match command {
+            PlacesCommand::Add { name, location } => add_place(name, location, &paths)?,
+            PlacesCommand::List => list_places(&paths)?,
+            PlacesCommand::Remove { name } => remove_place(name, &paths)?,
+            PlacesCommand::Default { location } => {
+                configure_default_place(location.as_deref(), &paths)?
+            }
+        },
+        Commands::Alerts { command } => match command {
+            AlertsCommand::List => list_alerts(&paths)?,
+            AlertsCommand::Add { rule } => add_alert(rule, &paths)?,
+            AlertsCommand::Remove { rule } => remove_alert(rule, &paths)?,
+            AlertsCommand::Check {
+                places,
+                watch,
+                provider,
+            } => {
+                let watch = watch.map(Duration::from_secs);
+                check_alerts(places, watch, provider, &paths).await?;
+            }
+        },
+        Commands::Serve { bind, provider } => serve(*bind, provider, &paths).await?,
+        Commands::Exporter {
+            locations,
+            bind,
+            interval,
+            provider,
+        } => {
+            let interval = Duration::from_secs(*interval);
+            export_metrics(locations, *bind, interval, provider, &paths).await?;
+        }
+        Commands::Tui {
+            places,
+            provider,
+            units,
+            no_cache,
+            network,
+        } => {
+            show_dashboard(places, *provider, units, *no_cache, *network, &paths).await?;
+        }
+        Commands::Network { settings } => {
+            configure_network(settings, &paths)?;
         }
-        Commands::Get { address, date } => {
-            get_weather(address, date, &config_file).await?;
+        Commands::Usage { command } => match command {
+            None => print_usage(&paths)?,
+            Some(UsageCommand::Limit { provider, limits }) => {
+                configure_limits(*provider, limits, &paths)?
+            }
+        },
+        Commands::Cache { command } => match command {
+            CacheCommand::Clear => clear_cache(cache_dir)?,
+            CacheCommand::Stats => {
+                let ttl = Config::load_or_default(&paths)?.cache_ttl();
+                print_cache_stats(cache_dir, ttl)?;
+            }
+            CacheCommand::Ttl { seconds } => configure_cache_ttl(*seconds, &paths)?,
+        },
+        Commands::Units { settings } => {
+            configure_units(settings, &paths)?;
         }
     }
 
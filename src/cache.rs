@@ -0,0 +1,422 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    error::Result,
+    location::Location,
+    metrics,
+    providers::{Place, Weather, WeatherProvider},
+};
+
+/// The default time to live of cached current weather and forecasts.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Historical weather of the last day may still be updated by the provider, so it is only cached
+/// permanently once it is older than this.
+const HISTORY_SETTLED_AFTER: chrono::Duration = chrono::Duration::days(1);
+
+/// The number of writes after which the expired entries are removed again.
+const PRUNE_EVERY: u64 = 100;
+
+/// An on-disk cache of provider responses.
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+/// A cached value, stored as one JSON file per key.
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    /// The full key, stored to detect hash collisions.
+    key: String,
+    stored_at: DateTime<Utc>,
+    /// `None` if the entry never expires.
+    expires_at: Option<DateTime<Utc>>,
+    data: T,
+}
+
+impl Cache {
+    /// Creates a cache storing entries in `dir`, with entries for current weather and forecasts
+    /// expiring after `ttl`.
+    pub fn new(dir: &Path, ttl: Duration) -> Self {
+        Self {
+            dir: dir.to_owned(),
+            ttl,
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", fnv1a(key.as_bytes())))
+    }
+
+    /// Returns the cached value for `key`, or `None` if there is no valid entry.
+    ///
+    /// Unreadable or expired entries are treated as missing.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let file = std::fs::File::open(self.path(key)).ok()?;
+        let entry: Entry<T> = serde_json::from_reader(std::io::BufReader::new(file)).ok()?;
+        if entry.key != key || entry.expires_at.is_some_and(|x| x <= Utc::now()) {
+            return None;
+        }
+        Some(entry.data)
+    }
+
+    /// Stores `data` under `key`. The entry expires after the cache's time to live, or never if
+    /// `permanent` is set. The expired entries are removed on the first write of the process and
+    /// every `PRUNE_EVERY` writes after it, so that a long running server doesn't fill the disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry cannot be serialized or written.
+    pub fn put<T: Serialize>(&self, key: &str, data: &T, permanent: bool) -> Result<()> {
+        let stored_at = Utc::now();
+        let expires_at = (!permanent)
+            .then(|| chrono::Duration::from_std(self.ttl).ok())
+            .flatten()
+            .map(|ttl| stored_at + ttl);
+        let entry = Entry {
+            key: key.to_owned(),
+            stored_at,
+            expires_at,
+            data,
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        // write to a temporary file first so concurrent readers never see a partial entry, unique
+        // to the call so that concurrent writers of the same key never share one
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let path = self.path(key);
+        let write = WRITES.fetch_add(1, Ordering::Relaxed);
+        let tmp = path.with_extension(format!("{}-{write}.tmp", std::process::id()));
+        std::fs::write(&tmp, serde_json::to_vec(&entry)?)?;
+        std::fs::rename(tmp, path)?;
+        if write.is_multiple_of(PRUNE_EVERY) {
+            // the entry is stored, so failing to remove others doesn't fail the write
+            let _ = self.prune();
+        }
+        Ok(())
+    }
+
+    /// Removes the expired entries.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of removed entries.
+    pub fn prune(&self) -> Result<usize> {
+        let now = Utc::now();
+        let mut removed = 0;
+        for path in self.entry_files()? {
+            let expired = read_expiry(&path).is_some_and(|x| x.is_some_and(|x| x <= now));
+            // another process may have removed or replaced the entry meanwhile
+            if expired && std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Removes all entries.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of removed entries.
+    pub fn clear(&self) -> Result<usize> {
+        let mut removed = 0;
+        for path in self.entry_files()? {
+            std::fs::remove_file(path)?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Collects statistics about the stored entries.
+    pub fn stats(&self) -> Result<CacheStats> {
+        let now = Utc::now();
+        let mut stats = CacheStats::default();
+        for path in self.entry_files()? {
+            stats.entries += 1;
+            stats.bytes += std::fs::metadata(&path)?.len();
+            match read_expiry(&path) {
+                Some(None) => stats.permanent += 1,
+                Some(Some(expires_at)) if expires_at <= now => stats.expired += 1,
+                Some(Some(_)) => {}
+                None => stats.corrupt += 1,
+            }
+        }
+        Ok(stats)
+    }
+
+    fn entry_files(&self) -> Result<Vec<PathBuf>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|x| x == "json") {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Returns when the entry stored in `path` expires, `Some(None)` if never, or `None` if it cannot be
+/// read.
+fn read_expiry(path: &Path) -> Option<Option<DateTime<Utc>>> {
+    let file = std::fs::File::open(path).ok()?;
+    let entry: Entry<serde::de::IgnoredAny> =
+        serde_json::from_reader(std::io::BufReader::new(file)).ok()?;
+    Some(entry.expires_at)
+}
+
+/// Statistics about the entries stored in a `Cache`.
+#[derive(Debug, Default, PartialEq)]
+pub struct CacheStats {
+    /// The number of entries.
+    pub entries: usize,
+    /// The number of entries that never expire.
+    pub permanent: usize,
+    /// The number of entries that have expired but were not removed yet.
+    pub expired: usize,
+    /// The number of entries that could not be read.
+    pub corrupt: usize,
+    /// The total size of the entries in bytes.
+    pub bytes: u64,
+}
+
+/// Removes all cached responses and prints how many were removed.
+///
+/// # Arguments
+///
+/// * `cache_dir` - A `Path` representing the directory responses are cached in.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn clear_cache(cache_dir: &Path) -> Result<()> {
+    let removed = Cache::new(cache_dir, DEFAULT_TTL).clear()?;
+    println!("Removed {removed} cached responses.");
+    Ok(())
+}
+
+/// Prints statistics about the cached responses.
+///
+/// # Arguments
+///
+/// * `cache_dir` - A `Path` representing the directory responses are cached in.
+/// * `ttl` - The configured time to live of cached current weather and forecasts.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn print_cache_stats(cache_dir: &Path, ttl: Duration) -> Result<()> {
+    let stats = Cache::new(cache_dir, ttl).stats()?;
+    println!("directory: {}", cache_dir.display());
+    println!("time to live: {} s", ttl.as_secs());
+    println!("entries: {}", stats.entries);
+    println!("permanent: {}", stats.permanent);
+    println!("expired: {}", stats.expired);
+    if stats.corrupt > 0 {
+        println!("unreadable: {}", stats.corrupt);
+    }
+    println!("size: {:.1} KiB", stats.bytes as f64 / 1024.);
+    Ok(())
+}
+
+/// 64-bit FNV-1a hash, used for file names as it is stable across builds unlike `DefaultHasher`.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// A weather provider that serves responses from a `Cache` when possible, and stores the
+/// responses of the wrapped provider otherwise.
+pub struct CachedProvider {
+    inner: Box<dyn WeatherProvider>,
+    name: &'static str,
+    /// The base URLs of the provider, e.g. of a proxy or a mock server.
+    endpoint: String,
+    cache: Cache,
+}
+
+impl CachedProvider {
+    /// Wraps `inner`, using `name` and `endpoint`, the base URLs requests are sent to, to tell
+    /// apart entries of different providers and servers.
+    pub fn new(
+        inner: Box<dyn WeatherProvider>,
+        name: &'static str,
+        endpoint: String,
+        cache: Cache,
+    ) -> Self {
+        Self {
+            inner,
+            name,
+            endpoint,
+            cache,
+        }
+    }
+
+    fn key(&self, kind: &str, location: &Location, suffix: &str) -> String {
+        format!("{}/{}/{kind}/{location}/{suffix}", self.name, self.endpoint)
+    }
+
+    /// Looks up `key` in the cache, counting the hit or miss in the metrics.
+    fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.cache.get(key);
+        metrics::record_cache_lookup(self.name, value.is_some());
+        value
+    }
+
+    /// Stores a response in the cache. The response is valid whether or not it can be stored, so
+    /// failures, e.g. of a read-only or full cache directory, are only reported.
+    fn put<T: Serialize>(&self, key: &str, data: &T, permanent: bool) {
+        if let Err(e) = self.cache.put(key, data, permanent) {
+            eprintln!("Couldn't write the response cache: {e}");
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for CachedProvider {
+    async fn get_weather(&self, location: &Location) -> Result<Weather> {
+        let key = self.key("current", location, "");
+        if let Some(weather) = self.get(&key) {
+            return Ok(weather);
+        }
+        let weather = self.inner.get_weather(location).await?;
+        self.put(&key, &weather, false);
+        Ok(weather)
+    }
+
+    async fn get_history_weather(
+        &self,
+        location: &Location,
+        date: DateTime<Utc>,
+    ) -> Result<Weather> {
+        let key = self.key("history", location, &date.to_rfc3339());
+        if let Some(weather) = self.get(&key) {
+            return Ok(weather);
+        }
+        let weather = self.inner.get_history_weather(location, date).await?;
+        let permanent = date + HISTORY_SETTLED_AFTER < Utc::now();
+        self.put(&key, &weather, permanent);
+        Ok(weather)
+    }
+
+    async fn get_history_range(
+        &self,
+        location: &Location,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Weather>> {
+        let suffix = format!("{}/{}", from.to_rfc3339(), to.to_rfc3339());
+        let key = self.key("history-range", location, &suffix);
+        if let Some(series) = self.get(&key) {
+            return Ok(series);
+        }
+        let series = self.inner.get_history_range(location, from, to).await?;
+        let permanent = to + HISTORY_SETTLED_AFTER < Utc::now();
+        self.put(&key, &series, permanent);
+        Ok(series)
+    }
+
+    async fn get_forecast(&self, location: &Location, days: u32) -> Result<Vec<Weather>> {
+        let key = self.key("forecast", location, &days.to_string());
+        if let Some(forecast) = self.get(&key) {
+            return Ok(forecast);
+        }
+        let forecast = self.inner.get_forecast(location, days).await?;
+        self.put(&key, &forecast, false);
+        Ok(forecast)
+    }
+
+    async fn search_places(&self, query: &str) -> Result<Vec<Place>> {
+        // places don't move, so they are kept until the cache is cleared
+        let key = format!(
+            "{}/{}/places/{}",
+            self.name,
+            self.endpoint,
+            query.to_lowercase()
+        );
+        if let Some(places) = self.get(&key) {
+            return Ok(places);
+        }
+        let places = self.inner.search_places(query).await?;
+        self.put(&key, &places, true);
+        Ok(places)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a cache in a new temporary directory, removed when the directory is dropped.
+    fn temp_cache(ttl: Duration) -> (tempfile::TempDir, Cache) {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path(), ttl);
+        (dir, cache)
+    }
+
+    #[test]
+    fn test_put_get() {
+        let (_dir, cache) = temp_cache(DEFAULT_TTL);
+        assert_eq!(cache.get::<i32>("a"), None);
+        cache.put("a", &1, false).unwrap();
+        cache.put("b", &2, true).unwrap();
+        assert_eq!(cache.get::<i32>("a"), Some(1));
+        assert_eq!(cache.get::<i32>("b"), Some(2));
+
+        let stats = cache.stats().unwrap();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.permanent, 1);
+        assert_eq!(cache.clear().unwrap(), 2);
+        assert_eq!(cache.get::<i32>("a"), None);
+    }
+
+    #[test]
+    fn test_expired() {
+        let (_dir, cache) = temp_cache(Duration::ZERO);
+        cache.put("a", &1, false).unwrap();
+        assert_eq!(cache.get::<i32>("a"), None);
+        assert_eq!(cache.stats().unwrap().expired, 1);
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_prune() {
+        let (_dir, cache) = temp_cache(Duration::ZERO);
+        cache.put("a", &1, false).unwrap();
+        cache.put("b", &2, true).unwrap();
+        assert_eq!(cache.prune().unwrap(), 1);
+        assert_eq!(cache.get::<i32>("b"), Some(2));
+        assert_eq!(cache.stats().unwrap().entries, 1);
+    }
+
+    #[test]
+    fn test_concurrent_put() {
+        let (dir, cache) = temp_cache(DEFAULT_TTL);
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        cache.put("a", &vec![i; 1000], false).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(cache.get::<Vec<i32>>("a").unwrap().len(), 1000);
+        // no temporary file is left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
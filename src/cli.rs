@@ -1,12 +1,25 @@
+use std::path::PathBuf;
+
 use chrono::{DateTime, NaiveDateTime, Utc};
-use clap::{command, Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-use crate::error;
+use crate::{
+    alerts::Rule, error, history::Step, location::Location, network::NetworkSettings,
+    output::OutputFormat, units::UnitSettings, usage::Limits,
+};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 pub struct Cli {
+    /// Configuration file read after all others, and written instead of the user's
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Profile of the configuration files to use
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -15,34 +28,393 @@ pub struct Cli {
 pub enum Commands {
     /// Prints weather
     Get {
-        /// City name
-        address: String,
+        /// Location: a city name, "<lat>,<lon>", "zip:<postal code>" or "id:<provider id>". The
+        /// configured default location if not given
+        address: Option<Location>,
 
         /// Date of the weather data. Must have the value of either "now" or a datetime string.
         /// Format: "%Y-%m-%d %H:%M:%S"
         #[arg(default_value = "now")]
         #[arg(value_parser = parse_date)]
         date: DateVariant,
+
+        /// Output format, the configured one or text by default
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+
+        /// Pick the place among those matching the city name: the number N as listed by
+        /// `locate`, or asked for without N
+        #[arg(long, value_name = "N", num_args = 0..=1)]
+        pick: Option<Option<usize>>,
+
+        /// Fetch the current weather every SECONDS, redrawing it and highlighting the changes,
+        /// until Ctrl-C is pressed
+        #[arg(long, value_name = "SECONDS", conflicts_with = "date")]
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        watch: Option<u64>,
+
+        #[command(flatten)]
+        units: UnitSettings,
+
+        #[command(flatten)]
+        provider: ProviderArgs,
+    },
+    /// Lists the places matching a name, with their region, country and coordinates
+    Locate {
+        /// Name of the place, optionally followed by the region or country, e.g. "London, CA"
+        query: String,
+
+        #[command(flatten)]
+        provider: ProviderArgs,
+    },
+    /// Prints weather forecast
+    Forecast {
+        /// Location: a city name, "<lat>,<lon>", "zip:<postal code>" or "id:<provider id>". The
+        /// configured default location if not given
+        address: Option<Location>,
+
+        /// Number of days to forecast, starting from today
+        #[arg(long, default_value_t = 3)]
+        #[arg(value_parser = clap::value_parser!(u32).range(1..=14))]
+        days: u32,
+
+        /// Output format, the configured one or text by default
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+
+        #[command(flatten)]
+        units: UnitSettings,
+
+        #[command(flatten)]
+        provider: ProviderArgs,
     },
     /// Configure provider
-    Configure { provider: Provider },
+    Configure {
+        provider: Provider,
+
+        #[command(flatten)]
+        key: ApiKeyArgs,
+    },
+    /// Get the historical weather between two dates
+    History {
+        /// Location: a city name, "<lat>,<lon>", "zip:<postal code>" or "id:<provider id>". The
+        /// configured default location if not given
+        address: Option<Location>,
+
+        /// Start of the range. Format: "%Y-%m-%d %H:%M:%S"
+        #[arg(long, value_parser = parse_datetime)]
+        from: DateTime<Utc>,
+
+        /// End of the range, now by default. Format: "%Y-%m-%d %H:%M:%S"
+        #[arg(long, value_parser = parse_datetime)]
+        to: Option<DateTime<Utc>>,
+
+        /// Time between the entries of the series
+        #[arg(long, value_enum, default_value_t)]
+        step: Step,
+
+        /// Output format, the configured one or text by default
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+
+        #[command(flatten)]
+        units: UnitSettings,
+
+        #[command(flatten)]
+        provider: ProviderArgs,
+    },
+    /// Compare the weather reported by all configured providers
+    Compare {
+        /// Location: a city name, "<lat>,<lon>", "zip:<postal code>" or "id:<provider id>". The
+        /// configured default location if not given
+        address: Option<Location>,
+
+        /// Date of the weather data. Must have the value of either "now" or a datetime string.
+        /// Format: "%Y-%m-%d %H:%M:%S"
+        #[arg(default_value = "now")]
+        #[arg(value_parser = parse_date)]
+        date: DateVariant,
+
+        /// Output format, the configured one or text by default
+        #[arg(short, long, value_enum)]
+        output: Option<OutputFormat>,
+
+        #[command(flatten)]
+        units: UnitSettings,
+
+        #[command(flatten)]
+        request: RequestArgs,
+    },
+    /// Show or change the settings of the configuration files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Manage the configured providers
+    Providers {
+        #[command(subcommand)]
+        command: ProvidersCommand,
+    },
+    /// Manage the saved places, whose names can be given instead of a location
+    Places {
+        #[command(subcommand)]
+        command: PlacesCommand,
+    },
+    /// Manage the alert rules, and check them against the weather of the saved places
+    Alerts {
+        #[command(subcommand)]
+        command: AlertsCommand,
+    },
+    /// Serves the current weather, history and forecasts as JSON over HTTP
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        bind: std::net::SocketAddr,
+
+        #[command(flatten)]
+        provider: ProviderArgs,
+    },
+    /// Polls the current weather of locations and serves it as Prometheus metrics
+    Exporter {
+        /// Saved places or locations to poll, labelled as given. All saved places if none are
+        /// given, or the default location if there are none
+        locations: Vec<String>,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:9101")]
+        bind: std::net::SocketAddr,
+
+        /// Seconds between two polls
+        #[arg(long, default_value_t = 300)]
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+
+        #[command(flatten)]
+        provider: ProviderArgs,
+    },
+    /// Shows a full screen dashboard of the weather of several locations
+    Tui {
+        /// Saved places or locations to show, labelled as given. All saved places if none are
+        /// given, or the default location if there are none
+        places: Vec<String>,
+
+        /// Provider shown first instead of the preferred one
+        #[arg(long, value_enum)]
+        provider: Option<Provider>,
+
+        #[command(flatten)]
+        units: UnitSettings,
+
+        /// Don't read or write the response cache
+        #[arg(long)]
+        no_cache: bool,
+
+        #[command(flatten)]
+        network: NetworkSettings,
+    },
+    /// Print the default network settings, or save them if any are given
+    Network {
+        #[command(flatten)]
+        settings: NetworkSettings,
+    },
+    /// Prints the calls made with the key of each provider, or manages their limits
+    Usage {
+        #[command(subcommand)]
+        command: Option<UsageCommand>,
+    },
+    /// Manage the response cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Prints the default units, or sets them if any units are given
+    Units {
+        #[command(flatten)]
+        settings: UnitSettings,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Prints the settings merged from all configuration files, with the API keys masked
+    Show,
+    /// Prints a setting, e.g. `units.system` or `providers.openweather.base_url`
+    Get { key: String },
+    /// Sets a setting in the written configuration file
+    Set { key: String, value: String },
+    /// Removes a setting from the written configuration file
+    Unset { key: String },
+    /// Prints the configuration files read and the one written
+    Path,
+    /// Lists the configured providers with their masked API keys and base URLs
+    ListProviders,
+    /// Removes a provider from the written configuration file
+    RemoveProvider { provider: Provider },
+    /// Encrypts the API keys stored in the written configuration file with a passphrase, read
+    /// from WEATHER_PASSPHRASE or asked for
+    EncryptKeys,
+    /// Decrypts the API keys stored in the written configuration file
+    DecryptKeys,
+}
+
+#[derive(Subcommand)]
+pub enum PlacesCommand {
+    /// Saves a place under a name, replacing the place saved under the same name
+    Add {
+        /// Name of the place, e.g. "hq"
+        name: String,
+        /// Location: a city name, "<lat>,<lon>", "zip:<postal code>" or "id:<provider id>"
+        location: Location,
+    },
+    /// Lists the saved places with their location
+    List,
+    /// Removes a saved place
+    Remove { name: String },
+    /// Prints the default location, or sets it to a saved place or location if given
+    Default { location: Option<String> },
+}
+
+#[derive(Subcommand)]
+pub enum AlertsCommand {
+    /// Lists the alert rules, and where alerts are sent
+    List,
+    /// Adds an alert rule
+    Add {
+        /// Rule: "<FIELD> <OPERATOR> <VALUE> [UNIT]", e.g. "wind.speed > 15 m/s" or
+        /// "rain_volume > 5mm". The fields are named like the CSV columns, the operators are
+        /// `>`, `>=`, `<`, `<=` and `=`, and the value is in the default unit of the field, °C,
+        /// m/s, mm, m, % or hPa, unless a unit is given
+        rule: Rule,
+    },
+    /// Removes an alert rule
+    Remove { rule: Rule },
+    /// Checks the rules against the current weather and forecast of places, and sends the alerts
+    /// not sent since their rule was last met
+    Check {
+        /// Saved places or locations to check. All saved places if none are given, or the
+        /// default location if there are none
+        places: Vec<String>,
+
+        /// Check again every SECONDS until Ctrl-C is pressed
+        #[arg(long, value_name = "SECONDS")]
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        watch: Option<u64>,
+
+        #[command(flatten)]
+        provider: ProviderArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProvidersCommand {
+    /// Lists the configured providers in the order of preference
+    List,
+    /// Moves the given providers to the front of the order of preference
+    Order {
+        /// Providers in the order of preference
+        #[arg(required = true)]
+        providers: Vec<Provider>,
+    },
+    /// Prints whether falling back to the next provider is enabled, or sets it if given
+    Fallback {
+        #[arg(value_parser = clap::builder::BoolishValueParser::new())]
+        enabled: Option<bool>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum UsageCommand {
+    /// Prints the usage limits of the key of a provider, or sets them if any are given. A limit
+    /// of 0 removes it
+    Limit {
+        provider: Provider,
+
+        #[command(flatten)]
+        limits: Limits,
+    },
+}
+
+/// Arguments giving the API key of a provider. Without them, the key is read from the
+/// provider's environment variable, or from stdin.
+#[derive(Args)]
+pub struct ApiKeyArgs {
+    /// API key of the provider
+    #[arg(long, conflicts_with = "api_key_file")]
+    pub api_key: Option<String>,
+
+    /// File to read the API key of the provider from
+    #[arg(long, value_name = "FILE")]
+    pub api_key_file: Option<PathBuf>,
+
+    /// Save the key without checking it with a test request
+    #[arg(long)]
+    pub no_validate: bool,
+}
+
+/// Arguments selecting the provider weather data is requested from.
+#[derive(Args)]
+pub struct ProviderArgs {
+    /// Provider to use instead of the preferred one
+    #[arg(long, value_enum)]
+    pub provider: Option<Provider>,
+
+    /// Ask the next provider if the preferred one fails
+    #[arg(long, conflicts_with = "provider")]
+    pub fallback: bool,
+
+    /// Ask all providers concurrently and merge their responses
+    #[arg(long, conflicts_with_all = ["provider", "fallback"])]
+    pub aggregate: bool,
+
+    #[command(flatten)]
+    pub request: RequestArgs,
+}
+
+/// Arguments controlling how requests are sent to the providers.
+#[derive(Args)]
+pub struct RequestArgs {
+    /// Don't read or write the response cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Print details of the requests sent to the providers, such as retries
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    #[command(flatten)]
+    pub network: NetworkSettings,
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// Removes all cached responses
+    Clear,
+    /// Prints statistics about the cached responses
+    Stats,
+    /// Prints the time to live of cached current weather and forecasts, or sets it if given
+    Ttl {
+        /// Time to live in seconds
+        seconds: Option<u64>,
+    },
 }
 
 fn parse_date(arg: &str) -> error::Result<DateVariant> {
     let variant = match arg {
         "now" => DateVariant::Now,
-        s => DateVariant::HistoryDate(
-            NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")?
-                .and_local_timezone(Utc)
-                .latest()
-                .ok_or(error::Error::InvalidTimezoneTime)?,
-        ),
+        s => DateVariant::HistoryDate(parse_datetime(s)?),
     };
     Ok(variant)
 }
 
+pub(crate) fn parse_datetime(arg: &str) -> error::Result<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(arg, "%Y-%m-%d %H:%M:%S")?
+        .and_local_timezone(Utc)
+        .latest()
+        .ok_or(error::Error::InvalidTimezoneTime)
+}
+
 /// Enum representing weather service providers.
-#[derive(Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
 pub enum Provider {
     /// OpenWeather provider.
     OpenWeather,
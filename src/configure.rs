@@ -1,47 +1,273 @@
-use std::path::Path;
+use crate::{
+    cli::{ApiKeyArgs, Provider},
+    config::{Config, Paths},
+    error::{Error, Result},
+    location::Location,
+    network::NetworkSettings,
+    providers::{api_key_var, name, ProviderUserInfo},
+    secrets,
+    units::UnitSettings,
+    usage::Limits,
+};
 
-use crate::{cli::Provider, error::Result, providers::ProviderUserInfo};
-
-/// Configures the specified weather provider by saving user information to a configuration file.
+/// Configures the specified weather provider by saving its API key to a configuration file.
+///
+/// The key is taken from the command line arguments, the provider's environment variable or
+/// stdin, in this order, and checked with a test request unless disabled. Only the key of the
+/// written configuration file's entry changes, so reconfiguring a provider keeps its base URLs
+/// and limits, and settings from the other files or the environment aren't copied into it. If
+/// the keys stored in the written configuration file are encrypted, the new key is encrypted
+/// with the same passphrase.
 ///
 /// # Arguments
 ///
 /// * `provider` - A `Provider` enum representing the weather provider to be configured.
-/// * `config_file` - A `Path` representing the path to the configuration file to be written.
+/// * `key` - An `ApiKeyArgs` representing how the key was given on the command line.
+/// * `paths` - The `Paths` of the configuration files and the usage counters.
 ///
 /// # Returns
 ///
 /// A `Result` indicating whether the operation was successful.
-pub async fn configure(provider: &Provider, config_file: &Path) -> Result<()> {
-    match provider {
-        Provider::OpenWeather => {
-            openweather(config_file)?;
-        }
-        Provider::WeatherApi => weatherapi(config_file)?,
+///
+/// # Errors
+///
+/// Returns an error if no key is given, or the test request fails.
+pub async fn configure(provider: Provider, key: &ApiKeyArgs, paths: &Paths) -> Result<()> {
+    let api_key = read_api_key(provider, key)?;
+
+    if !key.no_validate {
+        // checked with the settings in effect, which may come from other files or the environment
+        let config = Config::load_or_default(paths)?;
+        let mut info = match config.provider(provider) {
+            Ok(existing) => existing.clone(),
+            Err(_) => ProviderUserInfo::new(provider, String::new()),
+        };
+        info.set_api_key(api_key.clone());
+        let policy = config.network.resolve(false);
+        info.build_provider(policy, Some(&paths.usage_file()))
+            .get_weather(&Location::City(VALIDATION_CITY.to_owned()))
+            .await?;
+        println!("Key checked successfully.");
     }
+
+    // only the key of the written file's entry changes, its other settings are kept
+    Config::update(paths, |config| {
+        let api_key = secrets::protect_api_key(&config.providers, api_key)?;
+        match config.providers.iter_mut().find(|x| x.kind() == provider) {
+            Some(existing) => existing.set_api_key(api_key),
+            None => config
+                .providers
+                .push(ProviderUserInfo::new(provider, api_key)),
+        }
+        Ok(())
+    })?;
     println!("Key saved successfully.");
 
     Ok(())
 }
 
-fn openweather(config_file: &Path) -> Result<()> {
-    println!("OpenWeather api key:");
-    let mut str = String::new();
-    std::io::stdin().read_line(&mut str)?;
-    let serialized = serde_json::to_string(&ProviderUserInfo::OpenWeather {
-        api_key: str.trim().to_owned(),
-    })?;
-    std::fs::write(config_file, serialized)?;
+/// The city the weather is requested for to check a key.
+const VALIDATION_CITY: &str = "London";
+
+fn read_api_key(provider: Provider, key: &ApiKeyArgs) -> Result<String> {
+    let api_key = if let Some(api_key) = &key.api_key {
+        api_key.clone()
+    } else if let Some(file) = &key.api_key_file {
+        std::fs::read_to_string(file)?
+    } else if let Ok(api_key) = std::env::var(api_key_var(provider)) {
+        api_key
+    } else {
+        match provider {
+            Provider::OpenWeather => println!("OpenWeather api key:"),
+            Provider::WeatherApi => println!("Weather API api key:"),
+        }
+        let mut str = String::new();
+        std::io::stdin().read_line(&mut str)?;
+        str
+    };
+
+    let api_key = api_key.trim();
+    if api_key.is_empty() {
+        return Err(Error::MissingApiKey(name(provider)));
+    }
+    Ok(api_key.to_owned())
+}
+
+/// Prints the configured providers in the order of preference.
+///
+/// # Arguments
+///
+/// * `paths` - The `Paths` of the configuration files.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn list_providers(paths: &Paths) -> Result<()> {
+    let config = Config::load_or_default(paths)?;
+    for (i, provider) in config.providers.iter().enumerate() {
+        println!("{}. {}", i + 1, provider.name());
+    }
     Ok(())
 }
 
-fn weatherapi(config_file: &Path) -> Result<()> {
-    println!("Weather API api key:");
-    let mut str = String::new();
-    std::io::stdin().read_line(&mut str)?;
-    let serialized = serde_json::to_string(&ProviderUserInfo::WeatherApi {
-        api_key: str.trim().to_owned(),
-    })?;
-    std::fs::write(config_file, serialized)?;
+/// Moves the given providers to the front of the order of preference.
+///
+/// # Arguments
+///
+/// * `providers` - The providers in the order of preference.
+/// * `paths` - The `Paths` of the configuration files, and the one to be written.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn order_providers(providers: &[Provider], paths: &Paths) -> Result<()> {
+    Config::update(paths, |config| config.reorder_providers(providers))?;
+    println!("Provider order saved successfully.");
+    list_providers(paths)
+}
+
+/// Enables or disables falling back to the next provider, or prints whether it is enabled if no
+/// value is given.
+///
+/// # Arguments
+///
+/// * `enabled` - Whether to fall back to the next provider.
+/// * `paths` - The `Paths` of the configuration files, and the one to be written.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn configure_fallback(enabled: Option<bool>, paths: &Paths) -> Result<()> {
+    if let Some(enabled) = enabled {
+        Config::update(paths, |config| {
+            config.fallback = Some(enabled);
+            Ok(())
+        })?;
+        println!("Fallback saved successfully.");
+    }
+    let config = Config::load_or_default(paths)?;
+    println!("fallback: {}", if config.fallback() { "on" } else { "off" });
+    Ok(())
+}
+
+/// Sets the default units, or prints them if no units are given.
+///
+/// # Arguments
+///
+/// * `settings` - A `UnitSettings` representing the units to be merged into the saved defaults.
+/// * `paths` - The `Paths` of the configuration files, and the one to be written.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn configure_units(settings: &UnitSettings, paths: &Paths) -> Result<()> {
+    if !settings.is_empty() {
+        Config::update(paths, |config| {
+            config.units = config.units.merge(*settings);
+            Ok(())
+        })?;
+        println!("Units saved successfully.");
+    }
+
+    let units = Config::load_or_default(paths)?.units.resolve();
+    println!("temperature: {}", units.temperature);
+    println!("wind speed: {}", units.wind_speed);
+    println!("precipitation: {}", units.precipitation);
+    println!("visibility: {}", units.visibility);
+    Ok(())
+}
+
+/// Sets the default network settings, or prints them if no settings are given.
+///
+/// # Arguments
+///
+/// * `settings` - A `NetworkSettings` representing the settings to be merged into the saved defaults.
+/// * `paths` - The `Paths` of the configuration files, and the one to be written.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn configure_network(settings: &NetworkSettings, paths: &Paths) -> Result<()> {
+    if !settings.is_empty() {
+        Config::update(paths, |config| {
+            config.network = config.network.merge(*settings);
+            Ok(())
+        })?;
+        println!("Network settings saved successfully.");
+    }
+
+    let policy = Config::load_or_default(paths)?.network.resolve(false);
+    println!("connect timeout: {} s", policy.connect_timeout.as_secs());
+    println!("read timeout: {} s", policy.read_timeout.as_secs());
+    println!("retries: {}", policy.retries);
+    Ok(())
+}
+
+/// Sets the time to live of cached current weather and forecasts, or prints it if no value is
+/// given.
+///
+/// # Arguments
+///
+/// * `seconds` - The time to live in seconds.
+/// * `paths` - The `Paths` of the configuration files, and the one to be written.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn configure_cache_ttl(seconds: Option<u64>, paths: &Paths) -> Result<()> {
+    if let Some(seconds) = seconds {
+        Config::update(paths, |config| {
+            config.cache_ttl = Some(seconds);
+            Ok(())
+        })?;
+        println!("Cache time to live saved successfully.");
+    }
+    let ttl = Config::load_or_default(paths)?.cache_ttl();
+    println!("time to live: {} s", ttl.as_secs());
+    Ok(())
+}
+
+/// Sets the usage limits of the key of a configured provider, or prints them if no limits are
+/// given.
+///
+/// # Arguments
+///
+/// * `provider` - The `Provider` whose key the limits apply to.
+/// * `limits` - A `Limits` representing the limits to be merged into the saved limits, a limit of
+///   0 removes it.
+/// * `paths` - The `Paths` of the configuration files, and the one to be written.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if limits are given and the provider is not configured in the written file,
+/// or if the provider is not configured at all.
+pub fn configure_limits(provider: Provider, limits: &Limits, paths: &Paths) -> Result<()> {
+    if !limits.is_empty() {
+        // only the limits are changed, the key and URLs of the entry may come from other files
+        // or the environment and are left out of the written file
+        Config::update(paths, |config| {
+            let info = config
+                .providers
+                .iter_mut()
+                .find(|x| x.kind() == provider)
+                .ok_or_else(|| Error::InvalidSetting {
+                    key: format!("providers.{}", name(provider)),
+                    message: "not set in the written configuration file".to_owned(),
+                })?;
+            info.set_limits(info.limits().merge(*limits));
+            Ok(())
+        })?;
+        println!("Limits saved successfully.");
+    }
+
+    let limits = Config::load(paths)?.provider(provider)?.limits();
+    let format = |limit: Option<u32>| limit.map_or("none".to_owned(), |x| x.to_string());
+    println!("per minute: {}", format(limits.per_minute));
+    println!("per day: {}", format(limits.per_day));
+    println!("per month: {}", format(limits.per_month));
     Ok(())
 }
@@ -1,20 +1,240 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
 /// Enum representing errors that may occur during the weather CLI's execution.
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("request error")]
-    Request(#[from] reqwest::Error),
-    #[error("io error")]
+    #[error("request failed: {0}")]
+    Request(reqwest::Error),
+    #[error("{provider} rejected the API key: {message}")]
+    InvalidApiKey {
+        provider: &'static str,
+        message: String,
+    },
+    #[error("{provider} doesn't know the location: {message}")]
+    UnknownLocation {
+        provider: &'static str,
+        message: String,
+    },
+    #[error("{provider} quota exceeded: {message}")]
+    QuotaExceeded {
+        provider: &'static str,
+        message: String,
+    },
+    #[error("{provider} is unavailable (HTTP {status}): {message}")]
+    ProviderUnavailable {
+        provider: &'static str,
+        status: u16,
+        message: String,
+    },
+    #[error("{provider} usage limit of {limit} calls per {period} reached")]
+    UsageLimitReached {
+        provider: &'static str,
+        period: &'static str,
+        limit: u32,
+    },
+    #[error("{provider} didn't respond within {} s", .timeout.as_secs())]
+    Timeout {
+        provider: &'static str,
+        timeout: std::time::Duration,
+    },
+    #[error("{provider} returned an error (HTTP {status}): {message}")]
+    Provider {
+        provider: &'static str,
+        status: u16,
+        message: String,
+    },
+    #[error("io error: {0}")]
     File(#[from] std::io::Error),
-    #[error("serialization error")]
+    #[error("serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
-    #[error("date time parse error")]
+    #[error("no configuration file at {}", .0.display())]
+    ConfigNotFound(PathBuf),
+    #[error("serialization error: {0}")]
+    TomlSerialization(#[from] toml::ser::Error),
+    #[error("corrupt configuration file {}: {message}", .path.display())]
+    CorruptConfig { path: PathBuf, message: String },
+    #[error("no profile named {0} in the configuration files")]
+    UnknownProfile(String),
+    #[error("couldn't access the user config directory")]
+    NoConfigDirectory,
+    #[error("server error on {addr}: {message}")]
+    Server {
+        addr: std::net::SocketAddr,
+        message: String,
+    },
+    #[error("invalid date: {0}")]
     DateTimeParse(#[from] chrono::ParseError),
     #[error("invalid timezone time error")]
     InvalidTimezoneTime,
-    #[error("no weather history error")]
+    #[error("no weather history for this location and date")]
     WeatherNoHistory,
+    #[error("no provider configured, run `weather configure <PROVIDER>` first")]
+    ProviderNotConfigured,
+    #[error("no API key given for {0}")]
+    MissingApiKey(&'static str),
+    #[error("couldn't get the API key of {provider}: {message}")]
+    ApiKeyUnavailable {
+        provider: &'static str,
+        message: String,
+    },
+    #[error("setting {key}: {message}")]
+    InvalidSetting { key: String, message: String },
+    #[error("no location given and no default location configured")]
+    NoLocation,
+    #[error("no place {pick} among the {count} places matching {query}")]
+    InvalidPick {
+        query: String,
+        pick: usize,
+        count: usize,
+    },
+    #[error("invalid location: {0}")]
+    InvalidLocation(String),
+    #[error("invalid date range: {0}")]
+    InvalidDateRange(String),
+    #[error("invalid alert rule \"{rule}\": {message}")]
+    InvalidAlertRule { rule: String, message: String },
+    #[error("the dashboard needs a terminal")]
+    NotATerminal,
+    #[error("couldn't send the alert to the {channel}: {message}")]
+    Notification {
+        channel: &'static str,
+        message: String,
+    },
+}
+
+impl Error {
+    /// Classifies an error response of a provider by its HTTP status.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The name of the provider, e.g. "openweather".
+    /// * `status` - The HTTP status of the response.
+    /// * `message` - The error message given by the provider.
+    pub fn from_status(provider: &'static str, status: u16, message: String) -> Self {
+        match status {
+            401 => Error::InvalidApiKey { provider, message },
+            404 => Error::UnknownLocation { provider, message },
+            429 => Error::QuotaExceeded { provider, message },
+            500..=599 => Error::ProviderUnavailable {
+                provider,
+                status,
+                message,
+            },
+            _ => Error::Provider {
+                provider,
+                status,
+                message,
+            },
+        }
+    }
+
+    /// Returns the exit code of the process when it fails with this error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::InvalidLocation(_)
+            | Error::DateTimeParse(_)
+            | Error::InvalidTimezoneTime
+            | Error::InvalidDateRange(_)
+            | Error::MissingApiKey(_)
+            | Error::NoLocation
+            | Error::InvalidPick { .. }
+            | Error::InvalidSetting { .. }
+            | Error::InvalidAlertRule { .. }
+            | Error::NotATerminal => 2,
+            Error::ConfigNotFound(_) | Error::ProviderNotConfigured | Error::UnknownProfile(_) => 3,
+            Error::CorruptConfig { .. } => 4,
+            Error::InvalidApiKey { .. } | Error::ApiKeyUnavailable { .. } => 5,
+            Error::UnknownLocation { .. } => 6,
+            Error::QuotaExceeded { .. } | Error::UsageLimitReached { .. } => 7,
+            Error::Request(_) | Error::ProviderUnavailable { .. } | Error::Timeout { .. } => 8,
+            Error::WeatherNoHistory => 9,
+            Error::Provider { .. } => 10,
+            Error::File(_)
+            | Error::Serialization(_)
+            | Error::TomlSerialization(_)
+            | Error::NoConfigDirectory
+            | Error::Server { .. }
+            | Error::Notification { .. } => 1,
+        }
+    }
+
+    /// Returns a hint on how to resolve the error, if there is one.
+    pub fn hint(&self) -> Option<&'static str> {
+        Some(match self {
+            Error::InvalidApiKey { .. } => {
+                "check the key with the provider and save it again with `weather configure <PROVIDER>`"
+            }
+            Error::UnknownLocation { .. } => {
+                "check the spelling, or use coordinates such as \"51.51,-0.13\""
+            }
+            Error::QuotaExceeded { .. } => {
+                "wait for the quota to reset, upgrade the plan or use another provider with `--provider`"
+            }
+            Error::UsageLimitReached { .. } => {
+                "wait for the period to end, or raise the limit with `weather usage limit <PROVIDER>`"
+            }
+            Error::Request(_) | Error::ProviderUnavailable { .. } | Error::Timeout { .. } => {
+                "check the network connection or try again later, `--fallback` asks the other configured providers"
+            }
+            Error::MissingApiKey(_) => {
+                "pass it with `--api-key` or `--api-key-file`, set the provider's environment variable, or type it when prompted"
+            }
+            Error::ApiKeyUnavailable { .. } => {
+                "check the provider's `api_key_cmd`, or the passphrase of the encrypted keys given in WEATHER_PASSPHRASE or when prompted"
+            }
+            Error::InvalidSetting { .. } => {
+                "run `weather config show` to see the settings, and `weather config path` to see the written file"
+            }
+            Error::InvalidPick { .. } => {
+                "run `weather locate <QUERY>` to list the matching places and their numbers"
+            }
+            Error::NoLocation => {
+                "pass a location, or set a default one with `location = \"<LOCATION>\"` in the configuration file"
+            }
+            Error::UnknownProfile(_) => {
+                "add a `[profiles.<NAME>]` table to a configuration file, or omit `--profile`"
+            }
+            Error::Server { .. } => {
+                "check that no other program listens on the address, or pick another one with `--bind`"
+            }
+            Error::InvalidAlertRule { .. } => {
+                "write rules as `<FIELD> <OPERATOR> <VALUE> [UNIT]`, e.g. `wind.speed > 15 m/s`, see `weather alerts add --help`"
+            }
+            Error::NotATerminal => {
+                "run it in a terminal, or use `weather get --watch` to refresh the weather in scripts"
+            }
+            Error::Notification { .. } => {
+                "check the `alerts.command`, `alerts.webhook` and `alerts.desktop` settings with `weather alerts list`"
+            }
+            Error::ConfigNotFound(_) => "run `weather configure <PROVIDER>` to create it",
+            Error::CorruptConfig { .. } => {
+                "fix the file, or remove it and run `weather configure <PROVIDER>` again"
+            }
+            Error::WeatherNoHistory => {
+                "the provider may not have history this far back, or for this location"
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(mut e: reqwest::Error) -> Self {
+        // the API keys are passed in the query, keep them out of error messages
+        if let Some(url) = e.url_mut() {
+            let query = url
+                .query_pairs()
+                .map(|(k, v)| match k.as_ref() {
+                    "appid" | "key" => (k.into_owned(), "***".to_owned()),
+                    _ => (k.into_owned(), v.into_owned()),
+                })
+                .collect::<Vec<_>>();
+            url.query_pairs_mut().clear().extend_pairs(query);
+        }
+        Error::Request(e)
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
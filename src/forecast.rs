@@ -0,0 +1,41 @@
+use crate::{
+    cli::ProviderArgs,
+    config::{Config, Paths},
+    error::Result,
+    location::Location,
+    output::{write_series, OutputFormat},
+    units::UnitSettings,
+};
+
+/// Retrieves the weather forecast for a specified address, and prints it to the console.
+///
+/// # Arguments
+///
+/// * `address` - A `Location` for which to retrieve the forecast, the configured default
+///   location if `None`.
+/// * `days` - The number of days, starting from today, to retrieve the forecast for.
+/// * `output` - The `OutputFormat` to print the forecast in, the configured one if `None`.
+/// * `units` - A `UnitSettings` representing the units selected on the command line, overriding the configured ones.
+/// * `provider` - A `ProviderArgs` representing the provider selection made on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache and the usage counters.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub async fn get_forecast(
+    address: Option<&Location>,
+    days: u32,
+    output: Option<OutputFormat>,
+    units: &UnitSettings,
+    provider: &ProviderArgs,
+    paths: &Paths,
+) -> Result<()> {
+    let config = Config::load(paths)?;
+    let address = &config.location(address)?;
+    let output = config.output(output);
+    let units = config.units.merge(*units).resolve();
+    let weather_api = config.build_provider(provider, paths)?;
+    let forecast = weather_api.get_forecast(address, days).await?;
+
+    write_series(output, &forecast, &units, &mut std::io::stdout())
+}
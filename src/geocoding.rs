@@ -0,0 +1,175 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use crate::{
+    cli::ProviderArgs,
+    config::{Config, Paths},
+    error::{Error, Result},
+    location::Location,
+    providers::{Place, WeatherProvider},
+};
+
+/// Looks up the places matching a name, and prints them numbered, the best match first.
+///
+/// # Arguments
+///
+/// * `query` - The name of the place, optionally followed by the region or country.
+/// * `provider` - A `ProviderArgs` representing the provider selection made on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache and the usage counters.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub async fn locate(query: &str, provider: &ProviderArgs, paths: &Paths) -> Result<()> {
+    let config = Config::load(paths)?;
+    let weather_api = config.build_provider(provider, paths)?;
+    let places = weather_api.search_places(query).await?;
+    print_places(&places, &mut std::io::stdout())?;
+    Ok(())
+}
+
+/// Resolves a city name to the coordinates of one of the places matching it, if asked to pick
+/// one with `pick`. Otherwise, and for locations other than city names, the location is left to
+/// the provider to match, so that no lookup request is sent.
+///
+/// # Arguments
+///
+/// * `provider` - The provider to look up the places with.
+/// * `location` - The `Location` given on the command line.
+/// * `pick` - Whether to pick a place: the number of the place to pick, starting from 1, or
+///   `None` to ask the user if several places match.
+///
+/// # Errors
+///
+/// Returns an error if the lookup fails, or the number is out of range. If no place matches, the
+/// city name is left to the provider to match.
+pub async fn resolve(
+    provider: &dyn WeatherProvider,
+    location: &Location,
+    pick: Option<Option<usize>>,
+) -> Result<Location> {
+    let (Location::City(query), Some(pick)) = (location, pick) else {
+        return Ok(location.clone());
+    };
+    Ok(pick_place(provider, query, pick)
+        .await?
+        .unwrap_or_else(|| location.clone()))
+}
+
+/// Asks the user which of the places matching a city name is meant after the provider reported
+/// it unknown, as the name may be spelled differently by the geocoding service. Only done when the
+/// input is a terminal.
+///
+/// # Arguments
+///
+/// * `provider` - The provider to look up the places with.
+/// * `location` - The `Location` the provider reported unknown.
+/// * `error` - The error the provider reported.
+///
+/// # Errors
+///
+/// Returns `error` if the location is not a city name, the input is not a terminal, or no place
+/// matches, and an error if the lookup fails.
+pub async fn resolve_unknown(
+    provider: &dyn WeatherProvider,
+    location: &Location,
+    error: Error,
+) -> Result<Location> {
+    let Location::City(query) = location else {
+        return Err(error);
+    };
+    if !std::io::stdin().is_terminal() {
+        return Err(error);
+    }
+    pick_place(provider, query, None).await?.ok_or(error)
+}
+
+/// Looks up the places matching a city name and returns the coordinates of the picked one, or
+/// `None` if no place matches.
+async fn pick_place(
+    provider: &dyn WeatherProvider,
+    query: &str,
+    pick: Option<usize>,
+) -> Result<Option<Location>> {
+    let places = match provider.search_places(query).await {
+        Err(Error::UnknownLocation { .. }) => return Ok(None),
+        places => places?,
+    };
+    let pick = match pick {
+        Some(pick) => pick,
+        None if places.len() == 1 => 1,
+        None => ask_pick(&places)?,
+    };
+    let place = pick
+        .checked_sub(1)
+        .and_then(|i| places.get(i))
+        .ok_or_else(|| Error::InvalidPick {
+            query: query.to_owned(),
+            pick,
+            count: places.len(),
+        })?;
+    Ok(Some(Location::Coordinates(place.coordinates)))
+}
+
+fn print_places(places: &[Place], out: &mut impl Write) -> Result<()> {
+    for (i, place) in places.iter().enumerate() {
+        writeln!(out, "{}. {place}", i + 1)?;
+    }
+    Ok(())
+}
+
+/// Asks the user which of the places to pick, the first one by default.
+fn ask_pick(places: &[Place]) -> Result<usize> {
+    let mut err = std::io::stderr();
+    print_places(places, &mut err)?;
+    loop {
+        write!(err, "Pick a place [1-{}, default 1]: ", places.len())?;
+        err.flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Ok(1);
+        }
+        match answer.trim() {
+            "" => return Ok(1),
+            answer => match answer.parse() {
+                Ok(pick) if (1..=places.len()).contains(&pick) => return Ok(pick),
+                _ => writeln!(err, "{answer} is not one of the places")?,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Coordinates;
+
+    #[test]
+    fn test_print_places() {
+        let places = [
+            Place {
+                name: "London".into(),
+                region: Some("England".into()),
+                country: "GB".into(),
+                coordinates: Coordinates {
+                    lat: 51.5073,
+                    lon: -0.1277,
+                },
+            },
+            Place {
+                name: "London".into(),
+                region: None,
+                country: "CA".into(),
+                coordinates: Coordinates {
+                    lat: 42.9834,
+                    lon: -81.233,
+                },
+            },
+        ];
+        let mut out = Vec::new();
+        print_places(&places, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1. London, England, GB (51.5073,-0.1277)\n2. London, CA (42.9834,-81.233)\n"
+        );
+    }
+}
@@ -1,41 +1,72 @@
-use std::path::Path;
-
-use crate::{cli::DateVariant, error::Result, providers::ProviderUserInfo};
+use crate::{
+    cli::DateVariant,
+    cli::ProviderArgs,
+    config::{Config, Paths},
+    error::{Error, Result},
+    geocoding::{resolve, resolve_unknown},
+    location::Location,
+    output::{write_weather, OutputFormat},
+    providers::{Weather, WeatherProvider},
+    units::UnitSettings,
+};
 
 /// Retrieves weather data for a specified address and date, and prints it to the console.
 ///
 /// # Arguments
 ///
-/// * `address` - A string representing the address for which to retrieve weather data.
+/// * `address` - A `Location` for which to retrieve weather data, the configured default
+///   location if `None`.
 /// * `date` - A `DateVariant` representing the date for which to retrieve weather data.
-/// * `config_file` - A `Path` representing the path to the configuration file containing user information for the weather provider.
+/// * `output` - The `OutputFormat` to print the weather data in, the configured one if `None`.
+/// * `pick` - Whether to pick among the places matching a city name: the number of the place, or
+///   `None` to ask. Without it, the places are only looked up if the provider reports the city
+///   unknown and the input is a terminal.
+/// * `units` - A `UnitSettings` representing the units selected on the command line, overriding the configured ones.
+/// * `provider` - A `ProviderArgs` representing the provider selection made on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache and the usage counters.
 ///
 /// # Returns
 ///
 /// A `Result` indicating whether the operation was successful.
-pub async fn get_weather(address: &str, date: &DateVariant, config_file: &Path) -> Result<()> {
-    let weather_api = ProviderUserInfo::from_file(config_file)?.build_provider();
-    let weather = match date {
-        DateVariant::HistoryDate(d) => weather_api.get_history_weather_city(address, *d).await?,
-        DateVariant::Now => weather_api.get_weather_city(address).await?,
+pub async fn get_weather(
+    address: Option<&Location>,
+    date: &DateVariant,
+    output: Option<OutputFormat>,
+    pick: Option<Option<usize>>,
+    units: &UnitSettings,
+    provider: &ProviderArgs,
+    paths: &Paths,
+) -> Result<()> {
+    let config = Config::load(paths)?;
+    let address = config.location(address)?;
+    let output = config.output(output);
+    let units = config.units.merge(*units).resolve();
+    let weather_api = config.build_provider(provider, paths)?;
+    let address = resolve(weather_api.as_ref(), &address, pick).await?;
+    let weather = match request(weather_api.as_ref(), &address, date).await {
+        Err(e @ Error::UnknownLocation { .. }) if pick.is_none() => {
+            let address = resolve_unknown(weather_api.as_ref(), &address, e).await?;
+            request(weather_api.as_ref(), &address, date).await?
+        }
+        weather => weather?,
     };
 
-    println!("{}", weather.location);
-    println!("{}", weather.description);
-    println!("{:+} °C", weather.temperature);
+    write_weather(output, &weather, &units, &mut std::io::stdout())
+}
 
-    let speed_kmh = weather.wind.speed * 3.6;
-    println!(
-        "{} {:.1} km/h",
-        wind_direction_symbol(weather.wind.deg),
-        speed_kmh
-    );
-    println!("{} m", weather.visibility);
-    println!("{:.1} mm", weather.rain_volume);
-    Ok(())
+/// Requests the current weather, or the weather of a past date.
+async fn request(
+    weather_api: &dyn WeatherProvider,
+    address: &Location,
+    date: &DateVariant,
+) -> Result<Weather> {
+    match date {
+        DateVariant::HistoryDate(d) => weather_api.get_history_weather(address, *d).await,
+        DateVariant::Now => weather_api.get_weather(address).await,
+    }
 }
 
-fn wind_direction_symbol(degrees: i64) -> char {
+pub(crate) fn wind_direction_symbol(degrees: i64) -> char {
     let arrows = ['↑', '↖', '←', '↙', '↓', '↘', '→', '↗'];
     let index = ((degrees) as f64 / 45.0).round() as usize % 8;
     arrows[index]
@@ -0,0 +1,177 @@
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{
+    cli::ProviderArgs,
+    config::{Config, Paths},
+    error::{Error, Result},
+    location::Location,
+    output::{write_series, OutputFormat},
+    providers::{Weather, WeatherProvider},
+    units::UnitSettings,
+};
+
+/// Enum representing the time between the entries of a historical series.
+#[derive(Clone, Copy, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Step {
+    /// One entry per hour.
+    #[default]
+    Hourly,
+    /// One entry per day, at the time of day of the start of the range.
+    Daily,
+}
+
+impl Step {
+    /// Returns the time between two entries.
+    pub fn duration(self) -> Duration {
+        match self {
+            Step::Hourly => Duration::hours(1),
+            Step::Daily => Duration::days(1),
+        }
+    }
+}
+
+/// Retrieves the historical weather between two dates for a specified address, and prints it to
+/// the console.
+///
+/// # Arguments
+///
+/// * `address` - A `Location` for which to retrieve weather data, the configured default
+///   location if `None`.
+/// * `from` - A `DateTime<Utc>` representing the start of the range.
+/// * `to` - A `DateTime<Utc>` representing the end of the range, or `None` for now.
+/// * `step` - The `Step` between the entries of the printed series.
+/// * `output` - The `OutputFormat` to print the series in, the configured one if `None`.
+/// * `units` - A `UnitSettings` representing the units selected on the command line, overriding the configured ones.
+/// * `provider` - A `ProviderArgs` representing the provider selection made on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache and the usage counters.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+#[allow(clippy::too_many_arguments)]
+pub async fn get_history(
+    address: Option<&Location>,
+    from: DateTime<Utc>,
+    to: Option<DateTime<Utc>>,
+    step: Step,
+    output: Option<OutputFormat>,
+    units: &UnitSettings,
+    provider: &ProviderArgs,
+    paths: &Paths,
+) -> Result<()> {
+    let config = Config::load(paths)?;
+    let address = &config.location(address)?;
+    let output = config.output(output);
+    let units = config.units.merge(*units).resolve();
+    let weather_api = config.build_provider(provider, paths)?;
+    let series = fetch_history(weather_api.as_ref(), address, from, to, step).await?;
+
+    write_series(output, &series, &units, &mut std::io::stdout())
+}
+
+/// Retrieves the historical weather between two dates with a provider, with one entry per step.
+///
+/// # Arguments
+///
+/// * `weather_api` - The provider to retrieve the weather with.
+/// * `address` - A `Location` for which to retrieve weather data.
+/// * `from` - A `DateTime<Utc>` representing the start of the range.
+/// * `to` - A `DateTime<Utc>` representing the end of the range, or `None` for now.
+/// * `step` - The `Step` between the entries of the series.
+///
+/// # Errors
+///
+/// Returns an error if the range is empty, or the provider fails.
+pub async fn fetch_history(
+    weather_api: &dyn WeatherProvider,
+    address: &Location,
+    from: DateTime<Utc>,
+    to: Option<DateTime<Utc>>,
+    step: Step,
+) -> Result<Vec<Weather>> {
+    let now = Utc::now();
+    let to = end_of_range(from, to, step, now);
+    if from > to {
+        return Err(Error::InvalidDateRange(format!(
+            "{from} is after {to}, the end of the range"
+        )));
+    }
+    let series = weather_api.get_history_range(address, from, to).await?;
+    Ok(resample(&series, from, to, step.duration()))
+}
+
+/// Returns the end of the range, at most `now`. Ranges open to `now` end at their last step
+/// before it instead, so that the requests made within a step share their cached responses.
+fn end_of_range(
+    from: DateTime<Utc>,
+    to: Option<DateTime<Utc>>,
+    step: Step,
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    match to {
+        Some(to) if to < now => to,
+        _ if from <= now => {
+            let steps = (now - from).num_seconds() / step.duration().num_seconds();
+            from + step.duration() * steps as i32
+        }
+        _ => now,
+    }
+}
+
+/// Picks the entry nearest to each step from `from` to `to`. Steps without an entry within half a
+/// step are skipped.
+fn resample(
+    series: &[Weather],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    step: Duration,
+) -> Vec<Weather> {
+    let mut resampled: Vec<Weather> = Vec::new();
+    let mut time = from;
+    while time <= to {
+        let nearest = series
+            .iter()
+            .min_by_key(|x| (x.time - time).abs())
+            .filter(|x| (x.time - time).abs() * 2 < step);
+        if let Some(weather) = nearest {
+            if resampled.last().map(|x| x.time) != Some(weather.time) {
+                resampled.push(weather.clone());
+            }
+        }
+        time += step;
+    }
+    resampled
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(Some("2023-03-15T10:00:00Z"), Step::Hourly, "2023-03-15T10:00:00Z")]
+    #[case(None, Step::Hourly, "2023-03-15T11:30:00Z")]
+    #[case(Some("2023-03-16T00:00:00Z"), Step::Hourly, "2023-03-15T11:30:00Z")]
+    #[case(None, Step::Daily, "2023-03-15T06:30:00Z")]
+    fn test_end_of_range(#[case] to: Option<&str>, #[case] step: Step, #[case] expected: &str) {
+        let from = Utc.with_ymd_and_hms(2023, 3, 14, 6, 30, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2023, 3, 15, 12, 15, 42).unwrap();
+        let to = to.map(|x| x.parse().unwrap());
+        assert_eq!(
+            end_of_range(from, to, step, now),
+            expected.parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_end_of_range_after_now() {
+        let now = Utc.with_ymd_and_hms(2023, 3, 15, 12, 0, 0).unwrap();
+        let from = now + Duration::minutes(30);
+        assert_eq!(end_of_range(from, None, Step::Hourly, now), now);
+    }
+}
@@ -2,16 +2,57 @@
 //! data for a given location. This library module contains the top level modules for the
 //! application, including:
 //!
+//! - `alerts`: Contains the alert rules checked against the weather and the notifications sent.
+//! - `cache`: Contains the on-disk cache of provider responses.
 //! - `cli`: Contains the CLI command definitions and parsing logic.
+//! - `compare`: Contains the logic for comparing the weather data of all configured providers.
+//! - `config`: Contains the layered configuration files and their profiles.
 //! - `configure`: Contains the logic for configuring the application with API keys for weather
 //!   service providers.
 //! - `error`: Contains the custom error types used throughout the application.
+//! - `forecast`: Contains the logic for fetching weather forecasts from the chosen provider.
+//! - `geocoding`: Contains the lookup of places by name and the choice among several matches.
 //! - `get`: Contains the logic for fetching weather data from the chosen provider.
+//! - `history`: Contains the logic for fetching historical weather series from the chosen
+//!   provider.
+//! - `location`: Contains the location type used to specify where to fetch weather data for.
+//! - `metrics`: Contains the Prometheus metrics of the weather and of the requests sent to the
+//!   providers, and the exporter serving them.
+//! - `network`: Contains the timeouts and retries of the requests sent to the providers.
+//! - `output`: Contains the logic for printing weather data in human and machine readable formats.
+//! - `places`: Contains the logic for managing the saved places and the default location.
 //! - `providers`: Contains the provider interface and provider implementations for the
 //!   OpenWeather and WeatherAPI services.
+//! - `secrets`: Contains the encryption of the stored API keys and the commands printing them.
+//! - `serve`: Contains the HTTP server serving weather data as JSON.
+//! - `settings`: Contains the logic for showing and changing individual settings of the
+//!   configuration files.
+//! - `tui`: Contains the full screen dashboard of the weather of several locations.
+//! - `units`: Contains the unit systems and conversions of weather data.
+//! - `usage`: Contains the usage counters and limits of the API keys.
+//! - `watch`: Contains the logic for refreshing the weather periodically and showing its changes.
 
+pub mod alerts;
+pub mod cache;
 pub mod cli;
+pub mod compare;
+pub mod config;
 pub mod configure;
 pub mod error;
+pub mod forecast;
+pub mod geocoding;
 pub mod get;
+pub mod history;
+pub mod location;
+pub mod metrics;
+pub mod network;
+pub mod output;
+pub mod places;
 pub mod providers;
+pub mod secrets;
+pub mod serve;
+pub mod settings;
+pub mod tui;
+pub mod units;
+pub mod usage;
+pub mod watch;
@@ -0,0 +1,120 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Enum representing a location that weather data can be requested for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Location {
+    /// A free-text city name, e.g. "London" or "Springfield, US".
+    City(String),
+    /// A latitude/longitude pair.
+    Coordinates(Coordinates),
+    /// A postal code, optionally followed by a country code, e.g. "10001,US".
+    PostalCode(String),
+    /// A provider specific location ID.
+    Id(String),
+}
+
+/// A struct representing geographic coordinates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Coordinates {
+    /// The latitude in degrees, from -90 to 90.
+    pub lat: f64,
+
+    /// The longitude in degrees, from -180 to 180.
+    pub lon: f64,
+}
+
+impl Coordinates {
+    /// Creates coordinates, checking that they are within valid ranges.
+    pub fn new(lat: f64, lon: f64) -> Result<Self, Error> {
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(Error::InvalidLocation(format!("{lat},{lon}")));
+        }
+        Ok(Self { lat, lon })
+    }
+}
+
+impl Display for Coordinates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.lat, self.lon)
+    }
+}
+
+/// Parses a location from a string.
+///
+/// * `"<lat>,<lon>"` - coordinates, e.g. `"51.5,-0.12"`
+/// * `"zip:<code>"` - postal code, e.g. `"zip:10001,US"`
+/// * `"id:<id>"` - provider specific location ID, e.g. `"id:2643743"`
+/// * anything else - city name
+impl FromStr for Location {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(Error::InvalidLocation(s.to_owned()));
+        }
+        if let Some(code) = s.strip_prefix("zip:") {
+            return non_empty(code).map(Location::PostalCode);
+        }
+        if let Some(id) = s.strip_prefix("id:") {
+            return non_empty(id).map(Location::Id);
+        }
+        if let Some((lat, lon)) = s.split_once(',') {
+            if let (Ok(lat), Ok(lon)) = (lat.trim().parse(), lon.trim().parse()) {
+                return Coordinates::new(lat, lon).map(Location::Coordinates);
+            }
+        }
+        Ok(Location::City(s.to_owned()))
+    }
+}
+
+fn non_empty(s: &str) -> Result<String, Error> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(Error::InvalidLocation(s.to_owned()));
+    }
+    Ok(s.to_owned())
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::City(city) => write!(f, "{city}"),
+            Location::Coordinates(coord) => write!(f, "{coord}"),
+            Location::PostalCode(code) => write!(f, "zip:{code}"),
+            Location::Id(id) => write!(f, "id:{id}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("London", Location::City("London".into()))]
+    #[case("Springfield, US", Location::City("Springfield, US".into()))]
+    #[case("51.5,-0.12", Location::Coordinates(Coordinates { lat: 51.5, lon: -0.12 }))]
+    #[case(" 40.7 , -74 ", Location::Coordinates(Coordinates { lat: 40.7, lon: -74.0 }))]
+    #[case("zip:10001,US", Location::PostalCode("10001,US".into()))]
+    #[case("id:2643743", Location::Id("2643743".into()))]
+    fn test_parse(#[case] input: &str, #[case] expected: Location) {
+        assert_eq!(input.parse::<Location>().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("zip:")]
+    #[case("id: ")]
+    #[case("91,0")]
+    #[case("0,181")]
+    fn test_parse_invalid(#[case] input: &str) {
+        assert!(input.parse::<Location>().is_err());
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use axum::{http::header::CONTENT_TYPE, response::IntoResponse, routing::get, Router};
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+use crate::{
+    cli::ProviderArgs,
+    config::{Config, Paths},
+    error::{Error, Result},
+    location::Location,
+    providers::{Weather, WeatherProvider},
+    serve::stop_serving,
+};
+
+/// The metrics of the process: the weather polled by the exporter, and the requests sent to the
+/// providers by any command, which only the exporter exposes.
+struct Metrics {
+    registry: Registry,
+    temperature: GaugeVec,
+    feels_like: GaugeVec,
+    humidity: GaugeVec,
+    pressure: GaugeVec,
+    wind_speed: GaugeVec,
+    wind_direction: GaugeVec,
+    rain: GaugeVec,
+    visibility: GaugeVec,
+    cloudiness: GaugeVec,
+    observed_at: GaugeVec,
+    poll_errors: IntCounterVec,
+    request_duration: HistogramVec,
+    request_errors: IntCounterVec,
+    cache_requests: IntCounterVec,
+    /// The provider whose weather gauges are set for each location, so that the gauges of a
+    /// provider that stopped answering, e.g. after falling back, are removed.
+    providers: Mutex<HashMap<String, String>>,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new();
+        let weather = |name: &str, help: &str| -> prometheus::Result<GaugeVec> {
+            let gauge = GaugeVec::new(Opts::new(name, help), &["location", "provider"])?;
+            registry.register(Box::new(gauge.clone()))?;
+            Ok(gauge)
+        };
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels)?;
+            registry.register(Box::new(counter.clone()))?;
+            Ok::<_, prometheus::Error>(counter)
+        };
+        let metrics = Self {
+            temperature: weather("weather_temperature_celsius", "Temperature")?,
+            feels_like: weather("weather_feels_like_celsius", "Apparent temperature")?,
+            humidity: weather("weather_humidity_percent", "Relative humidity")?,
+            pressure: weather("weather_pressure_hpa", "Atmospheric pressure at sea level")?,
+            wind_speed: weather("weather_wind_speed_meters_per_second", "Wind speed")?,
+            wind_direction: weather(
+                "weather_wind_direction_degrees",
+                "Direction the wind is coming from, 0 for a northerly wind",
+            )?,
+            rain: weather("weather_rain_millimeters", "Volume of rain")?,
+            visibility: weather("weather_visibility_meters", "Visibility")?,
+            cloudiness: weather("weather_cloudiness_percent", "Sky covered by clouds")?,
+            observed_at: weather(
+                "weather_observation_timestamp_seconds",
+                "Time the weather was observed by the provider",
+            )?,
+            poll_errors: counter(
+                "weather_poll_errors_total",
+                "Failed polls of the weather of a location",
+                &["location"],
+            )?,
+            request_duration: {
+                let histogram = HistogramVec::new(
+                    HistogramOpts::new(
+                        "weather_provider_request_duration_seconds",
+                        "Duration of the requests sent to a provider, including retries",
+                    )
+                    .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+                    &["provider"],
+                )?;
+                registry.register(Box::new(histogram.clone()))?;
+                histogram
+            },
+            request_errors: counter(
+                "weather_provider_errors_total",
+                "Requests to a provider that failed or were answered with an error, by HTTP status \
+                 or \"timeout\" and \"connection\"",
+                &["provider", "reason"],
+            )?,
+            cache_requests: counter(
+                "weather_cache_requests_total",
+                "Lookups of the response cache, by result \"hit\" or \"miss\"",
+                &["provider", "result"],
+            )?,
+            providers: Mutex::new(HashMap::new()),
+            registry,
+        };
+        Ok(metrics)
+    }
+
+    fn weather_gauges(&self) -> [&GaugeVec; 10] {
+        [
+            &self.temperature,
+            &self.feels_like,
+            &self.humidity,
+            &self.pressure,
+            &self.wind_speed,
+            &self.wind_direction,
+            &self.rain,
+            &self.visibility,
+            &self.cloudiness,
+            &self.observed_at,
+        ]
+    }
+
+    fn set_weather(&self, location: &str, weather: &Weather) {
+        let mut providers = self.providers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(previous) = providers.insert(location.to_owned(), weather.provider.clone()) {
+            if previous != weather.provider {
+                for gauge in self.weather_gauges() {
+                    let _ = gauge.remove_label_values(&[location, &previous]);
+                }
+            }
+        }
+        let labels = [location, weather.provider.as_str()];
+        let set = |gauge: &GaugeVec, value: f64| gauge.with_label_values(&labels).set(value);
+        set(&self.temperature, weather.temperature);
+        set(&self.feels_like, weather.feels_like);
+        set(&self.humidity, weather.humidity as f64);
+        set(&self.pressure, weather.pressure);
+        set(&self.wind_speed, weather.wind.speed);
+        set(&self.wind_direction, weather.wind.deg as f64);
+        set(&self.rain, weather.rain_volume);
+        match weather.visibility {
+            Some(visibility) => set(&self.visibility, visibility),
+            None => {
+                let _ = self.visibility.remove_label_values(&labels);
+            }
+        }
+        set(&self.cloudiness, weather.cloudiness as f64);
+        set(&self.observed_at, weather.time.timestamp() as f64);
+    }
+
+    fn encode(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(std::io::Error::other)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("the metrics have unique names"))
+}
+
+/// Records a request sent to a provider, with its retries.
+///
+/// # Arguments
+///
+/// * `provider` - The name of the provider, e.g. "openweather".
+/// * `duration` - The time until the response, or the last error, was received.
+/// * `failure` - `None` if the provider answered successfully, otherwise the HTTP status of the
+///   error response, or "timeout" or "connection".
+pub fn record_request(provider: &str, duration: Duration, failure: Option<&str>) {
+    let metrics = metrics();
+    metrics
+        .request_duration
+        .with_label_values(&[provider])
+        .observe(duration.as_secs_f64());
+    if let Some(reason) = failure {
+        metrics
+            .request_errors
+            .with_label_values(&[provider, reason])
+            .inc();
+    }
+}
+
+/// Records a lookup of the response cache of a provider.
+pub fn record_cache_lookup(provider: &str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    metrics()
+        .cache_requests
+        .with_label_values(&[provider, result])
+        .inc();
+}
+
+/// Polls the current weather of locations periodically, and serves it with metrics about the
+/// requests sent to the providers in the Prometheus text format under `/metrics`, until
+/// interrupted or terminated.
+///
+/// # Arguments
+///
+/// * `locations` - The saved places or locations to poll, labelled as given. All saved places if
+///   empty, or the default location if there are none.
+/// * `bind` - The address to listen on.
+/// * `interval` - The time between two polls.
+/// * `provider` - A `ProviderArgs` representing the provider selection made on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache and the usage counters.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if no provider is configured, a location is invalid, there is no location to
+/// poll, or the address cannot be listened on.
+pub async fn export_metrics(
+    locations: &[String],
+    bind: SocketAddr,
+    interval: Duration,
+    provider: &ProviderArgs,
+    paths: &Paths,
+) -> Result<()> {
+    let config = Config::load(paths)?;
+    let provider = config.build_provider(provider, paths)?;
+    let locations = config.named_locations(locations)?;
+
+    let app = Router::new().route("/metrics", get(serve_metrics));
+    let server_error = |message: String| Error::Server {
+        addr: bind,
+        message,
+    };
+    let server = axum::Server::try_bind(&bind)
+        .map_err(|e| server_error(e.to_string()))?
+        .serve(app.into_make_service());
+    eprintln!("Serving metrics on http://{}/metrics", server.local_addr());
+
+    let poller = tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            poll(provider.as_ref(), &locations).await;
+        }
+    });
+    let result = server.with_graceful_shutdown(stop_serving()).await;
+    poller.abort();
+    result.map_err(|e| server_error(e.to_string()))?;
+    eprintln!("Server stopped");
+    Ok(())
+}
+
+/// Requests the current weather of all locations concurrently and sets their gauges, logging the
+/// failures.
+async fn poll(provider: &dyn WeatherProvider, locations: &[(String, Location)]) {
+    let requests = locations
+        .iter()
+        .map(|(name, location)| async move { (name, provider.get_weather(location).await) });
+    for (name, result) in futures::future::join_all(requests).await {
+        match result {
+            Ok(weather) => metrics().set_weather(name, &weather),
+            Err(e) => {
+                eprintln!("Polling {name} failed: {e}");
+                metrics().poll_errors.with_label_values(&[name]).inc();
+            }
+        }
+    }
+}
+
+async fn serve_metrics() -> axum::response::Response {
+    match metrics().encode() {
+        Ok(body) => ([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], body).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("couldn't encode the metrics: {e}"),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::sample_weather;
+
+    #[test]
+    fn test_set_weather() {
+        let metrics = Metrics::new().unwrap();
+        let mut weather = sample_weather();
+        metrics.set_weather("hq", &weather);
+        let text = metrics.encode().unwrap();
+        assert!(text
+            .contains(r#"weather_temperature_celsius{location="hq",provider="openweather"} 12.5"#));
+        assert!(text.contains(
+            r#"weather_wind_direction_degrees{location="hq",provider="openweather"} 270"#
+        ));
+        assert!(!text.contains("weather_visibility_meters{"));
+
+        // the gauges of the provider answering before are removed
+        weather.provider = "weatherapi".into();
+        metrics.set_weather("hq", &weather);
+        let text = metrics.encode().unwrap();
+        assert!(text
+            .contains(r#"weather_temperature_celsius{location="hq",provider="weatherapi"} 12.5"#));
+        assert!(!text.contains(r#"provider="openweather""#));
+    }
+}
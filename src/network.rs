@@ -0,0 +1,354 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+use rand::Rng;
+use reqwest::{header::RETRY_AFTER, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    metrics,
+    usage::UsageTracker,
+};
+
+/// The default time to wait for a connection to a provider.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The default time to wait for the full response of a provider.
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The default number of times a failed request is retried.
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// The delay before the first retry, doubled for every further retry.
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// The longest delay between two retries, including delays asked for with `Retry-After`. If a
+/// provider asks to wait longer, the request fails instead.
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Builds the URL of an endpoint of a provider, percent-encoding the query parameters.
+///
+/// # Arguments
+///
+/// * `setting` - The name of the setting the base URL comes from, e.g. "base_url".
+/// * `base_url` - The base URL of the provider, without a trailing slash.
+/// * `path` - The path of the endpoint below the base URL.
+/// * `params` - The query parameters, unencoded.
+///
+/// # Errors
+///
+/// Returns an error if the base URL is not a valid URL.
+pub fn build_url(
+    setting: &str,
+    base_url: &str,
+    path: &str,
+    params: &[(&str, String)],
+) -> Result<String> {
+    let url =
+        reqwest::Url::parse_with_params(&format!("{base_url}/{path}"), params).map_err(|e| {
+            Error::InvalidSetting {
+                key: setting.to_owned(),
+                message: format!("{base_url} is not a valid URL: {e}"),
+            }
+        })?;
+    Ok(url.into())
+}
+
+/// A struct representing the network settings selected by the user, either on the command line
+/// or in the configuration file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Args, Serialize, Deserialize)]
+pub struct NetworkSettings {
+    /// Seconds to wait for a connection to the provider
+    #[arg(long = "connect-timeout", value_name = "SECONDS")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+
+    /// Seconds to wait for the response of the provider
+    #[arg(long = "read-timeout", value_name = "SECONDS")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_timeout: Option<u64>,
+
+    /// Number of times a failed request is retried
+    #[arg(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+}
+
+impl NetworkSettings {
+    /// Returns whether no setting was selected.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merges `other` on top of these settings, the settings selected by `other` are overridden.
+    pub fn merge(self, other: NetworkSettings) -> NetworkSettings {
+        NetworkSettings {
+            connect_timeout: other.connect_timeout.or(self.connect_timeout),
+            read_timeout: other.read_timeout.or(self.read_timeout),
+            retries: other.retries.or(self.retries),
+        }
+    }
+
+    /// Resolves the settings into the policy requests are sent with.
+    pub fn resolve(&self, verbose: bool) -> RequestPolicy {
+        RequestPolicy {
+            connect_timeout: self
+                .connect_timeout
+                .map_or(DEFAULT_CONNECT_TIMEOUT, Duration::from_secs),
+            read_timeout: self
+                .read_timeout
+                .map_or(DEFAULT_READ_TIMEOUT, Duration::from_secs),
+            retries: self.retries.unwrap_or(DEFAULT_RETRIES),
+            verbose,
+        }
+    }
+}
+
+/// A struct representing how requests are sent to the providers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestPolicy {
+    /// The time to wait for a connection.
+    pub connect_timeout: Duration,
+
+    /// The time to wait for the full response of a request, including the connection.
+    pub read_timeout: Duration,
+
+    /// The number of times a failed request is retried.
+    pub retries: u32,
+
+    /// Whether to print the outcome of each request to stderr.
+    pub verbose: bool,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        NetworkSettings::default().resolve(false)
+    }
+}
+
+/// The clients of the process by connect timeout. A `Client` pools its connections, so the
+/// providers and requests of a run, e.g. all requests served by `weather serve`, share them.
+static CLIENTS: Mutex<Option<HashMap<Duration, Client>>> = Mutex::new(None);
+
+pub(crate) fn shared_client(connect_timeout: Duration) -> Client {
+    let mut clients = CLIENTS.lock().unwrap_or_else(|e| e.into_inner());
+    clients
+        .get_or_insert_with(HashMap::new)
+        .entry(connect_timeout)
+        .or_insert_with(|| {
+            Client::builder()
+                .connect_timeout(connect_timeout)
+                .build()
+                .unwrap_or_default()
+        })
+        .clone()
+}
+
+/// A response of a provider, read in full.
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+/// An HTTP client sending the requests of a provider according to a `RequestPolicy`.
+pub struct HttpClient {
+    client: Client,
+    provider: &'static str,
+    policy: RequestPolicy,
+    usage: Option<UsageTracker>,
+}
+
+impl HttpClient {
+    /// Creates a client for the provider with the given name, sharing the connections of the
+    /// other clients with the same connect timeout.
+    pub fn new(provider: &'static str, policy: RequestPolicy) -> Self {
+        Self {
+            client: shared_client(policy.connect_timeout),
+            provider,
+            policy,
+            usage: None,
+        }
+    }
+
+    /// Counts every request sent by this client with `usage`, enforcing the limits of the key.
+    pub fn with_usage(mut self, usage: UsageTracker) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Sends a GET request and reads the response.
+    ///
+    /// Connection errors, timeouts, rate limited and server error responses are retried with
+    /// exponential backoff and jitter, honouring `Retry-After`. Only idempotent requests may be
+    /// sent this way.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider cannot be reached or doesn't respond in time after all
+    /// retries, or if a usage limit of the key is reached. Error responses are returned as
+    /// responses.
+    pub async fn get(&self, url: &str) -> Result<HttpResponse> {
+        let start = Instant::now();
+        let result = self.send(url, start).await;
+        let failure = match &result {
+            Ok(response) if response.status.is_success() => None,
+            Ok(response) => Some(response.status.as_str()),
+            Err(Error::Timeout { .. }) => Some("timeout"),
+            Err(Error::Request(_)) => Some("connection"),
+            // refused before sending, e.g. by a usage limit
+            Err(_) => return result,
+        };
+        metrics::record_request(self.provider, start.elapsed(), failure);
+        result
+    }
+
+    /// Sends the request, retrying it as described by `get`.
+    async fn send(&self, url: &str, start: Instant) -> Result<HttpResponse> {
+        let mut retries = 0;
+        loop {
+            let (reason, retry_after, result) = match self.attempt(url).await {
+                Ok((response, retry_after)) if is_retryable(response.status) => (
+                    format!("HTTP {}", response.status.as_u16()),
+                    retry_after,
+                    Ok(response),
+                ),
+                Ok((response, _)) => {
+                    self.log(&format!(
+                        "HTTP {} in {} ms after {retries} retries",
+                        response.status.as_u16(),
+                        start.elapsed().as_millis()
+                    ));
+                    return Ok(response);
+                }
+                Err(e) if is_retryable_error(&e) => (e.to_string(), None, Err(e)),
+                Err(e) => return Err(e),
+            };
+            let delay = retry_after.unwrap_or_else(|| backoff(retries));
+            if retries >= self.policy.retries || delay > MAX_DELAY {
+                self.log(&format!("{reason}, giving up after {retries} retries"));
+                return result;
+            }
+            retries += 1;
+            self.log(&format!(
+                "{reason}, retry {retries} of {} in {:.1} s",
+                self.policy.retries,
+                delay.as_secs_f64()
+            ));
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Sends the request once, returning the response and the delay asked for with
+    /// `Retry-After`.
+    async fn attempt(&self, url: &str) -> Result<(HttpResponse, Option<Duration>)> {
+        if let Some(usage) = &self.usage {
+            usage.acquire(self.policy.verbose).await?;
+        }
+        let request = async {
+            let response = self.client.get(url).send().await?;
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|x| x.to_str().ok())
+                .and_then(parse_retry_after);
+            let status = response.status();
+            let body = response.text().await?;
+            Ok((HttpResponse { status, body }, retry_after))
+        };
+        tokio::time::timeout(self.policy.read_timeout, request)
+            .await
+            .map_err(|_| Error::Timeout {
+                provider: self.provider,
+                timeout: self.policy.read_timeout,
+            })?
+    }
+
+    fn log(&self, message: &str) {
+        if self.policy.verbose {
+            eprintln!("{}: {message}", self.provider);
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn is_retryable_error(error: &Error) -> bool {
+    match error {
+        Error::Timeout { .. } => true,
+        Error::Request(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+        _ => false,
+    }
+}
+
+/// Returns the delay before the given retry: exponential, with a random half of it left out so
+/// that clients failing together don't retry together.
+fn backoff(retry: u32) -> Duration {
+    let delay = BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(retry))
+        .min(MAX_DELAY);
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+/// Parses a `Retry-After` value, either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("120", Some(Duration::from_secs(120)))]
+    #[case("Wed, 21 Oct 2015 07:28:00 GMT", Some(Duration::ZERO))]
+    #[case("soon", None)]
+    fn test_parse_retry_after(#[case] value: &str, #[case] expected: Option<Duration>) {
+        assert_eq!(parse_retry_after(value), expected);
+    }
+
+    #[rstest]
+    #[case("SW1A 1AA", "q=SW1A+1AA&key=k")]
+    #[case("Fish & Chips#1", "q=Fish+%26+Chips%231&key=k")]
+    #[case("Zürich", "q=Z%C3%BCrich&key=k")]
+    fn test_build_url(#[case] query: &str, #[case] expected: &str) {
+        let params = [("q", query.to_owned()), ("key", "k".to_owned())];
+        let url = build_url(
+            "base_url",
+            "http://localhost:1234",
+            "v1/current.json",
+            &params,
+        );
+        assert_eq!(
+            url.unwrap(),
+            format!("http://localhost:1234/v1/current.json?{expected}")
+        );
+        assert!(build_url("base_url", "not a url", "v1", &params).is_err());
+    }
+
+    #[test]
+    fn test_backoff() {
+        for retry in 0..4 {
+            let max = BASE_DELAY * 2u32.pow(retry);
+            let delay = backoff(retry);
+            assert!(delay >= max / 2 && delay <= max, "{delay:?}");
+        }
+        assert!(backoff(30) <= MAX_DELAY);
+    }
+}
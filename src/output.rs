@@ -0,0 +1,584 @@
+use std::{collections::HashMap, io::Write};
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    error::Result,
+    get::wind_direction_symbol,
+    location::Coordinates,
+    providers::Weather,
+    units::{DistanceUnit, PrecipitationUnit, Units},
+};
+
+/// Enum representing the formats weather data can be printed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    /// A single JSON document.
+    Json,
+    /// One JSON document per line.
+    Ndjson,
+    /// Comma separated values with a header row.
+    Csv,
+    /// One `key=value` pair per line, records separated by an empty line.
+    Kv,
+}
+
+/// A weather record converted to the selected units, together with the units so that machine
+/// readable output is self-describing.
+#[derive(Serialize)]
+struct Record<'a> {
+    #[serde(flatten)]
+    weather: Weather,
+    units: &'a Units,
+}
+
+impl<'a> Record<'a> {
+    fn new(weather: &Weather, units: &'a Units) -> Self {
+        Record {
+            weather: units.convert(weather),
+            units,
+        }
+    }
+}
+
+/// Writes a single weather record in the given format.
+///
+/// # Arguments
+///
+/// * `format` - The `OutputFormat` to write the record in.
+/// * `weather` - The `Weather` record to write.
+/// * `units` - The `Units` to present the record in.
+/// * `out` - The writer to write the record to.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn write_weather(
+    format: OutputFormat,
+    weather: &Weather,
+    units: &Units,
+    out: &mut impl Write,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            writeln!(out, "{}", format_location(weather))?;
+            writeln!(out, "{}", format_local_time(weather, weather.time))?;
+            write_conditions(weather, units, out)?;
+            writeln!(out, "provided by {}", weather.provider)?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, &Record::new(weather, units))?;
+            writeln!(out)?;
+        }
+        format => write_series(format, std::slice::from_ref(weather), units, out)?,
+    }
+    Ok(())
+}
+
+/// Writes a time series of weather records in the given format.
+///
+/// # Arguments
+///
+/// * `format` - The `OutputFormat` to write the records in.
+/// * `series` - The `Weather` records to write.
+/// * `units` - The `Units` to present the records in.
+/// * `out` - The writer to write the records to.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn write_series(
+    format: OutputFormat,
+    series: &[Weather],
+    units: &Units,
+    out: &mut impl Write,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            if let Some(first) = series.first() {
+                writeln!(out, "{}", format_location(first))?;
+                writeln!(out, "provided by {}", first.provider)?;
+            }
+            for weather in series {
+                writeln!(out)?;
+                writeln!(out, "{}", format_local_time(weather, weather.time))?;
+                write_conditions(weather, units, out)?;
+            }
+        }
+        OutputFormat::Json => {
+            let records = series
+                .iter()
+                .map(|weather| Record::new(weather, units))
+                .collect::<Vec<_>>();
+            serde_json::to_writer_pretty(&mut *out, &records)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => {
+            for weather in series {
+                serde_json::to_writer(&mut *out, &Record::new(weather, units))?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            let records = series
+                .iter()
+                .map(|weather| flatten_record(&Record::new(weather, units)))
+                .collect::<Result<Vec<_>>>()?;
+            if let Some(first) = records.first() {
+                let header = first.iter().map(|(k, _)| csv_field(k)).collect::<Vec<_>>();
+                writeln!(out, "{}", header.join(","))?;
+            }
+            for record in records {
+                let row = record.iter().map(|(_, v)| csv_field(v)).collect::<Vec<_>>();
+                writeln!(out, "{}", row.join(","))?;
+            }
+        }
+        OutputFormat::Kv => {
+            for (i, weather) in series.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                for (key, value) in flatten_record(&Record::new(weather, units))? {
+                    writeln!(out, "{key}={}", kv_value(&value))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the reports of several providers side by side, together with their consensus and the
+/// spread of the reported values.
+///
+/// Formats other than text write the reports followed by the consensus as a series.
+///
+/// # Arguments
+///
+/// * `format` - The `OutputFormat` to write the comparison in.
+/// * `reports` - The `Weather` reported by each provider.
+/// * `consensus` - The `Weather` merged from the reports.
+/// * `units` - The `Units` to present the values in.
+/// * `out` - The writer to write the comparison to.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn write_comparison(
+    format: OutputFormat,
+    reports: &[Weather],
+    consensus: &Weather,
+    units: &Units,
+    out: &mut impl Write,
+) -> Result<()> {
+    let OutputFormat::Text = format else {
+        let mut series = reports.to_vec();
+        series.push(consensus.clone());
+        return write_series(format, &series, units, out);
+    };
+    writeln!(out, "{}", format_location(consensus))?;
+    writeln!(out, "{}", format_local_time(consensus, consensus.time))?;
+    writeln!(out)?;
+
+    let converted = reports
+        .iter()
+        .chain([consensus])
+        .map(|x| units.convert(x))
+        .collect::<Vec<_>>();
+    let mut header = vec![String::new()];
+    header.extend(reports.iter().map(|x| x.provider.clone()));
+    header.extend(["consensus".to_owned(), "spread".to_owned()]);
+    let mut rows = vec![header];
+
+    let (temperature, wind_speed) = (units.temperature.to_string(), units.wind_speed.to_string());
+    let rain_precision = match units.precipitation {
+        PrecipitationUnit::Millimeters => 1,
+        PrecipitationUnit::Inches => 2,
+    };
+    let visibility_precision = match units.visibility {
+        DistanceUnit::Meters => 0,
+        DistanceUnit::Kilometers | DistanceUnit::Miles => 1,
+    };
+    type Quantity<'a> = (&'a str, fn(&Weather) -> Option<f64>, usize, String);
+    let quantities: [Quantity; 11] = [
+        (
+            "temperature",
+            |x| Some(x.temperature),
+            1,
+            temperature.clone(),
+        ),
+        ("feels like", |x| Some(x.feels_like), 1, temperature.clone()),
+        ("dew point", |x| x.dew_point, 1, temperature),
+        ("humidity", |x| Some(x.humidity as f64), 0, "%".to_owned()),
+        ("pressure", |x| Some(x.pressure), 0, "hPa".to_owned()),
+        ("wind speed", |x| Some(x.wind.speed), 1, wind_speed.clone()),
+        ("wind gusts", |x| x.wind.gust, 1, wind_speed),
+        (
+            "rain",
+            |x| Some(x.rain_volume),
+            rain_precision,
+            units.precipitation.to_string(),
+        ),
+        (
+            "visibility",
+            |x| x.visibility,
+            visibility_precision,
+            units.visibility.to_string(),
+        ),
+        (
+            "cloudiness",
+            |x| Some(x.cloudiness as f64),
+            0,
+            "%".to_owned(),
+        ),
+        ("UV index", |x| x.uv_index, 1, String::new()),
+    ];
+    for (label, value, precision, unit) in quantities {
+        let values = converted.iter().map(value).collect::<Vec<_>>();
+        if values.iter().all(Option::is_none) {
+            continue;
+        }
+        let format = |x: f64| format!("{x:.precision$} {unit}").trim_end().to_owned();
+        let mut row = vec![label.to_owned()];
+        row.extend(values.iter().map(|x| x.map_or("-".to_owned(), format)));
+        let reported = values[..reports.len()].iter().flatten().copied();
+        let spread = reported
+            .clone()
+            .reduce(f64::max)
+            .zip(reported.reduce(f64::min))
+            .map(|(max, min)| max - min);
+        row.push(spread.map_or("-".to_owned(), format));
+        rows.push(row);
+    }
+
+    let mut row = vec!["wind direction".to_owned()];
+    row.extend(
+        converted
+            .iter()
+            .map(|x| format!("{} {}°", wind_direction_symbol(x.wind.deg), x.wind.deg)),
+    );
+    row.push(format!(
+        "{}°",
+        direction_spread(&converted[..reports.len()])
+    ));
+    rows.push(row);
+
+    let widths = (0..rows[0].len())
+        .map(|i| rows.iter().map(|x| x[i].chars().count()).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Returns the largest angle in degrees between the wind directions of any two reports.
+fn direction_spread(reports: &[Weather]) -> i64 {
+    let mut spread = 0;
+    for (i, a) in reports.iter().enumerate() {
+        for b in &reports[i + 1..] {
+            let diff = (a.wind.deg - b.wind.deg).rem_euclid(360);
+            spread = spread.max(diff.min(360 - diff));
+        }
+    }
+    spread
+}
+
+/// Writes the weather conditions, without the location, as human readable text.
+fn write_conditions(weather: &Weather, units: &Units, out: &mut impl Write) -> Result<()> {
+    let converted = units.convert(weather);
+    writeln!(out, "{}", converted.description)?;
+
+    write!(out, "{:+.1} {}", converted.temperature, units.temperature)?;
+    write!(
+        out,
+        " (feels like {:+.1} {}",
+        converted.feels_like, units.temperature
+    )?;
+    if let (Some(min), Some(max)) = (converted.temperature_min, converted.temperature_max) {
+        write!(
+            out,
+            ", min {min:+.1} {0}, max {max:+.1} {0}",
+            units.temperature
+        )?;
+    }
+    writeln!(out, ")")?;
+
+    write!(
+        out,
+        "{} {:.1} {}",
+        wind_direction_symbol(converted.wind.deg),
+        converted.wind.speed,
+        units.wind_speed
+    )?;
+    if let Some(gust) = converted.wind.gust {
+        write!(out, " (gusts {gust:.1} {})", units.wind_speed)?;
+    }
+    writeln!(out)?;
+
+    if let Some(visibility) = converted.visibility {
+        let precision = match units.visibility {
+            DistanceUnit::Meters => 0,
+            DistanceUnit::Kilometers | DistanceUnit::Miles => 1,
+        };
+        writeln!(out, "{visibility:.precision$} {}", units.visibility)?;
+    }
+    let rain_precision = match units.precipitation {
+        PrecipitationUnit::Millimeters => 1,
+        PrecipitationUnit::Inches => 2,
+    };
+    writeln!(
+        out,
+        "{:.*} {}",
+        rain_precision, converted.rain_volume, units.precipitation
+    )?;
+
+    writeln!(out, "humidity {} %", converted.humidity)?;
+    writeln!(out, "pressure {:.0} hPa", converted.pressure)?;
+    if let Some(dew_point) = converted.dew_point {
+        writeln!(out, "dew point {dew_point:+.1} {}", units.temperature)?;
+    }
+    if let Some(uv_index) = converted.uv_index {
+        writeln!(out, "UV index {uv_index:.1}")?;
+    }
+    if let (Some(sunrise), Some(sunset)) = (converted.sunrise, converted.sunset) {
+        writeln!(
+            out,
+            "sunrise {}, sunset {}",
+            weather.local_time(sunrise).format("%H:%M"),
+            weather.local_time(sunset).format("%H:%M")
+        )?;
+    }
+    Ok(())
+}
+
+/// Formats the location name, with the coordinates when they are known.
+fn format_location(weather: &Weather) -> String {
+    match weather.coordinates {
+        Some(coordinates) => format!("{} ({coordinates})", weather.location),
+        None => weather.location.clone(),
+    }
+}
+
+/// Formats a time in the local time of the location, with the timezone name when it is known.
+fn format_local_time(weather: &Weather, time: DateTime<Utc>) -> String {
+    let local = weather.local_time(time).format("%Y-%m-%d %H:%M %:z");
+    match &weather.timezone {
+        Some(timezone) => format!("{local} ({timezone})"),
+        None => local.to_string(),
+    }
+}
+
+/// Flattens the serialized record into `(key, value)` pairs, nested keys are joined with a dot,
+/// e.g. `wind.speed`.
+///
+/// Optional objects are flattened into the keys of their fields whether they are set or not,
+/// with empty values when unset, so that all records have the same keys in the same order.
+fn flatten_record(record: &Record) -> Result<Vec<(String, String)>> {
+    let mut template = record.weather.clone();
+    template
+        .coordinates
+        .get_or_insert_with(Coordinates::default);
+    let keys = flatten_value(&Record {
+        weather: template,
+        units: record.units,
+    })?;
+    let mut values = flatten_value(record)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    Ok(keys
+        .into_iter()
+        .map(|(key, _)| {
+            let value = values.remove(&key).unwrap_or_default();
+            (key, value)
+        })
+        .collect())
+}
+
+fn flatten_value(record: &Record) -> Result<Vec<(String, String)>> {
+    let mut fields = Vec::new();
+    if let Value::Object(map) = serde_json::to_value(record)? {
+        flatten_object("", &map, &mut fields);
+    }
+    Ok(fields)
+}
+
+fn flatten_object(prefix: &str, map: &Map<String, Value>, fields: &mut Vec<(String, String)>) {
+    for (key, value) in map {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            Value::Object(map) => flatten_object(&key, map, fields),
+            Value::Null => fields.push((key, String::new())),
+            Value::String(s) => fields.push((key, s.clone())),
+            value => fields.push((key, value.to_string())),
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn kv_value(value: &str) -> String {
+    if value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rstest::rstest;
+
+    use super::*;
+    use crate::providers::{sample_weather, Wind};
+
+    fn weather() -> Weather {
+        Weather {
+            time: Utc.with_ymd_and_hms(2023, 3, 20, 12, 0, 0).unwrap(),
+            description: "light rain".into(),
+            temperature: 8.5,
+            feels_like: 6.,
+            temperature_min: Some(7.),
+            temperature_max: Some(10.),
+            humidity: 81,
+            wind: Wind {
+                speed: 4.1,
+                deg: 250,
+                gust: None,
+            },
+            visibility: Some(10000.),
+            sunrise: Some(Utc.with_ymd_and_hms(2023, 3, 20, 6, 3, 0).unwrap()),
+            sunset: Some(Utc.with_ymd_and_hms(2023, 3, 20, 18, 14, 0).unwrap()),
+            location: "London, GB".into(),
+            coordinates: Some(Coordinates {
+                lat: 51.51,
+                lon: -0.13,
+            }),
+            ..sample_weather()
+        }
+    }
+
+    fn render(format: OutputFormat, series: &[Weather]) -> String {
+        let mut out = Vec::new();
+        write_series(format, series, &Units::default(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        let out = render(OutputFormat::Csv, &[weather(), weather()]);
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "time,cloudiness,description,temperature,feels_like,temperature_min,temperature_max,dew_point,humidity,pressure,uv_index,wind.speed,wind.deg,wind.gust,rain_volume,visibility,sunrise,sunset,location,coordinates.lat,coordinates.lon,utc_offset,timezone,provider,units.temperature,units.wind_speed,units.precipitation,units.visibility"
+        );
+        assert_eq!(
+            lines[1],
+            "2023-03-20T12:00:00Z,75,light rain,8.5,6.0,7.0,10.0,,81,1012.0,,14.76,250,,0.3,10000.0,2023-03-20T06:03:00Z,2023-03-20T18:14:00Z,\"London, GB\",51.51,-0.13,0,,openweather,celsius,km/h,mm,m"
+        );
+    }
+
+    #[test]
+    fn test_csv_optional_objects() {
+        let mut unlocated = weather();
+        unlocated.coordinates = None;
+        let out = render(OutputFormat::Csv, &[unlocated, weather()]);
+        let lines = out.lines().collect::<Vec<_>>();
+        assert!(lines[0].contains(",location,coordinates.lat,coordinates.lon,utc_offset,"));
+        assert!(lines[1].contains(",\"London, GB\",,,0,"));
+        assert!(lines[2].contains(",\"London, GB\",51.51,-0.13,0,"));
+    }
+
+    #[test]
+    fn test_kv() {
+        let out = render(OutputFormat::Kv, &[weather()]);
+        assert!(out.contains("description=\"light rain\"\n"));
+        assert!(out.contains("wind.deg=250\n"));
+        assert!(out.contains("location=\"London, GB\"\n"));
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let out = render(OutputFormat::Ndjson, &[weather(), weather()]);
+        for line in out.lines() {
+            let value: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(value["units"]["wind_speed"], "km/h");
+        }
+        assert_eq!(out.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_text_imperial() {
+        let mut out = Vec::new();
+        let units = Units::from(crate::units::UnitSystem::Imperial);
+        write_weather(OutputFormat::Text, &weather(), &units, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "London, GB (51.51,-0.13)\n2023-03-20 12:00 +00:00\nlight rain\n\
+             +47.3 °F (feels like +42.8 °F, min +44.6 °F, max +50.0 °F)\n→ 9.2 mph\n6.2 mi\n\
+             0.01 in\nhumidity 81 %\npressure 1012 hPa\nsunrise 06:03, sunset 18:14\n\
+             provided by openweather\n"
+        );
+    }
+
+    #[test]
+    fn test_comparison() {
+        let mut other = weather();
+        other.provider = "weatherapi".into();
+        other.temperature = 9.5;
+        other.wind.deg = 10;
+        let mut consensus = weather();
+        consensus.provider = "openweather+weatherapi".into();
+        consensus.temperature = 9.;
+
+        let mut out = Vec::new();
+        let units = Units::default();
+        write_comparison(
+            OutputFormat::Text,
+            &[weather(), other],
+            &consensus,
+            &units,
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\n                openweather  weatherapi  consensus  spread\n"));
+        assert!(out.contains("\ntemperature     8.5 °C       9.5 °C      9.0 °C     1.0 °C\n"));
+        // quantities no provider reports are left out
+        assert!(!out.contains("dew point"));
+        assert!(out.contains("\nwind direction  → 250°       ↑ 10°       → 250°     120°\n"));
+    }
+
+    #[rstest]
+    #[case("plain", "plain")]
+    #[case("a,b", "\"a,b\"")]
+    #[case("say \"hi\"", "\"say \"\"hi\"\"\"")]
+    fn test_csv_field(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(csv_field(value), expected);
+    }
+}
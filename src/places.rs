@@ -0,0 +1,131 @@
+use crate::{
+    config::{Config, Paths},
+    error::{Error, Result},
+    location::Location,
+};
+
+/// Saves a place under a name, which can then be given instead of its location, replacing the
+/// place saved under the same name.
+///
+/// # Arguments
+///
+/// * `name` - The name of the place, made of letters, digits, `-` and `_`.
+/// * `location` - The `Location` of the place.
+/// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the name is invalid.
+pub fn add_place(name: &str, location: &Location, paths: &Paths) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(invalid(
+            name,
+            "the name may only contain letters, digits, `-` and `_`",
+        ));
+    }
+    Config::update(paths, |config| {
+        config.places.insert(name.to_owned(), location.to_string());
+        Ok(())
+    })?;
+    println!("Place saved successfully.");
+    Ok(())
+}
+
+/// Prints the saved places of all configuration files with their location, marking the default
+/// location.
+///
+/// # Arguments
+///
+/// * `paths` - The `Paths` of the configuration files.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn list_places(paths: &Paths) -> Result<()> {
+    let config = Config::load_or_default(paths)?;
+    for (name, location) in &config.places {
+        let default = if config.location.as_ref() == Some(name) {
+            " (default)"
+        } else {
+            ""
+        };
+        println!("{name}: {location}{default}");
+    }
+    Ok(())
+}
+
+/// Removes a saved place from the configuration file written by the configure commands, and the
+/// default location if it is the place.
+///
+/// # Arguments
+///
+/// * `name` - The name of the place.
+/// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the place is not saved in the written file.
+pub fn remove_place(name: &str, paths: &Paths) -> Result<()> {
+    Config::update(paths, |config| {
+        if config.places.remove(name).is_none() {
+            return Err(invalid(name, "not set in the written configuration file"));
+        }
+        if config.location.as_deref() == Some(name) {
+            config.location = None;
+        }
+        Ok(())
+    })?;
+    println!("Place removed successfully.");
+    Ok(())
+}
+
+/// Sets the default location, used when no location is given, or prints it if none is given.
+///
+/// # Arguments
+///
+/// * `location` - The name of a saved place or a location.
+/// * `paths` - The `Paths` of the configuration files, and the one to be written.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the location is invalid.
+pub fn configure_default_place(location: Option<&str>, paths: &Paths) -> Result<()> {
+    if let Some(location) = location {
+        let location = location.trim();
+        location.parse::<Location>()?;
+        Config::update(paths, |config| {
+            config.location = Some(location.to_owned());
+            Ok(())
+        })?;
+        println!("Default location saved successfully.");
+    }
+    let config = Config::load_or_default(paths)?;
+    match &config.location {
+        Some(location) => println!("{location}"),
+        None => println!("none"),
+    }
+    Ok(())
+}
+
+fn invalid(name: &str, message: &str) -> Error {
+    Error::InvalidSetting {
+        key: format!("places.{name}"),
+        message: message.to_owned(),
+    }
+}
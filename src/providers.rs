@@ -1,172 +1,949 @@
+mod aggregate;
+mod fallback;
+mod lazy;
 mod openweather;
 mod weatherapi;
 
+pub use self::{
+    aggregate::{merge, merge_series, successful_reports, AggregateProvider},
+    fallback::FallbackProvider,
+    lazy::LazyProvider,
+};
+
 use self::{
-    openweather::{CurrentWeather, OpenWeather},
-    weatherapi::WeatherApi,
+    openweather::{
+        City, CurrentWeather, ForecastItem, HistoryItem, OpenWeather, MAX_HISTORY_STEPS,
+    },
+    weatherapi::{Forecastday, Hour, WeatherApi},
+};
+use crate::{
+    cli::Provider,
+    error::{Error, Result},
+    location::{Coordinates, Location},
+    network::RequestPolicy,
+    secrets,
+    usage::{Limits, UsageTracker},
 };
-use crate::error::{Error, Result};
 
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
+/// Environment variable overriding the base URL of the OpenWeather API.
+pub const ENV_OPENWEATHER_URL: &str = "WEATHER_OPENWEATHER_URL";
+/// Environment variable overriding the base URL of the OpenWeather history API.
+pub const ENV_OPENWEATHER_HISTORY_URL: &str = "WEATHER_OPENWEATHER_HISTORY_URL";
+/// Environment variable overriding the base URL of the WeatherAPI API.
+pub const ENV_WEATHERAPI_URL: &str = "WEATHER_WEATHERAPI_URL";
+/// Environment variable giving the OpenWeather API key.
+pub const ENV_OPENWEATHER_API_KEY: &str = "WEATHER_OPENWEATHER_API_KEY";
+/// Environment variable giving the WeatherAPI API key.
+pub const ENV_WEATHERAPI_API_KEY: &str = "WEATHER_WEATHERAPI_API_KEY";
+
 /// An enum representing user information required by weather providers.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Base URLs are the scheme and host the provider's API is served from, e.g.
+/// `https://api.openweathermap.org`. They can be overridden to use a proxy or a mock server, the
+/// environment variables take precedence over the configured values.
+///
+/// In the configuration file, the provider is given by the `provider` key, e.g.
+/// `provider = "openweather"`. The API key is either stored in `api_key`, in plain text or
+/// encrypted, or printed by the command in `api_key_cmd`.
+///
+/// The `Debug` output doesn't show the API key, so that it doesn't end up in logs.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "lowercase")]
 pub enum ProviderUserInfo {
     /// User information required by the OpenWeather provider.
     OpenWeather {
         /// The API key required by the OpenWeather provider.
+        #[serde(default, skip_serializing_if = "String::is_empty")]
         api_key: String,
+
+        /// The command printing the API key, used if no key is stored.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        api_key_cmd: Option<String>,
+
+        /// The base URL of the current weather and forecast API.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        base_url: Option<String>,
+
+        /// The base URL of the history API.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        history_base_url: Option<String>,
+
+        /// The most calls the key may make.
+        #[serde(default, skip_serializing_if = "Limits::is_empty")]
+        limits: Limits,
     },
 
     /// User information required by the WeatherApi provider.
     WeatherApi {
         /// The API key required by the WeatherApi provider.
+        #[serde(default, skip_serializing_if = "String::is_empty")]
         api_key: String,
+
+        /// The command printing the API key, used if no key is stored.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        api_key_cmd: Option<String>,
+
+        /// The base URL of the API.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        base_url: Option<String>,
+
+        /// The most calls the key may make.
+        #[serde(default, skip_serializing_if = "Limits::is_empty")]
+        limits: Limits,
     },
 }
 
 impl ProviderUserInfo {
-    /// Builds a weather provider from the given provider user info.
-    pub fn build_provider(self) -> Box<dyn WeatherProvider> {
+    /// Creates the user info of the given provider with the default base URLs and no limits.
+    pub fn new(kind: Provider, api_key: String) -> Self {
+        match kind {
+            Provider::OpenWeather => ProviderUserInfo::OpenWeather {
+                api_key,
+                api_key_cmd: None,
+                base_url: None,
+                history_base_url: None,
+                limits: Limits::default(),
+            },
+            Provider::WeatherApi => ProviderUserInfo::WeatherApi {
+                api_key,
+                api_key_cmd: None,
+                base_url: None,
+                limits: Limits::default(),
+            },
+        }
+    }
+
+    /// Returns the user info of the providers whose API key is given in the environment
+    /// variables, in the order of the providers.
+    pub fn from_env() -> Vec<Self> {
+        [Provider::OpenWeather, Provider::WeatherApi]
+            .into_iter()
+            .filter_map(|kind| {
+                let api_key = std::env::var(api_key_var(kind)).ok()?;
+                Some(Self::new(kind, api_key.trim().to_owned())).filter(|x| !x.api_key().is_empty())
+            })
+            .collect()
+    }
+
+    /// Returns the name of the provider, used to tell apart data of different providers.
+    pub fn name(&self) -> &'static str {
+        name(self.kind())
+    }
+
+    /// Returns which provider the user info is for.
+    pub fn kind(&self) -> Provider {
+        match self {
+            ProviderUserInfo::OpenWeather { .. } => Provider::OpenWeather,
+            ProviderUserInfo::WeatherApi { .. } => Provider::WeatherApi,
+        }
+    }
+
+    /// Returns the API key.
+    pub fn api_key(&self) -> &str {
+        match self {
+            ProviderUserInfo::OpenWeather { api_key, .. }
+            | ProviderUserInfo::WeatherApi { api_key, .. } => api_key,
+        }
+    }
+
+    /// Replaces the API key, keeping the base URLs and limits.
+    pub fn set_api_key(&mut self, new: String) {
         match self {
-            ProviderUserInfo::OpenWeather { api_key } => Box::new(OpenWeather::new(api_key)),
-            ProviderUserInfo::WeatherApi { api_key } => Box::new(WeatherApi::new(api_key)),
+            ProviderUserInfo::OpenWeather { api_key, .. }
+            | ProviderUserInfo::WeatherApi { api_key, .. } => *api_key = new,
         }
     }
 
-    /// Creates a `ProviderUserInfo` from a JSON file.
+    /// Returns the command printing the API key.
+    pub fn api_key_cmd(&self) -> Option<&str> {
+        match self {
+            ProviderUserInfo::OpenWeather { api_key_cmd, .. }
+            | ProviderUserInfo::WeatherApi { api_key_cmd, .. } => api_key_cmd.as_deref(),
+        }
+    }
+
+    /// Replaces a stored encrypted API key by the decrypted one, or sets the API key printed by
+    /// the `api_key_cmd` command if no key is stored.
     ///
     /// # Errors
     ///
-    /// Returns an error if the file cannot be read or deserialized.
-    pub fn from_file(file: &Path) -> Result<Self> {
-        Ok(serde_json::from_reader(std::fs::File::open(file)?)?)
+    /// Returns an error if the key cannot be decrypted, the command fails, or there is no key.
+    pub fn resolve_api_key(mut self) -> Result<Self> {
+        let api_key = if secrets::is_encrypted(self.api_key()) {
+            secrets::decrypt(self.api_key(), &secrets::passphrase()?).map_err(|e| match e {
+                Error::ApiKeyUnavailable { message, .. } => Error::ApiKeyUnavailable {
+                    provider: self.name(),
+                    message,
+                },
+                e => e,
+            })?
+        } else if !self.api_key().is_empty() {
+            return Ok(self);
+        } else if let Some(command) = self.api_key_cmd() {
+            secrets::run_api_key_cmd(self.name(), command)?
+        } else {
+            return Err(Error::ApiKeyUnavailable {
+                provider: self.name(),
+                message: "neither `api_key` nor `api_key_cmd` is set".to_owned(),
+            });
+        };
+        self.set_api_key(api_key);
+        Ok(self)
+    }
+
+    /// Returns the most calls the key may make.
+    pub fn limits(&self) -> Limits {
+        match self {
+            ProviderUserInfo::OpenWeather { limits, .. }
+            | ProviderUserInfo::WeatherApi { limits, .. } => *limits,
+        }
+    }
+
+    /// Replaces the most calls the key may make.
+    pub fn set_limits(&mut self, new: Limits) {
+        match self {
+            ProviderUserInfo::OpenWeather { limits, .. }
+            | ProviderUserInfo::WeatherApi { limits, .. } => *limits = new,
+        }
+    }
+
+    /// Removes the settings running a command or sending the API key to another host, returning
+    /// the names of those that were set.
+    pub fn remove_untrusted(&mut self) -> Vec<&'static str> {
+        let (api_key_cmd, base_url, history_base_url) = match self {
+            ProviderUserInfo::OpenWeather {
+                api_key_cmd,
+                base_url,
+                history_base_url,
+                ..
+            } => (api_key_cmd, base_url, Some(history_base_url)),
+            ProviderUserInfo::WeatherApi {
+                api_key_cmd,
+                base_url,
+                ..
+            } => (api_key_cmd, base_url, None),
+        };
+        [("api_key_cmd", api_key_cmd), ("base_url", base_url)]
+            .into_iter()
+            .chain(history_base_url.map(|url| ("history_base_url", url)))
+            .filter_map(|(name, setting)| setting.take().map(|_| name))
+            .collect()
+    }
+
+    /// Merges `other`, the user info of the same provider, on top of this user info: the
+    /// settings set by `other` are overridden. The key is overridden as a whole, so a key stored
+    /// by `other` also overrides an `api_key_cmd` of this user info and the other way around.
+    pub fn merge(self, other: ProviderUserInfo) -> ProviderUserInfo {
+        let limits = self.limits().merge(other.limits());
+        let sets_key = !other.api_key().is_empty() || other.api_key_cmd().is_some();
+        let key = |this: (String, Option<String>), other| if sets_key { other } else { this };
+        match (self, other) {
+            (
+                ProviderUserInfo::OpenWeather {
+                    api_key,
+                    api_key_cmd,
+                    base_url,
+                    history_base_url,
+                    ..
+                },
+                ProviderUserInfo::OpenWeather {
+                    api_key: other_api_key,
+                    api_key_cmd: other_api_key_cmd,
+                    base_url: other_base_url,
+                    history_base_url: other_history_base_url,
+                    ..
+                },
+            ) => {
+                let (api_key, api_key_cmd) =
+                    key((api_key, api_key_cmd), (other_api_key, other_api_key_cmd));
+                ProviderUserInfo::OpenWeather {
+                    api_key,
+                    api_key_cmd,
+                    base_url: other_base_url.or(base_url),
+                    history_base_url: other_history_base_url.or(history_base_url),
+                    limits,
+                }
+            }
+            (
+                ProviderUserInfo::WeatherApi {
+                    api_key,
+                    api_key_cmd,
+                    base_url,
+                    ..
+                },
+                ProviderUserInfo::WeatherApi {
+                    api_key: other_api_key,
+                    api_key_cmd: other_api_key_cmd,
+                    base_url: other_base_url,
+                    ..
+                },
+            ) => {
+                let (api_key, api_key_cmd) =
+                    key((api_key, api_key_cmd), (other_api_key, other_api_key_cmd));
+                ProviderUserInfo::WeatherApi {
+                    api_key,
+                    api_key_cmd,
+                    base_url: other_base_url.or(base_url),
+                    limits,
+                }
+            }
+            (_, other) => other,
+        }
+    }
+
+    /// Returns the base URLs the requests are sent to, taken from the environment, the user info
+    /// or the defaults like `build_provider` does.
+    pub fn endpoint(&self) -> String {
+        match self {
+            ProviderUserInfo::OpenWeather {
+                base_url,
+                history_base_url,
+                ..
+            } => format!(
+                "{} {}",
+                resolve_url(
+                    ENV_OPENWEATHER_URL,
+                    base_url.clone(),
+                    openweather::DEFAULT_BASE_URL
+                ),
+                resolve_url(
+                    ENV_OPENWEATHER_HISTORY_URL,
+                    history_base_url.clone(),
+                    openweather::DEFAULT_HISTORY_BASE_URL,
+                )
+            ),
+            ProviderUserInfo::WeatherApi { base_url, .. } => resolve_url(
+                ENV_WEATHERAPI_URL,
+                base_url.clone(),
+                weatherapi::DEFAULT_BASE_URL,
+            ),
+        }
+    }
+
+    /// Builds a weather provider from the given provider user info, sending requests according
+    /// to `policy`. If `usage_file` is given, the requests are counted in it and the limits of the
+    /// key are enforced.
+    pub fn build_provider(
+        self,
+        policy: RequestPolicy,
+        usage_file: Option<&Path>,
+    ) -> Box<dyn WeatherProvider> {
+        let usage = usage_file.map(|file| UsageTracker::new(file, &self));
+        match self {
+            ProviderUserInfo::OpenWeather {
+                api_key,
+                base_url,
+                history_base_url,
+                ..
+            } => {
+                let provider = OpenWeather::new(
+                    api_key,
+                    &resolve_url(ENV_OPENWEATHER_URL, base_url, openweather::DEFAULT_BASE_URL),
+                    &resolve_url(
+                        ENV_OPENWEATHER_HISTORY_URL,
+                        history_base_url,
+                        openweather::DEFAULT_HISTORY_BASE_URL,
+                    ),
+                    policy,
+                );
+                match usage {
+                    Some(usage) => Box::new(provider.with_usage(usage)),
+                    None => Box::new(provider),
+                }
+            }
+            ProviderUserInfo::WeatherApi {
+                api_key, base_url, ..
+            } => {
+                let provider = WeatherApi::new(
+                    api_key,
+                    &resolve_url(ENV_WEATHERAPI_URL, base_url, weatherapi::DEFAULT_BASE_URL),
+                    policy,
+                );
+                match usage {
+                    Some(usage) => Box::new(provider.with_usage(usage)),
+                    None => Box::new(provider),
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ProviderUserInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let api_key = if self.api_key().is_empty() { "" } else { "***" };
+        match self {
+            ProviderUserInfo::OpenWeather {
+                api_key_cmd,
+                base_url,
+                history_base_url,
+                limits,
+                ..
+            } => f
+                .debug_struct("OpenWeather")
+                .field("api_key", &api_key)
+                .field("api_key_cmd", api_key_cmd)
+                .field("base_url", base_url)
+                .field("history_base_url", history_base_url)
+                .field("limits", limits)
+                .finish(),
+            ProviderUserInfo::WeatherApi {
+                api_key_cmd,
+                base_url,
+                limits,
+                ..
+            } => f
+                .debug_struct("WeatherApi")
+                .field("api_key", &api_key)
+                .field("api_key_cmd", api_key_cmd)
+                .field("base_url", base_url)
+                .field("limits", limits)
+                .finish(),
+        }
+    }
+}
+
+/// Returns the name of the provider, used to tell apart data of different providers.
+pub fn name(kind: Provider) -> &'static str {
+    match kind {
+        Provider::OpenWeather => openweather::NAME,
+        Provider::WeatherApi => weatherapi::NAME,
+    }
+}
+
+/// Returns the environment variable giving the API key of the provider.
+pub fn api_key_var(kind: Provider) -> &'static str {
+    match kind {
+        Provider::OpenWeather => ENV_OPENWEATHER_API_KEY,
+        Provider::WeatherApi => ENV_WEATHERAPI_API_KEY,
     }
 }
 
+fn resolve_url(env: &str, configured: Option<String>, default: &str) -> String {
+    std::env::var(env)
+        .ok()
+        .filter(|x| !x.is_empty())
+        .or(configured)
+        .unwrap_or_else(|| default.to_owned())
+}
+
 /// A trait for weather providers that can provide current and historical weather data.
 #[async_trait]
-pub trait WeatherProvider {
-    /// Retrieves the current weather data for the specified city.
+pub trait WeatherProvider: Send + Sync {
+    /// Retrieves the current weather data for the specified location.
     ///
     /// # Arguments
     ///
-    /// * `city` - A string representing the name of the city for which to retrieve weather data.
+    /// * `location` - A `Location` for which to retrieve weather data.
     ///
     /// # Returns
     ///
-    /// A `Result` containing the `Weather` data for the specified city, or an error if the data could not be retrieved.
-    async fn get_weather_city(&self, city: &str) -> Result<Weather>;
+    /// A `Result` containing the `Weather` data for the specified location, or an error if the data could not be retrieved.
+    async fn get_weather(&self, location: &Location) -> Result<Weather>;
 
-    /// Retrieves the historical weather data for the specified city and date.
+    /// Retrieves the historical weather data for the specified location and date.
     ///
     /// # Arguments
     ///
-    /// * `city` - A string representing the name of the city for which to retrieve weather data.
+    /// * `location` - A `Location` for which to retrieve weather data.
     /// * `date` - A `DateTime<Utc>` representing the date for which to retrieve weather data.
     ///
     /// # Returns
     ///
-    /// A `Result` containing the `Weather` data for the specified city and date, or an error if the data could not be retrieved.
-    async fn get_history_weather_city(&self, city: &str, date: DateTime<Utc>) -> Result<Weather>;
+    /// A `Result` containing the `Weather` data for the specified location and date, or an error if the data could not be retrieved.
+    async fn get_history_weather(
+        &self,
+        location: &Location,
+        date: DateTime<Utc>,
+    ) -> Result<Weather>;
+
+    /// Retrieves the hourly historical weather data for the specified location between two dates.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - A `Location` for which to retrieve weather data.
+    /// * `from` - A `DateTime<Utc>` representing the start of the range, inclusive.
+    /// * `to` - A `DateTime<Utc>` representing the end of the range, inclusive.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Weather` time series ordered by time, or an error if the data could not be retrieved.
+    async fn get_history_range(
+        &self,
+        location: &Location,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Weather>>;
+
+    /// Retrieves the weather forecast for the specified location.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - A `Location` for which to retrieve weather data.
+    /// * `days` - The number of days, starting from today, to retrieve the forecast for.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Weather` time series ordered by time, or an error if the data could not be retrieved.
+    async fn get_forecast(&self, location: &Location, days: u32) -> Result<Vec<Weather>>;
+
+    /// Looks up the places whose name matches a query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The name of the place, optionally followed by the region or country, e.g. "London, CA".
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the matching places, the best match first, or an error if no place matches.
+    async fn search_places(&self, query: &str) -> Result<Vec<Place>>;
 }
 
 #[async_trait]
 impl WeatherProvider for OpenWeather {
-    async fn get_weather_city(&self, city: &str) -> Result<Weather> {
-        let w = self.current_weather_city(city).await?;
+    async fn get_weather(&self, location: &Location) -> Result<Weather> {
+        let w = self.current_weather(location).await?;
         Ok(openweather_extract_weather_data(w))
     }
 
-    async fn get_history_weather_city(&self, city: &str, date: DateTime<Utc>) -> Result<Weather> {
-        let weather = self.history_weather(city, date).await?;
+    async fn get_history_weather(
+        &self,
+        location: &Location,
+        date: DateTime<Utc>,
+    ) -> Result<Weather> {
+        let weather = self.history_weather(location, date, 1).await?;
         let ok_or = weather
             .list
             .into_iter()
             .next()
             .ok_or(Error::WeatherNoHistory)?;
-        Ok(openweather_extract_weather_data(ok_or))
+        Ok(openweather_extract_history_data(ok_or, location))
+    }
+
+    async fn get_history_range(
+        &self,
+        location: &Location,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Weather>> {
+        let mut series = Vec::new();
+        let mut start = from;
+        // a single request returns at most a week, so longer ranges are requested in chunks
+        while start <= to {
+            let cnt = ((to - start).num_hours() + 1).min(MAX_HISTORY_STEPS as i64) as u32;
+            let w = self.history_weather(location, start, cnt).await?;
+            series.extend(
+                w.list
+                    .into_iter()
+                    .filter(|x| (from.timestamp()..=to.timestamp()).contains(&x.dt))
+                    .map(|x| openweather_extract_history_data(x, location)),
+            );
+            start += chrono::Duration::hours(cnt as i64);
+        }
+        into_series(series)
+    }
+
+    async fn get_forecast(&self, location: &Location, days: u32) -> Result<Vec<Weather>> {
+        // forecast is given in 3 hour steps for at most 5 days
+        let cnt = (days * 8).min(40);
+        let w = self.forecast_weather(location, cnt).await?;
+        Ok(w.list
+            .into_iter()
+            .map(|x| openweather_extract_forecast_data(x, &w.city))
+            .collect())
+    }
+
+    async fn search_places(&self, query: &str) -> Result<Vec<Place>> {
+        let places = self.geocode(query).await?;
+        non_empty_places(
+            openweather::NAME,
+            query,
+            places
+                .into_iter()
+                .map(|x| Place {
+                    name: x.name,
+                    region: x.state,
+                    country: x.country,
+                    coordinates: Coordinates {
+                        lat: x.lat,
+                        lon: x.lon,
+                    },
+                })
+                .collect(),
+        )
     }
 }
 
 #[async_trait]
 impl WeatherProvider for WeatherApi {
-    async fn get_weather_city(&self, city: &str) -> Result<Weather> {
-        let w = self.current_weather_city(city).await?;
-        Ok({
-            let w = w;
-            Weather {
-                cloudiness: w.current.cloud,
-                description: w.current.condition.text,
-                temperature: w.current.temp_c,
-                wind: Wind {
-                    speed: w.current.wind_kph / 3.6,
-                    deg: w.current.wind_degree,
-                },
-                rain_volume: w.current.precip_mm,
-                visibility: (w.current.vis_km * 1000.) as i64,
-                location: format!(
-                    "{}, {}, {}",
-                    w.location.name, w.location.region, w.location.country
-                ),
-            }
+    async fn get_weather(&self, location: &Location) -> Result<Weather> {
+        let w = self.current_weather(location).await?;
+        Ok(Weather {
+            time: timestamp_to_utc(w.current.last_updated_epoch),
+            cloudiness: w.current.cloud,
+            description: w.current.condition.text,
+            temperature: w.current.temp_c,
+            feels_like: w.current.feelslike_c,
+            temperature_min: None,
+            temperature_max: None,
+            dew_point: None,
+            humidity: w.current.humidity,
+            pressure: w.current.pressure_mb,
+            uv_index: Some(w.current.uv),
+            wind: Wind {
+                speed: w.current.wind_kph / 3.6,
+                deg: w.current.wind_degree,
+                gust: Some(w.current.gust_kph / 3.6),
+            },
+            rain_volume: w.current.precip_mm,
+            visibility: Some(w.current.vis_km * 1000.),
+            sunrise: None,
+            sunset: None,
+            location: weatherapi_location_name(&w.location),
+            coordinates: Some(weatherapi_coordinates(&w.location)),
+            utc_offset: weatherapi_utc_offset(&w.location),
+            timezone: Some(w.location.tz_id),
+            provider: weatherapi::NAME.into(),
         })
     }
 
-    async fn get_history_weather_city(&self, city: &str, date: DateTime<Utc>) -> Result<Weather> {
-        let w = self.history_weather(city, date).await?;
-        let hour = w
+    async fn get_history_weather(
+        &self,
+        location: &Location,
+        date: DateTime<Utc>,
+    ) -> Result<Weather> {
+        let w = self.history_weather(location, date).await?;
+        let day = w
             .forecast
             .forecastday
             .first()
-            .ok_or(Error::WeatherNoHistory)?
+            .ok_or(Error::WeatherNoHistory)?;
+        let hour = day
             .hour
             .iter()
             .min_by_key(|x| (x.time_epoch - date.timestamp()).abs())
             .ok_or(Error::WeatherNoHistory)?;
-        Ok(Weather {
-            cloudiness: hour.cloud,
-            description: hour.condition.text.clone(),
-            temperature: hour.temp_c,
-            wind: Wind {
-                speed: hour.wind_kph / 3.6,
-                deg: hour.wind_degree,
-            },
-            rain_volume: hour.precip_mm,
-            visibility: (hour.vis_km * 1000.) as i64,
-            location: format!(
-                "{}, {}, {}",
-                w.location.name, w.location.region, w.location.country
-            ),
-        })
+        Ok(weatherapi_extract_hour_data(hour, day, &w.location))
+    }
+
+    async fn get_history_range(
+        &self,
+        location: &Location,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Weather>> {
+        // history is requested per day in the local time of the location, which is only known
+        // from a response
+        let first = self.history_weather(location, from).await?;
+        let offset = chrono::Duration::seconds(weatherapi_utc_offset(&first.location).unwrap_or(0));
+        // the first response is of the local day given by the date of `from` in UTC, which is
+        // another day than the local day of `from` when the offset crosses midnight
+        let first_day = first
+            .forecast
+            .forecastday
+            .first()
+            .and_then(|x| NaiveDate::parse_from_str(&x.date, "%Y-%m-%d").ok());
+        let mut series = Vec::new();
+        let mut day = (from + offset).date_naive();
+        while day <= (to + offset).date_naive() {
+            let w = if Some(day) == first_day {
+                first.clone()
+            } else {
+                let date = day.and_hms_opt(12, 0, 0).unwrap_or_default().and_utc();
+                self.history_weather(location, date).await?
+            };
+            for day in &w.forecast.forecastday {
+                series.extend(
+                    day.hour
+                        .iter()
+                        .filter(|x| (from.timestamp()..=to.timestamp()).contains(&x.time_epoch))
+                        .map(|hour| weatherapi_extract_hour_data(hour, day, &w.location)),
+                );
+            }
+            day = day.succ_opt().unwrap_or(NaiveDate::MAX);
+        }
+        into_series(series)
+    }
+
+    async fn get_forecast(&self, location: &Location, days: u32) -> Result<Vec<Weather>> {
+        let w = self.forecast_weather(location, days).await?;
+        // skip the hours of today that have already passed
+        let now = w.location.localtime_epoch;
+        Ok(w.forecast
+            .forecastday
+            .iter()
+            .flat_map(|day| day.hour.iter().map(move |hour| (hour, day)))
+            .filter(|(hour, _)| hour.time_epoch + 3600 > now)
+            .map(|(hour, day)| weatherapi_extract_hour_data(hour, day, &w.location))
+            .collect())
+    }
+
+    async fn search_places(&self, query: &str) -> Result<Vec<Place>> {
+        let places = self.search(query).await?;
+        non_empty_places(
+            weatherapi::NAME,
+            query,
+            places
+                .into_iter()
+                .map(|x| Place {
+                    name: x.name,
+                    region: Some(x.region).filter(|x| !x.is_empty()),
+                    country: x.country,
+                    coordinates: Coordinates {
+                        lat: x.lat,
+                        lon: x.lon,
+                    },
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Fails like a weather request for an unknown location if no place matches the query.
+fn non_empty_places(provider: &'static str, query: &str, places: Vec<Place>) -> Result<Vec<Place>> {
+    if places.is_empty() {
+        return Err(Error::UnknownLocation {
+            provider,
+            message: format!("no place matches \"{query}\""),
+        });
+    }
+    Ok(places)
+}
+
+/// Orders the series by time and removes duplicate steps, e.g. from overlapping requests.
+fn into_series(mut series: Vec<Weather>) -> Result<Vec<Weather>> {
+    if series.is_empty() {
+        return Err(Error::WeatherNoHistory);
+    }
+    series.sort_by_key(|x| x.time);
+    series.dedup_by_key(|x| x.time);
+    Ok(series)
+}
+
+fn weatherapi_location_name(location: &weatherapi::Location) -> String {
+    format!(
+        "{}, {}, {}",
+        location.name, location.region, location.country
+    )
+}
+
+fn weatherapi_coordinates(location: &weatherapi::Location) -> Coordinates {
+    Coordinates {
+        lat: location.lat,
+        lon: location.lon,
+    }
+}
+
+/// WeatherAPI doesn't return the UTC offset directly, so it is derived from the local time and
+/// the epoch of the location, rounded to 15 minutes.
+fn weatherapi_utc_offset(location: &weatherapi::Location) -> Option<i64> {
+    let local = NaiveDateTime::parse_from_str(&location.localtime, "%Y-%m-%d %H:%M").ok()?;
+    let diff = local.and_utc().timestamp() - location.localtime_epoch;
+    Some((diff as f64 / 900.).round() as i64 * 900)
+}
+
+/// Parses a local time of day such as "06:45 AM" on the given day into UTC.
+fn weatherapi_astro_time(
+    day: &Forecastday,
+    time: &str,
+    location: &weatherapi::Location,
+) -> Option<DateTime<Utc>> {
+    let local =
+        NaiveDateTime::parse_from_str(&format!("{} {}", day.date, time), "%Y-%m-%d %I:%M %p")
+            .ok()?;
+    let offset = weatherapi_utc_offset(location)?;
+    Some(timestamp_to_utc(local.and_utc().timestamp() - offset))
+}
+
+fn weatherapi_extract_hour_data(
+    hour: &Hour,
+    day: &Forecastday,
+    location: &weatherapi::Location,
+) -> Weather {
+    Weather {
+        time: timestamp_to_utc(hour.time_epoch),
+        cloudiness: hour.cloud,
+        description: hour.condition.text.clone(),
+        temperature: hour.temp_c,
+        feels_like: hour.feelslike_c,
+        temperature_min: Some(day.day.mintemp_c),
+        temperature_max: Some(day.day.maxtemp_c),
+        dew_point: Some(hour.dewpoint_c),
+        humidity: hour.humidity,
+        pressure: hour.pressure_mb,
+        uv_index: Some(hour.uv),
+        wind: Wind {
+            speed: hour.wind_kph / 3.6,
+            deg: hour.wind_degree,
+            gust: Some(hour.gust_kph / 3.6),
+        },
+        rain_volume: hour.precip_mm,
+        visibility: Some(hour.vis_km * 1000.),
+        sunrise: weatherapi_astro_time(day, &day.astro.sunrise, location),
+        sunset: weatherapi_astro_time(day, &day.astro.sunset, location),
+        location: weatherapi_location_name(location),
+        coordinates: Some(weatherapi_coordinates(location)),
+        utc_offset: weatherapi_utc_offset(location),
+        timezone: Some(location.tz_id.clone()),
+        provider: weatherapi::NAME.into(),
     }
 }
 
 fn openweather_extract_weather_data(w: CurrentWeather) -> Weather {
     Weather {
+        time: timestamp_to_utc(w.dt),
         cloudiness: w.clouds.all,
         rain_volume: w.rain.and_then(|x| x.n1h).unwrap_or(0.),
         temperature: w.main.temp,
-        visibility: w.visibility,
+        feels_like: w.main.feels_like,
+        temperature_min: Some(w.main.temp_min),
+        temperature_max: Some(w.main.temp_max),
+        dew_point: None,
+        humidity: w.main.humidity,
+        pressure: w.main.pressure as f64,
+        uv_index: None,
+        visibility: Some(w.visibility as f64),
         wind: Wind {
             speed: w.wind.speed,
             deg: w.wind.deg,
+            gust: w.wind.gust,
         },
         description: w
             .weather
             .first()
             .map(|x| x.description.clone())
             .unwrap_or_else(|| "".into()),
+        sunrise: Some(timestamp_to_utc(w.sys.sunrise)),
+        sunset: Some(timestamp_to_utc(w.sys.sunset)),
         location: format!("{}, {}", w.name, w.sys.country),
+        coordinates: Some(Coordinates {
+            lat: w.coord.lat,
+            lon: w.coord.lon,
+        }),
+        utc_offset: Some(w.timezone),
+        timezone: None,
+        provider: openweather::NAME.into(),
+    }
+}
+
+/// The history API doesn't return any information about the location, so the requested location
+/// is used as is.
+fn openweather_extract_history_data(w: HistoryItem, location: &Location) -> Weather {
+    Weather {
+        time: timestamp_to_utc(w.dt),
+        cloudiness: w.clouds.all,
+        rain_volume: w.rain.and_then(|x| x.n1h).unwrap_or(0.),
+        temperature: w.main.temp,
+        feels_like: w.main.feels_like,
+        temperature_min: Some(w.main.temp_min),
+        temperature_max: Some(w.main.temp_max),
+        dew_point: None,
+        humidity: w.main.humidity,
+        pressure: w.main.pressure as f64,
+        uv_index: None,
+        visibility: None,
+        wind: Wind {
+            speed: w.wind.speed,
+            deg: w.wind.deg,
+            gust: w.wind.gust,
+        },
+        description: w
+            .weather
+            .first()
+            .map(|x| x.description.clone())
+            .unwrap_or_else(|| "".into()),
+        sunrise: None,
+        sunset: None,
+        location: location.to_string(),
+        coordinates: match location {
+            Location::Coordinates(coord) => Some(*coord),
+            _ => None,
+        },
+        utc_offset: None,
+        timezone: None,
+        provider: openweather::NAME.into(),
+    }
+}
+
+fn openweather_extract_forecast_data(w: ForecastItem, city: &City) -> Weather {
+    Weather {
+        time: timestamp_to_utc(w.dt),
+        cloudiness: w.clouds.all,
+        rain_volume: w.rain.and_then(|x| x.n3h).unwrap_or(0.),
+        temperature: w.main.temp,
+        feels_like: w.main.feels_like,
+        temperature_min: Some(w.main.temp_min),
+        temperature_max: Some(w.main.temp_max),
+        dew_point: None,
+        humidity: w.main.humidity,
+        pressure: w.main.pressure as f64,
+        uv_index: None,
+        visibility: w.visibility.map(|x| x as f64),
+        wind: Wind {
+            speed: w.wind.speed,
+            deg: w.wind.deg,
+            gust: w.wind.gust,
+        },
+        description: w
+            .weather
+            .first()
+            .map(|x| x.description.clone())
+            .unwrap_or_else(|| "".into()),
+        // the city's sunrise and sunset are only given for the current day
+        sunrise: None,
+        sunset: None,
+        location: format!("{}, {}", city.name, city.country),
+        coordinates: Some(Coordinates {
+            lat: city.coord.lat,
+            lon: city.coord.lon,
+        }),
+        utc_offset: Some(city.timezone),
+        timezone: None,
+        provider: openweather::NAME.into(),
+    }
+}
+
+fn timestamp_to_utc(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default()
+}
+
+/// A struct representing a place found by looking up its name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Place {
+    /// The name of the place, e.g. "London".
+    pub name: String,
+
+    /// The region, state or county of the place, if the provider returns it.
+    pub region: Option<String>,
+
+    /// The country of the place, as a code or a name depending on the provider.
+    pub country: String,
+
+    /// The coordinates of the place.
+    pub coordinates: Coordinates,
+}
+
+impl std::fmt::Display for Place {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(region) = &self.region {
+            write!(f, ", {region}")?;
+        }
+        write!(f, ", {} ({})", self.country, self.coordinates)
     }
 }
 
 /// A struct representing weather information.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Weather {
+    /// The time the weather information pertains to, i.e. the observation time for current and
+    /// historical weather, or the forecasted time.
+    pub time: DateTime<Utc>,
+
     /// The percentage of sky covered by clouds.
     pub cloudiness: i64,
 
@@ -176,6 +953,33 @@ pub struct Weather {
     /// The temperature in degrees Celsius.
     pub temperature: f64,
 
+    /// The apparent temperature in degrees Celsius.
+    pub feels_like: f64,
+
+    /// The minimum temperature in degrees Celsius.
+    ///
+    /// For OpenWeather this is the minimum currently observed within the area, for WeatherAPI the
+    /// minimum of the day.
+    pub temperature_min: Option<f64>,
+
+    /// The maximum temperature in degrees Celsius.
+    ///
+    /// For OpenWeather this is the maximum currently observed within the area, for WeatherAPI the
+    /// maximum of the day.
+    pub temperature_max: Option<f64>,
+
+    /// The dew point in degrees Celsius.
+    pub dew_point: Option<f64>,
+
+    /// The relative humidity in percent.
+    pub humidity: i64,
+
+    /// The atmospheric pressure at sea level in hectopascals.
+    pub pressure: f64,
+
+    /// The UV index.
+    pub uv_index: Option<f64>,
+
     /// The wind speed and direction.
     pub wind: Wind,
 
@@ -183,14 +987,77 @@ pub struct Weather {
     pub rain_volume: f64,
 
     /// The visibility in meters.
-    pub visibility: i64,
+    pub visibility: Option<f64>,
+
+    /// The time of sunrise on the day.
+    pub sunrise: Option<DateTime<Utc>>,
+
+    /// The time of sunset on the day.
+    pub sunset: Option<DateTime<Utc>>,
 
     /// The name of the location for which the weather information pertains.
     pub location: String,
+
+    /// The coordinates of the location as resolved by the provider, if the provider returns them.
+    pub coordinates: Option<Coordinates>,
+
+    /// The offset of the location's local time from UTC in seconds.
+    pub utc_offset: Option<i64>,
+
+    /// The IANA name of the location's timezone, e.g. "Europe/London".
+    pub timezone: Option<String>,
+
+    /// The name of the provider that returned the weather information, e.g. "openweather".
+    pub provider: String,
+}
+
+impl Weather {
+    /// Converts a time to the local time of the location, or returns it in UTC if the offset is
+    /// unknown.
+    pub fn local_time(&self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        let offset = self
+            .utc_offset
+            .and_then(|x| FixedOffset::east_opt(x as i32))
+            .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+        time.with_timezone(&offset)
+    }
+}
+
+/// Returns light rain in London on 2023-03-15 at 12:00 UTC as reported by OpenWeather, for tests
+/// to change the fields they check.
+#[cfg(test)]
+pub(crate) fn sample_weather() -> Weather {
+    Weather {
+        time: Utc.with_ymd_and_hms(2023, 3, 15, 12, 0, 0).unwrap(),
+        cloudiness: 75,
+        description: "Light rain".into(),
+        temperature: 12.5,
+        feels_like: 11.0,
+        temperature_min: None,
+        temperature_max: None,
+        dew_point: None,
+        humidity: 80,
+        pressure: 1012.0,
+        uv_index: None,
+        wind: Wind {
+            speed: 4.5,
+            deg: 270,
+            gust: None,
+        },
+        rain_volume: 0.3,
+        visibility: None,
+        sunrise: None,
+        sunset: None,
+        location: "London".into(),
+        coordinates: None,
+        utc_offset: Some(0),
+        timezone: None,
+        provider: "openweather".into(),
+    }
 }
 
 /// A struct representing wind information.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Wind {
     /// The wind speed in meters per second.
     pub speed: f64,
@@ -199,4 +1066,63 @@ pub struct Wind {
     ///
     /// A value of 0 degrees indicates a northerly wind, while a value of 180 degrees indicates a southerly wind.
     pub deg: i64,
+
+    /// The speed of wind gusts in meters per second.
+    pub gust: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_debug_hides_api_key() {
+        let mut info = ProviderUserInfo::new(Provider::WeatherApi, "secret-key".into());
+        assert!(!format!("{info:?}").contains("secret-key"));
+        assert!(format!("{info:#?}").contains("***"));
+
+        info.set_api_key(String::new());
+        assert!(matches!(
+            info.resolve_api_key(),
+            Err(Error::ApiKeyUnavailable { .. })
+        ));
+    }
+
+    #[rstest]
+    #[case("2023-03-20 12:00", 1679313630, 0)]
+    #[case("2023-03-20 13:00", 1679313630, 3600)]
+    #[case("2023-03-20 17:30", 1679313630, 19800)]
+    #[case("2023-03-20 07:00", 1679313630, -18000)]
+    fn test_weatherapi_utc_offset(
+        #[case] localtime: &str,
+        #[case] epoch: i64,
+        #[case] offset: i64,
+    ) {
+        let location = weatherapi::Location {
+            localtime: localtime.into(),
+            localtime_epoch: epoch,
+            ..Default::default()
+        };
+        assert_eq!(weatherapi_utc_offset(&location), Some(offset));
+    }
+
+    #[test]
+    fn test_weatherapi_astro_time() {
+        let location = weatherapi::Location {
+            localtime: "2023-03-20 13:00".into(),
+            localtime_epoch: 1679313600,
+            ..Default::default()
+        };
+        let day = Forecastday {
+            date: "2023-03-20".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            weatherapi_astro_time(&day, "06:05 AM", &location),
+            Some(Utc.with_ymd_and_hms(2023, 3, 20, 5, 5, 0).unwrap())
+        );
+        assert_eq!(weatherapi_astro_time(&day, "No sunset", &location), None);
+    }
 }
@@ -0,0 +1,274 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+
+use super::{Place, Weather, WeatherProvider, Wind};
+use crate::{
+    error::{Error, Result},
+    location::Location,
+};
+
+/// A weather provider that asks all providers concurrently and merges their responses into a
+/// consensus.
+pub struct AggregateProvider {
+    providers: Vec<(&'static str, Box<dyn WeatherProvider>)>,
+}
+
+impl AggregateProvider {
+    /// Creates the provider from named providers, the most preferred one first.
+    pub fn new(providers: Vec<(&'static str, Box<dyn WeatherProvider>)>) -> Self {
+        Self { providers }
+    }
+
+    /// Returns the current weather reported by each provider.
+    pub async fn get_each_weather(
+        &self,
+        location: &Location,
+    ) -> Vec<(&'static str, Result<Weather>)> {
+        join_all(
+            self.providers.iter().map(|(name, provider)| async move {
+                (*name, provider.get_weather(location).await)
+            }),
+        )
+        .await
+    }
+
+    /// Returns the historical weather reported by each provider.
+    pub async fn get_each_history_weather(
+        &self,
+        location: &Location,
+        date: DateTime<Utc>,
+    ) -> Vec<(&'static str, Result<Weather>)> {
+        join_all(self.providers.iter().map(|(name, provider)| async move {
+            (*name, provider.get_history_weather(location, date).await)
+        }))
+        .await
+    }
+
+    /// Returns the historical weather series reported by each provider.
+    pub async fn get_each_history_range(
+        &self,
+        location: &Location,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<(&'static str, Result<Vec<Weather>>)> {
+        join_all(self.providers.iter().map(|(name, provider)| async move {
+            (*name, provider.get_history_range(location, from, to).await)
+        }))
+        .await
+    }
+
+    /// Returns the forecast reported by each provider.
+    pub async fn get_each_forecast(
+        &self,
+        location: &Location,
+        days: u32,
+    ) -> Vec<(&'static str, Result<Vec<Weather>>)> {
+        join_all(self.providers.iter().map(|(name, provider)| async move {
+            (*name, provider.get_forecast(location, days).await)
+        }))
+        .await
+    }
+}
+
+/// Keeps the successful responses, reporting the failed ones. Fails with the error of the last
+/// provider if none succeeded.
+pub fn successful_reports<T>(responses: Vec<(&'static str, Result<T>)>) -> Result<Vec<T>> {
+    let mut last_err = Error::ProviderNotConfigured;
+    let mut ok = Vec::new();
+    for (name, response) in responses {
+        match response {
+            Ok(x) => ok.push(x),
+            Err(e) => {
+                eprintln!("{name} failed: {e}, leaving it out");
+                last_err = e;
+            }
+        }
+    }
+    if ok.is_empty() {
+        return Err(last_err);
+    }
+    Ok(ok)
+}
+
+#[async_trait]
+impl WeatherProvider for AggregateProvider {
+    async fn get_weather(&self, location: &Location) -> Result<Weather> {
+        let reports = successful_reports(self.get_each_weather(location).await)?;
+        Ok(merge(&reports))
+    }
+
+    async fn get_history_weather(
+        &self,
+        location: &Location,
+        date: DateTime<Utc>,
+    ) -> Result<Weather> {
+        let reports = successful_reports(self.get_each_history_weather(location, date).await)?;
+        Ok(merge(&reports))
+    }
+
+    async fn get_history_range(
+        &self,
+        location: &Location,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Weather>> {
+        let series = successful_reports(self.get_each_history_range(location, from, to).await)?;
+        Ok(merge_series(&series))
+    }
+
+    async fn get_forecast(&self, location: &Location, days: u32) -> Result<Vec<Weather>> {
+        let forecasts = successful_reports(self.get_each_forecast(location, days).await)?;
+        Ok(merge_series(&forecasts))
+    }
+
+    /// Places can't be merged, so the places found by the most preferred provider are returned.
+    async fn search_places(&self, query: &str) -> Result<Vec<Place>> {
+        let mut last_err = Error::ProviderNotConfigured;
+        for (name, provider) in &self.providers {
+            match provider.search_places(query).await {
+                Ok(places) => return Ok(places),
+                Err(e) => {
+                    eprintln!("{name} failed: {e}, asking the next provider");
+                    last_err = e;
+                }
+            }
+        }
+        Err(last_err)
+    }
+}
+
+/// Merges time series of several providers, using the time steps of the first one.
+///
+/// Providers forecast in different steps, so each step is merged with the steps of the other
+/// providers at the same time, if they have one.
+pub fn merge_series(series: &[Vec<Weather>]) -> Vec<Weather> {
+    let Some((first, others)) = series.split_first() else {
+        return Vec::new();
+    };
+    first
+        .iter()
+        .map(|weather| {
+            let mut reports = vec![weather.clone()];
+            reports.extend(
+                others
+                    .iter()
+                    .filter_map(|x| x.iter().find(|other| other.time == weather.time))
+                    .cloned(),
+            );
+            merge(&reports)
+        })
+        .collect()
+}
+
+/// Merges the reports of several providers into a consensus.
+///
+/// The temperatures are the median of the reports, the wind is the vector average, the
+/// precipitation is the maximum and the other quantities are the mean. Descriptive fields are
+/// taken from the first report.
+///
+/// # Panics
+///
+/// Panics if `reports` is empty.
+pub fn merge(reports: &[Weather]) -> Weather {
+    let mut merged = reports[0].clone();
+    let values = |f: fn(&Weather) -> f64| reports.iter().map(f).collect::<Vec<_>>();
+    let options = |f: fn(&Weather) -> Option<f64>| reports.iter().filter_map(f).collect::<Vec<_>>();
+
+    merged.temperature = median(values(|x| x.temperature));
+    merged.feels_like = median(values(|x| x.feels_like));
+    merged.temperature_min = options(|x| x.temperature_min).into_iter().reduce(f64::min);
+    merged.temperature_max = options(|x| x.temperature_max).into_iter().reduce(f64::max);
+    merged.dew_point = Some(options(|x| x.dew_point))
+        .filter(|x| !x.is_empty())
+        .map(median);
+    merged.cloudiness = mean(&values(|x| x.cloudiness as f64)).round() as i64;
+    merged.humidity = mean(&values(|x| x.humidity as f64)).round() as i64;
+    merged.pressure = mean(&values(|x| x.pressure));
+    merged.uv_index = Some(options(|x| x.uv_index))
+        .filter(|x| !x.is_empty())
+        .map(|x| mean(&x));
+    merged.visibility = Some(options(|x| x.visibility))
+        .filter(|x| !x.is_empty())
+        .map(|x| mean(&x));
+    merged.rain_volume = values(|x| x.rain_volume).into_iter().fold(0., f64::max);
+    merged.wind = Wind {
+        gust: options(|x| x.wind.gust).into_iter().reduce(f64::max),
+        ..vector_average(reports.iter().map(|x| &x.wind))
+    };
+    merged.coordinates = reports.iter().find_map(|x| x.coordinates);
+    merged.provider = reports
+        .iter()
+        .map(|x| x.provider.as_str())
+        .collect::<Vec<_>>()
+        .join("+");
+    merged
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.
+    } else {
+        values[mid]
+    }
+}
+
+/// Averages the wind as vectors, so that e.g. winds from 350° and 10° average to 0° rather than
+/// 180°. Opposite winds cancel out.
+fn vector_average<'a>(winds: impl Iterator<Item = &'a Wind>) -> Wind {
+    let (mut u, mut v, mut n) = (0., 0., 0.);
+    for wind in winds {
+        let rad = (wind.deg as f64).to_radians();
+        u += wind.speed * rad.sin();
+        v += wind.speed * rad.cos();
+        n += 1.;
+    }
+    let (u, v) = (u / n, v / n);
+    Wind {
+        speed: u.hypot(v),
+        deg: (u.atan2(v).to_degrees().round() as i64).rem_euclid(360),
+        gust: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn wind(speed: f64, deg: i64) -> Wind {
+        Wind {
+            speed,
+            deg,
+            gust: None,
+        }
+    }
+
+    #[rstest]
+    #[case(vec![wind(5., 350), wind(5., 10)], 0)]
+    #[case(vec![wind(2., 90), wind(2., 180)], 135)]
+    #[case(vec![wind(1., 270)], 270)]
+    fn test_vector_average(#[case] winds: Vec<Wind>, #[case] deg: i64) {
+        assert_eq!(vector_average(winds.iter()).deg, deg);
+    }
+
+    #[test]
+    fn test_vector_average_opposite() {
+        let winds = [wind(3., 0), wind(3., 180)];
+        assert!(vector_average(winds.iter()).speed < 1e-9);
+    }
+
+    #[rstest]
+    #[case(vec![3., 1., 2.], 2.)]
+    #[case(vec![4., 1., 3., 2.], 2.5)]
+    fn test_median(#[case] values: Vec<f64>, #[case] expected: f64) {
+        assert_eq!(median(values), expected);
+    }
+}
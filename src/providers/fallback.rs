@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{Place, Weather, WeatherProvider};
+use crate::{
+    error::{Error, Result},
+    location::Location,
+};
+
+/// A weather provider that asks several providers in order, falling back to the next one when
+/// a provider fails.
+pub struct FallbackProvider {
+    providers: Vec<(&'static str, Box<dyn WeatherProvider>)>,
+}
+
+impl FallbackProvider {
+    /// Creates the provider from named providers in the order they should be asked.
+    pub fn new(providers: Vec<(&'static str, Box<dyn WeatherProvider>)>) -> Self {
+        Self { providers }
+    }
+
+    /// Returns the first successful response, or the error of the last provider if all fail.
+    async fn first_ok<'a, T, F>(&'a self, mut request: F) -> Result<T>
+    where
+        F: FnMut(
+            &'a dyn WeatherProvider,
+        )
+            -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<T>> + Send + 'a>>,
+    {
+        let mut last_err = Error::ProviderNotConfigured;
+        for (i, (name, provider)) in self.providers.iter().enumerate() {
+            match request(provider.as_ref()).await {
+                Ok(x) => return Ok(x),
+                Err(e) => {
+                    if let Some((next, _)) = self.providers.get(i + 1) {
+                        eprintln!("{name} failed: {e}, falling back to {next}");
+                    }
+                    last_err = e;
+                }
+            }
+        }
+        Err(last_err)
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for FallbackProvider {
+    async fn get_weather(&self, location: &Location) -> Result<Weather> {
+        self.first_ok(|p| p.get_weather(location)).await
+    }
+
+    async fn get_history_weather(
+        &self,
+        location: &Location,
+        date: DateTime<Utc>,
+    ) -> Result<Weather> {
+        self.first_ok(|p| p.get_history_weather(location, date))
+            .await
+    }
+
+    async fn get_history_range(
+        &self,
+        location: &Location,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Weather>> {
+        self.first_ok(|p| p.get_history_range(location, from, to))
+            .await
+    }
+
+    async fn get_forecast(&self, location: &Location, days: u32) -> Result<Vec<Weather>> {
+        self.first_ok(|p| p.get_forecast(location, days)).await
+    }
+
+    async fn search_places(&self, query: &str) -> Result<Vec<Place>> {
+        self.first_ok(|p| p.search_places(query)).await
+    }
+}
@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use tokio::sync::OnceCell;
+
+use super::{Place, ProviderUserInfo, Weather, WeatherProvider};
+use crate::{error::Result, location::Location, network::RequestPolicy};
+
+/// A weather provider that resolves the API key and builds the provider on the first request,
+/// so a key that cannot be resolved only fails the requests of this provider.
+pub struct LazyProvider {
+    info: ProviderUserInfo,
+    policy: RequestPolicy,
+    usage_file: Option<PathBuf>,
+    provider: OnceCell<Box<dyn WeatherProvider>>,
+}
+
+impl LazyProvider {
+    /// Creates the provider from the given provider user info, sending requests according to
+    /// `policy`. If `usage_file` is given, the requests are counted in it and the limits of the
+    /// key are enforced.
+    pub fn new(info: ProviderUserInfo, policy: RequestPolicy, usage_file: Option<PathBuf>) -> Self {
+        Self {
+            info,
+            policy,
+            usage_file,
+            provider: OnceCell::new(),
+        }
+    }
+
+    /// Returns the built provider, resolving the API key if it wasn't yet. A failure isn't kept,
+    /// so the next request tries again.
+    async fn provider(&self) -> Result<&dyn WeatherProvider> {
+        let provider = self
+            .provider
+            .get_or_try_init(|| async {
+                self.info
+                    .clone()
+                    .resolve_api_key()
+                    .map(|info| info.build_provider(self.policy, self.usage_file.as_deref()))
+            })
+            .await?;
+        Ok(provider.as_ref())
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for LazyProvider {
+    async fn get_weather(&self, location: &Location) -> Result<Weather> {
+        self.provider().await?.get_weather(location).await
+    }
+
+    async fn get_history_weather(
+        &self,
+        location: &Location,
+        date: DateTime<Utc>,
+    ) -> Result<Weather> {
+        self.provider()
+            .await?
+            .get_history_weather(location, date)
+            .await
+    }
+
+    async fn get_history_range(
+        &self,
+        location: &Location,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Weather>> {
+        self.provider()
+            .await?
+            .get_history_range(location, from, to)
+            .await
+    }
+
+    async fn get_forecast(&self, location: &Location, days: u32) -> Result<Vec<Weather>> {
+        self.provider().await?.get_forecast(location, days).await
+    }
+
+    async fn search_places(&self, query: &str) -> Result<Vec<Place>> {
+        self.provider().await?.search_places(query).await
+    }
+}
@@ -1,54 +1,201 @@
-use crate::error::Result;
+use crate::{
+    error::{Error, Result},
+    location::Location,
+    network::{build_url, HttpClient, HttpResponse, RequestPolicy},
+    usage::UsageTracker,
+};
 use chrono::{DateTime, Utc};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
+pub const NAME: &str = "openweather";
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openweathermap.org";
+pub const DEFAULT_HISTORY_BASE_URL: &str = "https://history.openweathermap.org";
+
+/// The most places returned by the geocoding API.
+pub const MAX_PLACES: u32 = 5;
+
+/// The maximum number of hourly steps returned by a single history request, i.e. one week.
+pub const MAX_HISTORY_STEPS: u32 = 168;
+
 pub struct OpenWeather {
     api_key: String,
-    client: Client,
+    base_url: String,
+    history_base_url: String,
+    http: HttpClient,
 }
 
 impl OpenWeather {
-    pub fn new(api_key: String) -> Self {
+    /// Creates the provider. `base_url` is used for current weather and forecasts,
+    /// `history_base_url` for historical weather.
+    pub fn new(
+        api_key: String,
+        base_url: &str,
+        history_base_url: &str,
+        policy: RequestPolicy,
+    ) -> Self {
         Self {
             api_key,
-            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            history_base_url: history_base_url.trim_end_matches('/').to_owned(),
+            http: HttpClient::new(NAME, policy),
         }
     }
 
-    fn format_addr(&self, query: &str) -> String {
-        const BASE_HTTP: &str = "https://api.openweathermap.org/data/2.5/";
-        format!(
-            "{}{}&appid={}&units=metric",
-            &BASE_HTTP, &query, self.api_key
-        )
+    /// Counts the requests sent with the key, enforcing its limits.
+    pub fn with_usage(mut self, usage: UsageTracker) -> Self {
+        self.http = self.http.with_usage(usage);
+        self
+    }
+
+    fn format_addr(&self, endpoint: &str, mut params: Vec<(&str, String)>) -> Result<String> {
+        params.push(("appid", self.api_key.clone()));
+        params.push(("units", "metric".into()));
+        let path = format!("data/2.5/{endpoint}");
+        build_url("base_url", &self.base_url, &path, &params)
+    }
+
+    fn format_addr_history(
+        &self,
+        endpoint: &str,
+        mut params: Vec<(&str, String)>,
+    ) -> Result<String> {
+        params.push(("appid", self.api_key.clone()));
+        params.push(("type", "hour".into()));
+        params.push(("units", "metric".into()));
+        let path = format!("data/2.5/{endpoint}");
+        build_url("history_base_url", &self.history_base_url, &path, &params)
     }
 
-    fn format_addr_history(&self, query: &str) -> String {
-        const BASE_HTTP: &str = "https://history.openweathermap.org/data/2.5/";
-        format!("{}{}&appid={}&type=hour", &BASE_HTTP, &query, self.api_key)
+    /// Get hourly history starting at `start`. `cnt` is the number of steps, at most
+    /// `MAX_HISTORY_STEPS`.
+    pub async fn history_weather(
+        &self,
+        location: &Location,
+        start: DateTime<Utc>,
+        cnt: u32,
+    ) -> Result<HistoryWeather> {
+        let mut params = location_query(location);
+        params.push(("start", start.timestamp().to_string()));
+        params.push(("cnt", cnt.to_string()));
+        let addr = self.format_addr_history("history/city", params)?;
+        let response = self.http.get(&addr).await?;
+        Ok(serde_json::from_str(&check(response)?)?)
     }
 
-    pub async fn history_weather(&self, city: &str, date: DateTime<Utc>) -> Result<HistoryWeather> {
-        let timestamp = date.timestamp();
-        let addr = self.format_addr_history(&format!(
-            "history/city?q={city}&type=hour&start={timestamp}&cnt=1"
-        ));
-        let response = self.client.get(&addr).send().await?.error_for_status()?;
-        Ok(response.json().await?)
+    pub async fn current_weather(&self, location: &Location) -> Result<CurrentWeather> {
+        let addr = self.format_addr("weather", location_query(location))?;
+        let response = self.http.get(&addr).await?;
+        Ok(serde_json::from_str(&check(response)?)?)
+    }
+
+    /// Get the places whose name matches `query`, at most `MAX_PLACES`.
+    pub async fn geocode(&self, query: &str) -> Result<Vec<GeoPlace>> {
+        let params = [
+            ("q", query.to_owned()),
+            ("limit", MAX_PLACES.to_string()),
+            ("appid", self.api_key.clone()),
+        ];
+        let addr = build_url("base_url", &self.base_url, "geo/1.0/direct", &params)?;
+        let response = self.http.get(&addr).await?;
+        Ok(serde_json::from_str(&check(response)?)?)
+    }
+
+    /// Get forecast in 3 hour steps. `cnt` is the number of steps, at most 40 (5 days).
+    pub async fn forecast_weather(&self, location: &Location, cnt: u32) -> Result<ForecastWeather> {
+        let mut params = location_query(location);
+        params.push(("cnt", cnt.to_string()));
+        let addr = self.format_addr("forecast", params)?;
+        let response = self.http.get(&addr).await?;
+        Ok(serde_json::from_str(&check(response)?)?)
+    }
+}
+
+/// Turns an error response into an `Error` carrying the message given by OpenWeather.
+fn check(response: HttpResponse) -> Result<String> {
+    let HttpResponse { status, body } = response;
+    if status.is_success() {
+        return Ok(body);
     }
+    let message = serde_json::from_str::<ErrorResponse>(&body)
+        .map(|x| x.message)
+        .unwrap_or(body);
+    Err(Error::from_status(NAME, status.as_u16(), message))
+}
 
-    pub async fn current_weather_city(&self, city: &str) -> Result<CurrentWeather> {
-        let addr = self.format_addr(&format!("weather?q={city}"));
-        let response = self.client.get(&addr).send().await?.error_for_status()?;
-        Ok(response.json().await?)
+/// Query parameters selecting the location, unencoded.
+fn location_query(location: &Location) -> Vec<(&'static str, String)> {
+    match location {
+        Location::City(city) => vec![("q", city.clone())],
+        Location::Coordinates(coord) => vec![
+            ("lat", coord.lat.to_string()),
+            ("lon", coord.lon.to_string()),
+        ],
+        Location::PostalCode(code) => vec![("zip", code.clone())],
+        Location::Id(id) => vec![("id", id.to_string())],
     }
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub message: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeoPlace {
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub country: String,
+    pub state: Option<String>,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HistoryWeather {
-    pub list: Vec<CurrentWeather>,
+    pub list: Vec<HistoryItem>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryItem {
+    pub dt: i64,
+    pub main: Main,
+    pub weather: Vec<Weather>,
+    pub clouds: Clouds,
+    pub wind: Wind,
+    pub rain: Option<Rain>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForecastWeather {
+    pub list: Vec<ForecastItem>,
+    pub city: City,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastItem {
+    pub dt: i64,
+    pub main: Main,
+    pub weather: Vec<Weather>,
+    pub clouds: Clouds,
+    pub wind: Wind,
+    pub visibility: Option<i64>,
+    pub pop: f64,
+    pub rain: Option<Rain>,
 }
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct City {
+    pub id: i64,
+    pub name: String,
+    pub coord: Coord,
+    pub country: String,
+    pub timezone: i64,
+    pub sunrise: i64,
+    pub sunset: i64,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CurrentWeather {
@@ -1,41 +1,152 @@
-use crate::error::Result;
+use crate::{
+    error::{Error, Result},
+    location,
+    network::{build_url, HttpClient, HttpResponse, RequestPolicy},
+    usage::UsageTracker,
+};
 use chrono::{DateTime, Utc};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
+pub const NAME: &str = "weatherapi";
+
+pub const DEFAULT_BASE_URL: &str = "https://api.weatherapi.com";
+
 pub struct WeatherApi {
     api_key: String,
-    client: Client,
+    base_url: String,
+    http: HttpClient,
 }
 
 impl WeatherApi {
-    pub fn new(api_key: String) -> Self {
+    pub fn new(api_key: String, base_url: &str, policy: RequestPolicy) -> Self {
         Self {
             api_key,
-            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            http: HttpClient::new(NAME, policy),
         }
     }
 
-    fn format_addr(&self, query: &str) -> String {
-        const BASE_HTTP: &str = "https://api.weatherapi.com/v1/";
-        format!("{}{}&key={}", &BASE_HTTP, &query, self.api_key)
+    /// Counts the requests sent with the key, enforcing its limits.
+    pub fn with_usage(mut self, usage: UsageTracker) -> Self {
+        self.http = self.http.with_usage(usage);
+        self
+    }
+
+    fn format_addr(&self, endpoint: &str, params: &[(&str, String)]) -> Result<String> {
+        let mut params = params.to_vec();
+        params.push(("key", self.api_key.clone()));
+        let path = format!("v1/{endpoint}");
+        build_url("base_url", &self.base_url, &path, &params)
     }
 
     /// Get weather for a date. Returns hourly history for a particular day.
-    pub async fn history_weather(&self, city: &str, date: DateTime<Utc>) -> Result<WeatherHistory> {
-        let date = date.format("%Y-%m-%d");
-        let addr = self.format_addr(&format!("history.json?q={city}&dt={date}"));
-        let response = self.client.get(&addr).send().await?.error_for_status()?;
-        Ok(response.json().await?)
+    pub async fn history_weather(
+        &self,
+        location: &location::Location,
+        date: DateTime<Utc>,
+    ) -> Result<WeatherHistory> {
+        let params = [
+            ("q", location_query(location)),
+            ("dt", date.format("%Y-%m-%d").to_string()),
+        ];
+        let addr = self.format_addr("history.json", &params)?;
+        let response = self.http.get(&addr).await?;
+        Ok(serde_json::from_str(&check(response)?)?)
+    }
+
+    pub async fn current_weather(&self, location: &location::Location) -> Result<Weather> {
+        let params = [("q", location_query(location)), ("aqi", "no".into())];
+        let addr = self.format_addr("current.json", &params)?;
+        let response = self.http.get(&addr).await?;
+        Ok(serde_json::from_str(&check(response)?)?)
+    }
+
+    /// Get the places whose name matches `query`.
+    pub async fn search(&self, query: &str) -> Result<Vec<SearchPlace>> {
+        let addr = self.format_addr("search.json", &[("q", query.to_owned())])?;
+        let response = self.http.get(&addr).await?;
+        Ok(serde_json::from_str(&check(response)?)?)
+    }
+
+    /// Get forecast for the next `days` days. Returns hourly forecast for each day.
+    pub async fn forecast_weather(
+        &self,
+        location: &location::Location,
+        days: u32,
+    ) -> Result<WeatherForecast> {
+        let params = [
+            ("q", location_query(location)),
+            ("days", days.to_string()),
+            ("aqi", "no".into()),
+            ("alerts", "no".into()),
+        ];
+        let addr = self.format_addr("forecast.json", &params)?;
+        let response = self.http.get(&addr).await?;
+        Ok(serde_json::from_str(&check(response)?)?)
     }
+}
+
+/// Turns an error response into an `Error` carrying the message given by WeatherAPI.
+///
+/// WeatherAPI reports most errors with its own codes rather than the HTTP status, e.g. an unknown
+/// location is a 400 with code 1006.
+fn check(response: HttpResponse) -> Result<String> {
+    let HttpResponse { status, body } = response;
+    if status.is_success() {
+        return Ok(body);
+    }
+    let Ok(ErrorResponse { error }) = serde_json::from_str(&body) else {
+        return Err(Error::from_status(NAME, status.as_u16(), body));
+    };
+    let message = error.message;
+    Err(match error.code {
+        1002 | 2006 | 2008 => Error::InvalidApiKey {
+            provider: NAME,
+            message,
+        },
+        1006 => Error::UnknownLocation {
+            provider: NAME,
+            message,
+        },
+        2007 => Error::QuotaExceeded {
+            provider: NAME,
+            message,
+        },
+        _ => Error::from_status(NAME, status.as_u16(), message),
+    })
+}
 
-    pub async fn current_weather_city(&self, city: &str) -> Result<Weather> {
-        let addr = self.format_addr(&format!("current.json?q={city}&aqi=no"));
-        let response = self.client.get(&addr).send().await?.error_for_status()?;
-        Ok(response.json().await?)
+/// Value of the `q` parameter accepted by all WeatherAPI endpoints.
+fn location_query(location: &location::Location) -> String {
+    match location {
+        location::Location::City(city) => city.clone(),
+        location::Location::Coordinates(coord) => format!("{},{}", coord.lat, coord.lon),
+        location::Location::PostalCode(code) => code.clone(),
+        location::Location::Id(id) => format!("id:{id}"),
     }
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorDetail {
+    pub code: i64,
+    pub message: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchPlace {
+    pub id: i64,
+    pub name: String,
+    pub region: String,
+    pub country: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Weather {
@@ -120,6 +231,14 @@ pub struct WeatherHistory {
     pub forecast: Forecast,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeatherForecast {
+    pub location: Location,
+    pub current: Current,
+    pub forecast: Forecast,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Forecast {
@@ -0,0 +1,246 @@
+use std::{process::Command, sync::OnceLock};
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::RngCore;
+
+use crate::{
+    config::{Config, Paths},
+    error::{Error, Result},
+    providers::ProviderUserInfo,
+};
+
+/// Environment variable giving the passphrase the API keys are encrypted with.
+pub const ENV_PASSPHRASE: &str = "WEATHER_PASSPHRASE";
+
+/// The prefix of encrypted API keys in the configuration files.
+const ENCRYPTED_PREFIX: &str = "enc:";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// The passphrase, asked for at most once per run.
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+/// Returns whether the configured API key is encrypted.
+pub fn is_encrypted(api_key: &str) -> bool {
+    api_key.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Encrypts an API key with a key derived from the passphrase, with a random salt and nonce.
+pub fn encrypt(api_key: &str, passphrase: &str) -> Result<String> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(&nonce, api_key.as_bytes())
+        .map_err(|_| unavailable("encryption failed"))?;
+
+    let mut data = salt.to_vec();
+    data.extend_from_slice(&nonce);
+    data.extend(ciphertext);
+    Ok(format!("{ENCRYPTED_PREFIX}{}", STANDARD.encode(data)))
+}
+
+/// Decrypts an API key encrypted by `encrypt`.
+///
+/// # Errors
+///
+/// Returns an error if the passphrase is wrong or the encrypted key is corrupt.
+pub fn decrypt(encrypted: &str, passphrase: &str) -> Result<String> {
+    let data = encrypted
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .and_then(|x| STANDARD.decode(x).ok())
+        .filter(|x| x.len() > SALT_LEN + NONCE_LEN)
+        .ok_or_else(|| unavailable("the encrypted key is corrupt"))?;
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| unavailable("wrong passphrase"))?;
+    String::from_utf8(plain).map_err(|_| unavailable("the encrypted key is corrupt"))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| unavailable(&e.to_string()))?;
+    Ok(key)
+}
+
+fn unavailable(message: &str) -> Error {
+    Error::ApiKeyUnavailable {
+        provider: "weather",
+        message: message.to_owned(),
+    }
+}
+
+/// Returns the passphrase from the environment variable, or asks for it on the terminal.
+///
+/// # Errors
+///
+/// Returns an error if the passphrase is not in the environment variable and there is no
+/// terminal to ask on.
+pub fn passphrase() -> Result<String> {
+    if let Some(passphrase) = PASSPHRASE.get() {
+        return Ok(passphrase.clone());
+    }
+    let passphrase = match std::env::var(ENV_PASSPHRASE) {
+        Ok(passphrase) => passphrase,
+        Err(_) => rpassword::prompt_password("Passphrase of the API keys: ").map_err(|e| {
+            unavailable(&format!(
+                "couldn't read the passphrase from the terminal: {e}"
+            ))
+        })?,
+    };
+    Ok(PASSPHRASE.get_or_init(|| passphrase).clone())
+}
+
+/// Returns a command running `command` with `sh -c`, or `cmd /C` on Windows.
+pub(crate) fn shell(command: &str) -> Command {
+    #[cfg(unix)]
+    let (shell, flag) = ("sh", "-c");
+    #[cfg(not(unix))]
+    let (shell, flag) = ("cmd", "/C");
+    let mut cmd = Command::new(shell);
+    cmd.args([flag, command]);
+    cmd
+}
+
+/// Runs the command configured with `api_key_cmd` with the shell, and returns the first line of
+/// its output.
+///
+/// # Errors
+///
+/// Returns an error if the command cannot be run, fails or prints nothing.
+pub fn run_api_key_cmd(provider: &'static str, command: &str) -> Result<String> {
+    let output = shell(command).output();
+    let error = |message: String| Error::ApiKeyUnavailable { provider, message };
+    let output = output.map_err(|e| error(format!("couldn't run `{command}`: {e}")))?;
+    if !output.status.success() {
+        return Err(error(format!("`{command}` failed with {}", output.status)));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(str::to_owned)
+        .ok_or_else(|| error(format!("`{command}` printed no key")))
+}
+
+/// Returns the API key to store in the written configuration: encrypted if the keys stored in it
+/// are encrypted, so that they are all encrypted with the same passphrase.
+///
+/// # Errors
+///
+/// Returns an error if the passphrase cannot be read, or doesn't decrypt the stored keys.
+pub fn protect_api_key(stored: &[ProviderUserInfo], api_key: String) -> Result<String> {
+    match stored.iter().find(|x| is_encrypted(x.api_key())) {
+        Some(info) => {
+            let passphrase = passphrase()?;
+            decrypt(info.api_key(), &passphrase)?;
+            encrypt(&api_key, &passphrase)
+        }
+        None => Ok(api_key),
+    }
+}
+
+/// Encrypts the API keys stored in the written configuration file, or in the selected profile in
+/// it, with a passphrase.
+///
+/// # Arguments
+///
+/// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn encrypt_api_keys(paths: &Paths) -> Result<()> {
+    let mut count = 0;
+    Config::update(paths, |config| {
+        let needs_encrypting =
+            |info: &ProviderUserInfo| !info.api_key().is_empty() && !is_encrypted(info.api_key());
+        if !config.providers.iter().any(needs_encrypting) {
+            return Ok(());
+        }
+        let passphrase = passphrase()?;
+        // all keys are encrypted with the same passphrase
+        if let Some(info) = config.providers.iter().find(|x| is_encrypted(x.api_key())) {
+            decrypt(info.api_key(), &passphrase)?;
+        }
+        for info in config.providers.iter_mut().filter(|x| needs_encrypting(x)) {
+            let api_key = encrypt(info.api_key(), &passphrase)?;
+            info.set_api_key(api_key);
+            count += 1;
+        }
+        Ok(())
+    })?;
+    println!("Encrypted {count} API keys.");
+    Ok(())
+}
+
+/// Decrypts the API keys stored in the written configuration file, or in the selected profile in
+/// it.
+///
+/// # Arguments
+///
+/// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn decrypt_api_keys(paths: &Paths) -> Result<()> {
+    let mut count = 0;
+    Config::update(paths, |config| {
+        for info in &mut config.providers {
+            if is_encrypted(info.api_key()) {
+                let api_key = decrypt(info.api_key(), &passphrase()?)?;
+                info.set_api_key(api_key);
+                count += 1;
+            }
+        }
+        Ok(())
+    })?;
+    println!("Decrypted {count} API keys.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt() {
+        let encrypted = encrypt("secret-key", "passphrase").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("secret-key"));
+        // salted, so the same key isn't recognisable
+        assert_ne!(encrypted, encrypt("secret-key", "passphrase").unwrap());
+
+        assert_eq!(decrypt(&encrypted, "passphrase").unwrap(), "secret-key");
+        assert!(matches!(
+            decrypt(&encrypted, "wrong"),
+            Err(Error::ApiKeyUnavailable { .. })
+        ));
+        assert!(decrypt("enc:garbage", "passphrase").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_api_key_cmd() {
+        assert_eq!(
+            run_api_key_cmd("openweather", "printf 'key\\nother'").unwrap(),
+            "key"
+        );
+        assert!(run_api_key_cmd("openweather", "exit 1").is_err());
+        assert!(run_api_key_cmd("openweather", "true").is_err());
+    }
+}
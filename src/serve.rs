@@ -0,0 +1,337 @@
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+
+use axum::{
+    extract::{Query, State},
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::{Duration, SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    cli::{parse_datetime, ProviderArgs},
+    config::{Config, Paths},
+    error::{Error, Result},
+    history::{fetch_history, Step},
+    location::Location,
+    output::{write_series, write_weather, OutputFormat},
+    providers::WeatherProvider,
+    units::{
+        DistanceUnit, PrecipitationUnit, SpeedUnit, TemperatureUnit, UnitSettings, UnitSystem,
+    },
+};
+
+/// The number of days forecast when none is asked for.
+const DEFAULT_FORECAST_DAYS: u32 = 3;
+
+/// The longest history range a request may ask for, as each day may take a request to the
+/// provider.
+const MAX_HISTORY_DAYS: i64 = 31;
+
+/// The configuration and the provider shared by all requests, so that they share the connections
+/// to the providers and the response cache.
+struct AppState {
+    config: Config,
+    provider: Box<dyn WeatherProvider>,
+}
+
+impl AppState {
+    /// Returns the location given in the query, which may be a saved place, or the default one.
+    fn location(&self, location: Option<&str>) -> Result<Location> {
+        let location = location.map(str::parse::<Location>).transpose()?;
+        self.config.location(location.as_ref())
+    }
+}
+
+#[derive(Deserialize)]
+struct CurrentQuery {
+    location: Option<String>,
+    /// A past time to get the weather of, "%Y-%m-%d %H:%M:%S" in UTC.
+    date: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    location: Option<String>,
+    from: String,
+    to: Option<String>,
+    #[serde(default)]
+    step: Step,
+}
+
+#[derive(Deserialize)]
+struct ForecastQuery {
+    location: Option<String>,
+    days: Option<u32>,
+}
+
+/// The units selected in the query, named like the command line options.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct UnitsQuery {
+    units: Option<UnitSystem>,
+    temperature_unit: Option<TemperatureUnit>,
+    wind_unit: Option<SpeedUnit>,
+    precipitation_unit: Option<PrecipitationUnit>,
+    visibility_unit: Option<DistanceUnit>,
+}
+
+impl From<UnitsQuery> for UnitSettings {
+    fn from(query: UnitsQuery) -> Self {
+        Self {
+            system: query.units,
+            temperature: query.temperature_unit,
+            wind_speed: query.wind_unit,
+            precipitation: query.precipitation_unit,
+            visibility: query.visibility_unit,
+        }
+    }
+}
+
+/// Serves the current weather, historical weather and forecasts of the configured provider as
+/// JSON over HTTP, until interrupted or terminated.
+///
+/// Requests are logged to stderr. On SIGTERM or Ctrl-C, the server stops accepting connections
+/// and exits once the requests in progress are answered.
+///
+/// # Arguments
+///
+/// * `bind` - The address to listen on.
+/// * `provider` - A `ProviderArgs` representing the provider selection made on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache and the usage counters.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if no provider is configured, or the address cannot be listened on.
+pub async fn serve(bind: SocketAddr, provider: &ProviderArgs, paths: &Paths) -> Result<()> {
+    let config = Config::load(paths)?;
+    let provider = config.build_provider(provider, paths)?;
+    let state = Arc::new(AppState { config, provider });
+    let app = Router::new()
+        .route("/current", get(current))
+        .route("/history", get(history))
+        .route("/forecast", get(forecast))
+        .layer(middleware::from_fn(log_request))
+        .with_state(state);
+
+    let server_error = |message: String| Error::Server {
+        addr: bind,
+        message,
+    };
+    let server = axum::Server::try_bind(&bind)
+        .map_err(|e| server_error(e.to_string()))?
+        .serve(app.into_make_service());
+    eprintln!("Listening on http://{}", server.local_addr());
+    server
+        .with_graceful_shutdown(stop_serving())
+        .await
+        .map_err(|e| server_error(e.to_string()))?;
+    eprintln!("Server stopped");
+    Ok(())
+}
+
+async fn current(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CurrentQuery>,
+    Query(units): Query<UnitsQuery>,
+) -> Response {
+    respond(
+        async {
+            let location = state.location(query.location.as_deref())?;
+            let weather = match query.date.as_deref().map(parse_datetime).transpose()? {
+                Some(date) => state.provider.get_history_weather(&location, date).await?,
+                None => state.provider.get_weather(&location).await?,
+            };
+            let mut body = Vec::new();
+            let units = state.config.units.merge(units.into()).resolve();
+            write_weather(OutputFormat::Json, &weather, &units, &mut body)?;
+            Ok(body)
+        }
+        .await,
+    )
+}
+
+async fn history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+    Query(units): Query<UnitsQuery>,
+) -> Response {
+    respond(
+        async {
+            let location = state.location(query.location.as_deref())?;
+            let from = parse_datetime(&query.from)?;
+            let to = query.to.as_deref().map(parse_datetime).transpose()?;
+            if to.unwrap_or_else(Utc::now) - from > Duration::days(MAX_HISTORY_DAYS) {
+                return Err(Error::InvalidDateRange(format!(
+                    "longer than {MAX_HISTORY_DAYS} days, the most a request may ask for"
+                )));
+            }
+            let series =
+                fetch_history(state.provider.as_ref(), &location, from, to, query.step).await?;
+            let mut body = Vec::new();
+            let units = state.config.units.merge(units.into()).resolve();
+            write_series(OutputFormat::Json, &series, &units, &mut body)?;
+            Ok(body)
+        }
+        .await,
+    )
+}
+
+async fn forecast(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ForecastQuery>,
+    Query(units): Query<UnitsQuery>,
+) -> Response {
+    respond(
+        async {
+            let location = state.location(query.location.as_deref())?;
+            let days = query.days.unwrap_or(DEFAULT_FORECAST_DAYS).clamp(1, 14);
+            let forecast = state.provider.get_forecast(&location, days).await?;
+            let mut body = Vec::new();
+            let units = state.config.units.merge(units.into()).resolve();
+            write_series(OutputFormat::Json, &forecast, &units, &mut body)?;
+            Ok(body)
+        }
+        .await,
+    )
+}
+
+/// Turns the JSON body, or the error, into a response.
+fn respond(result: Result<Vec<u8>>) -> Response {
+    match result {
+        Ok(body) => ([(CONTENT_TYPE, "application/json")], body).into_response(),
+        Err(e) => {
+            let body = json!({ "error": e.to_string(), "hint": e.hint() });
+            (status(&e), axum::Json(body)).into_response()
+        }
+    }
+}
+
+/// Returns the status of the response to a request failing with the error. Errors in the
+/// configuration of the server, rather than in the request, are server errors.
+fn status(error: &Error) -> StatusCode {
+    match error {
+        Error::InvalidLocation(_)
+        | Error::DateTimeParse(_)
+        | Error::InvalidTimezoneTime
+        | Error::InvalidDateRange(_)
+        | Error::NoLocation
+        | Error::InvalidPick { .. } => StatusCode::BAD_REQUEST,
+        Error::UnknownLocation { .. } | Error::WeatherNoHistory => StatusCode::NOT_FOUND,
+        Error::QuotaExceeded { .. } | Error::UsageLimitReached { .. } => {
+            StatusCode::TOO_MANY_REQUESTS
+        }
+        Error::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+        Error::InvalidApiKey { .. }
+        | Error::ApiKeyUnavailable { .. }
+        | Error::Request(_)
+        | Error::ProviderUnavailable { .. }
+        | Error::Provider { .. } => StatusCode::BAD_GATEWAY,
+        Error::MissingApiKey(_)
+        | Error::InvalidSetting { .. }
+        | Error::InvalidAlertRule { .. }
+        | Error::NotATerminal
+        | Error::ConfigNotFound(_)
+        | Error::ProviderNotConfigured
+        | Error::UnknownProfile(_)
+        | Error::CorruptConfig { .. }
+        | Error::File(_)
+        | Error::Serialization(_)
+        | Error::TomlSerialization(_)
+        | Error::NoConfigDirectory
+        | Error::Server { .. }
+        | Error::Notification { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Logs the time, method, path, status and duration of each request.
+async fn log_request<B>(request: Request<B>, next: Next<B>) -> Response {
+    let start = Instant::now();
+    let (method, uri) = (request.method().clone(), request.uri().clone());
+    let response = next.run(request).await;
+    eprintln!(
+        "{} {method} {uri} {} {} ms",
+        Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        response.status().as_u16(),
+        start.elapsed().as_millis()
+    );
+    response
+}
+
+/// Waits for Ctrl-C or, on unix, SIGTERM.
+pub(crate) async fn shutdown_signal() {
+    let ctrl_c = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = ctrl_c => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = ctrl_c.await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = ctrl_c.await;
+}
+
+/// Waits for `shutdown_signal`, telling that the server is stopping.
+pub(crate) async fn stop_serving() {
+    shutdown_signal().await;
+    eprintln!("Shutting down, answering the requests in progress");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        assert_eq!(
+            status(&Error::InvalidLocation("".into())),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(&Error::UnknownLocation {
+                provider: "openweather",
+                message: "city not found".into()
+            }),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(&Error::Timeout {
+                provider: "openweather",
+                timeout: std::time::Duration::from_secs(1)
+            }),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            status(&Error::QuotaExceeded {
+                provider: "openweather",
+                message: "too many requests".into()
+            }),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            status(&Error::ProviderNotConfigured),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            status(&Error::MissingApiKey("openweather")),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
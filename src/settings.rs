@@ -0,0 +1,400 @@
+use toml::{Table, Value};
+
+use crate::{
+    cli::Provider,
+    config::{Config, Paths},
+    error::{Error, Result},
+    location::Location,
+    providers::name,
+    secrets::{self, is_encrypted},
+    usage::mask,
+};
+
+/// Prints the configuration merged from all configuration files, with the API keys masked.
+///
+/// # Arguments
+///
+/// * `paths` - The `Paths` of the configuration files.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn show_config(paths: &Paths) -> Result<()> {
+    let mut value = Value::try_from(Config::load_or_default(paths)?)?;
+    mask_api_keys(&mut value);
+    print!("{}", toml::to_string(&value)?);
+    Ok(())
+}
+
+/// Prints a setting of the configuration merged from all configuration files, with the API keys
+/// masked.
+///
+/// # Arguments
+///
+/// * `key` - The dotted key of the setting, e.g. `units.system` or `providers.openweather.base_url`.
+/// * `paths` - The `Paths` of the configuration files.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the setting is not set.
+pub fn get_setting(key: &str, paths: &Paths) -> Result<()> {
+    let mut value = Value::try_from(Config::load_or_default(paths)?)?;
+    mask_api_keys(&mut value);
+    let segments: Vec<_> = key.split('.').collect();
+    let setting = get_path(&value, &segments).ok_or_else(|| invalid(key, "not set"))?;
+    match setting {
+        Value::String(s) => println!("{s}"),
+        Value::Table(table) => print!("{}", toml::to_string(table)?),
+        Value::Array(_) => {
+            let table =
+                Table::from_iter([(segments[segments.len() - 1].to_owned(), setting.clone())]);
+            print!("{}", toml::to_string(&table)?);
+        }
+        other => println!("{other}"),
+    }
+    Ok(())
+}
+
+/// Sets a setting in the configuration file written by the configure commands, after checking
+/// that the setting exists and the value is valid. API keys are encrypted if the keys stored in
+/// the file are.
+///
+/// # Arguments
+///
+/// * `key` - The dotted key of the setting, e.g. `units.system` or `providers.openweather.base_url`.
+/// * `value` - The value, parsed as a TOML value if possible, e.g. `60` or `true`, and as a string
+///   otherwise.
+/// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the setting doesn't exist or the value is invalid.
+pub fn set_setting(key: &str, value: &str, paths: &Paths) -> Result<()> {
+    let segments: Vec<_> = key.split('.').collect();
+    let value = parse_value(value);
+    Config::update(paths, |config| {
+        let mut value = value;
+        // keys are stored like `configure` stores them, encrypted if the stored keys are
+        if let (["providers", _, "api_key"], Value::String(api_key)) = (segments.as_slice(), &value)
+        {
+            if !is_encrypted(api_key) {
+                value = secrets::protect_api_key(&config.providers, api_key.clone())?.into();
+            }
+        }
+        let mut edited = Value::try_from(&*config)?;
+        set_path(&mut edited, &segments, value.clone()).map_err(|e| invalid(key, e))?;
+        let edited: Config = edited
+            .try_into()
+            .map_err(|e: toml::de::Error| invalid(key, e.message()))?;
+
+        // fields that don't exist are dropped by the round trip
+        let written = Value::try_from(&edited)?;
+        if get_path(&written, &segments) != Some(&value) {
+            return Err(invalid(key, "no such setting"));
+        }
+        for location in edited.location.iter().chain(edited.places.values()) {
+            location.parse::<Location>()?;
+        }
+        *config = edited;
+        Ok(())
+    })?;
+    println!("Setting saved successfully.");
+    Ok(())
+}
+
+/// Removes a setting from the configuration file written by the configure commands.
+///
+/// # Arguments
+///
+/// * `key` - The dotted key of the setting, e.g. `units.system` or `providers.openweather.base_url`.
+/// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the setting is not set in the file, or is required.
+pub fn unset_setting(key: &str, paths: &Paths) -> Result<()> {
+    let segments: Vec<_> = key.split('.').collect();
+    Config::update(paths, |config| {
+        let mut edited = Value::try_from(&*config)?;
+        if !remove_path(&mut edited, &segments) {
+            return Err(invalid(key, "not set in the written configuration file"));
+        }
+        *config = edited
+            .try_into()
+            .map_err(|e: toml::de::Error| invalid(key, e.message()))?;
+        Ok(())
+    })?;
+    println!("Setting removed successfully.");
+    Ok(())
+}
+
+/// Prints the configuration files read, in the order of precedence, and the one written.
+///
+/// # Arguments
+///
+/// * `paths` - The `Paths` of the configuration files.
+pub fn print_config_paths(paths: &Paths) {
+    println!("written: {}", paths.config_file.display());
+    println!("read:");
+    for layer in &paths.config_layers {
+        let found = if layer.is_file() { "" } else { " (not found)" };
+        println!("  {}{found}", layer.display());
+    }
+}
+
+/// Prints the configured providers in the order of preference, with their masked API keys, or
+/// whether they are encrypted or printed by a command, and base URLs.
+///
+/// # Arguments
+///
+/// * `paths` - The `Paths` of the configuration files.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn list_provider_settings(paths: &Paths) -> Result<()> {
+    let config = Config::load_or_default(paths)?;
+    for (i, info) in config.providers.iter().enumerate() {
+        let key = if is_encrypted(info.api_key()) {
+            "encrypted".to_owned()
+        } else if let (true, Some(command)) = (info.api_key().is_empty(), info.api_key_cmd()) {
+            format!("from `{command}`")
+        } else {
+            mask(info.api_key())
+        };
+        println!("{}. {} (key {key})", i + 1, info.name());
+        if let Value::Table(table) = Value::try_from(info)? {
+            for (key, value) in table.iter().filter(|(key, _)| key.ends_with("_url")) {
+                println!("   {key}: {}", value.as_str().unwrap_or_default());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes a provider from the configuration file written by the configure commands.
+///
+/// # Arguments
+///
+/// * `provider` - The `Provider` to be removed.
+/// * `paths` - The `Paths` of the configuration file to be written and the selected profile.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the provider is not configured in the written file.
+pub fn remove_provider(provider: Provider, paths: &Paths) -> Result<()> {
+    Config::update(paths, |config| {
+        let count = config.providers.len();
+        config.providers.retain(|x| x.kind() != provider);
+        if config.providers.len() == count {
+            return Err(invalid(
+                &format!("providers.{}", name(provider)),
+                "not set in the written configuration file",
+            ));
+        }
+        Ok(())
+    })?;
+    println!("Provider removed successfully.");
+    Ok(())
+}
+
+fn invalid(key: &str, message: impl Into<String>) -> Error {
+    Error::InvalidSetting {
+        key: key.to_owned(),
+        message: message.into(),
+    }
+}
+
+/// Parses a value given on the command line as a TOML value, or a string if it isn't one.
+fn parse_value(value: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_owned()))
+}
+
+/// Returns the index of the settings of the provider with the given name.
+fn find_provider(providers: &[Value], name: &str) -> Option<usize> {
+    providers
+        .iter()
+        .position(|x| x.get("provider").and_then(Value::as_str) == Some(name))
+}
+
+fn get_path<'a>(value: &'a Value, key: &[&str]) -> Option<&'a Value> {
+    let Some((first, rest)) = key.split_first() else {
+        return Some(value);
+    };
+    let next = match value {
+        Value::Table(table) => table.get(*first)?,
+        Value::Array(providers) => &providers[find_provider(providers, first)?],
+        _ => return None,
+    };
+    get_path(next, rest)
+}
+
+fn set_path(value: &mut Value, key: &[&str], new: Value) -> std::result::Result<(), String> {
+    let Some((first, rest)) = key.split_first() else {
+        return Err("no setting given".to_owned());
+    };
+    let next = match value {
+        Value::Table(table) if rest.is_empty() => {
+            table.insert((*first).to_owned(), new);
+            return Ok(());
+        }
+        Value::Table(table) => table.entry(*first).or_insert_with(|| {
+            // the providers are the only array of tables
+            if *first == "providers" {
+                Value::Array(Vec::new())
+            } else {
+                Value::Table(Table::new())
+            }
+        }),
+        Value::Array(_) if rest.is_empty() => {
+            return Err(
+                "set the settings of the provider, e.g. `providers.<PROVIDER>.api_key`".to_owned(),
+            )
+        }
+        Value::Array(providers) => match find_provider(providers, first) {
+            Some(i) => &mut providers[i],
+            None => {
+                let provider = Table::from_iter([("provider".to_owned(), Value::from(*first))]);
+                providers.push(Value::Table(provider));
+                providers.last_mut().expect("just pushed")
+            }
+        },
+        _ => return Err("no such setting".to_owned()),
+    };
+    set_path(next, rest, new)
+}
+
+fn remove_path(value: &mut Value, key: &[&str]) -> bool {
+    let Some((first, rest)) = key.split_first() else {
+        return false;
+    };
+    match value {
+        Value::Table(table) if rest.is_empty() => table.remove(*first).is_some(),
+        Value::Table(table) => table
+            .get_mut(*first)
+            .is_some_and(|next| remove_path(next, rest)),
+        Value::Array(providers) => match find_provider(providers, first) {
+            Some(i) if rest.is_empty() => {
+                providers.remove(i);
+                true
+            }
+            Some(i) => remove_path(&mut providers[i], rest),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Masks the values of all `api_key` settings.
+fn mask_api_keys(value: &mut Value) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                match value {
+                    Value::String(s) if key == "api_key" && !is_encrypted(s) => *s = mask(s),
+                    value => mask_api_keys(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(mask_api_keys),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn sample() -> Value {
+        toml::from_str(
+            r#"
+location = "London"
+
+[units]
+system = "si"
+
+[[providers]]
+provider = "openweather"
+api_key = "abcdef123456"
+"#,
+        )
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case("60", Value::Integer(60))]
+    #[case("true", Value::Boolean(true))]
+    #[case("\"60\"", Value::String("60".into()))]
+    #[case("New York", Value::String("New York".into()))]
+    fn test_parse_value(#[case] value: &str, #[case] expected: Value) {
+        assert_eq!(parse_value(value), expected);
+    }
+
+    #[test]
+    fn test_paths() {
+        let mut value = sample();
+        assert_eq!(
+            get_path(&value, &["providers", "openweather", "api_key"]),
+            Some(&Value::from("abcdef123456"))
+        );
+        assert_eq!(
+            get_path(&value, &["units", "system"]),
+            Some(&Value::from("si"))
+        );
+        assert_eq!(get_path(&value, &["providers", "weatherapi"]), None);
+
+        set_path(
+            &mut value,
+            &["providers", "weatherapi", "api_key"],
+            "key".into(),
+        )
+        .unwrap();
+        set_path(&mut value, &["network", "retries"], 2.into()).unwrap();
+        let config: Config = value.clone().try_into().unwrap();
+        assert_eq!(config.providers[1].kind(), Provider::WeatherApi);
+        assert_eq!(config.network.retries, Some(2));
+        assert!(set_path(&mut value, &["providers", "openweather"], "key".into()).is_err());
+
+        assert!(remove_path(&mut value, &["providers", "openweather"]));
+        assert!(remove_path(&mut value, &["units", "system"]));
+        assert!(!remove_path(&mut value, &["units", "system"]));
+        let config: Config = value.try_into().unwrap();
+        assert_eq!(config.providers.len(), 1);
+        assert!(config.units.is_empty());
+    }
+
+    #[test]
+    fn test_mask_api_keys() {
+        let mut value = sample();
+        mask_api_keys(&mut value);
+        assert_eq!(
+            get_path(&value, &["providers", "openweather", "api_key"]),
+            Some(&Value::from("********3456"))
+        );
+        assert_eq!(
+            get_path(&value, &["location"]),
+            Some(&Value::from("London"))
+        );
+    }
+}
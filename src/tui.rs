@@ -0,0 +1,756 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{IsTerminal, Stdout},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Sparkline},
+    Frame, Terminal,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    cli::{Provider, RequestArgs},
+    config::{Config, Paths},
+    error::{Error, Result},
+    get::wind_direction_symbol,
+    history::{fetch_history, Step},
+    location::Location,
+    network::NetworkSettings,
+    output::{write_weather, OutputFormat},
+    providers::{Weather, WeatherProvider},
+    units::{UnitSettings, UnitSystem, Units},
+};
+
+/// The furthest day in the past that can be paged to.
+const MAX_DAYS_BACK: i64 = 365;
+
+/// The unit systems cycled through, in order.
+const SYSTEMS: [UnitSystem; 3] = [UnitSystem::Metric, UnitSystem::Imperial, UnitSystem::Si];
+
+const COMPASS_POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+
+/// How long to wait for a key press before drawing the weather received meanwhile.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// The providers of the dashboard, shared with the tasks requesting the weather.
+type Providers = [(&'static str, Arc<dyn WeatherProvider>)];
+
+/// Shows a full screen dashboard of the weather of several locations, until quit.
+///
+/// The dashboard lists the current weather of the locations, and shows the conditions, wind and
+/// the hourly temperature and precipitation of the selected one, either for the next 24 hours or
+/// for a past day. The keys shown at the bottom switch the location, the day, the provider and
+/// the units.
+///
+/// # Arguments
+///
+/// * `places` - The saved places or locations to show, labelled as given. All saved places if
+///   empty, or the default location if there are none.
+/// * `provider` - The provider shown first, the preferred one if `None`.
+/// * `units` - A `UnitSettings` representing the units selected on the command line, overriding the configured ones.
+/// * `no_cache` - Whether to skip reading and writing the response cache.
+/// * `network` - The `NetworkSettings` selected on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache and the usage counters.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the output is not a terminal, no provider is configured, a location is
+/// invalid or there is no location to show.
+pub async fn show_dashboard(
+    places: &[String],
+    provider: Option<Provider>,
+    units: &UnitSettings,
+    no_cache: bool,
+    network: NetworkSettings,
+    paths: &Paths,
+) -> Result<()> {
+    if !std::io::stdout().is_terminal() {
+        return Err(Error::NotATerminal);
+    }
+    let config = Config::load(paths)?;
+    if let Some(kind) = provider {
+        config.provider(kind)?;
+    }
+    let places = config.named_locations(places)?;
+    // requests printing details would garble the screen
+    let request = RequestArgs {
+        no_cache,
+        verbose: false,
+        network,
+    };
+    let providers: Vec<_> = config
+        .build_all(&request, paths)?
+        .into_iter()
+        .map(|(name, provider)| (name, Arc::from(provider)))
+        .collect();
+    if providers.is_empty() {
+        return Err(Error::ProviderNotConfigured);
+    }
+    let settings = config.units.merge(*units);
+    let mut state = State::new(
+        places,
+        providers.iter().map(|(name, _)| *name).collect(),
+        settings,
+    );
+    if let Some(kind) = provider {
+        state.provider = config
+            .providers
+            .iter()
+            .position(|x| x.kind() == kind)
+            .unwrap_or(0);
+    }
+
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let mut terminal = TerminalGuard::enter()?;
+    loop {
+        while let Ok(loaded) = received.try_recv() {
+            state.receive(loaded);
+        }
+        if state.is_loading() {
+            state.request(&providers, &sender);
+        }
+        terminal.0.draw(|frame| state.render(frame))?;
+        // the weather is requested in the background, so that keys work while it loads
+        if !tokio::task::block_in_place(|| event::poll(POLL_INTERVAL))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !state.handle_key(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped, also on errors.
+struct TerminalGuard(Terminal<CrosstermBackend<Stdout>>);
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        if let Err(e) = execute!(stdout, EnterAlternateScreen) {
+            let _ = disable_raw_mode();
+            return Err(e.into());
+        }
+        let guard = Self(Terminal::new(CrosstermBackend::new(stdout))?);
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(self.0.backend_mut(), LeaveAlternateScreen);
+        let _ = self.0.show_cursor();
+    }
+}
+
+/// Weather received by a task requesting it, with the time the dashboard showed when it was
+/// requested.
+enum Loaded {
+    Current {
+        key: (usize, usize),
+        now: DateTime<Utc>,
+        result: std::result::Result<Box<Weather>, String>,
+    },
+    Series {
+        key: (usize, usize, i64),
+        now: DateTime<Utc>,
+        result: std::result::Result<Vec<Weather>, String>,
+    },
+}
+
+/// The selections made on the dashboard and the weather received, by provider and place index.
+struct State {
+    places: Vec<(String, Location)>,
+    providers: Vec<&'static str>,
+    /// The configured units, and those selected on the command line.
+    settings: UnitSettings,
+    units: Units,
+    system: UnitSystem,
+    provider: usize,
+    selected: usize,
+    /// The day shown, 0 for the next 24 hours, otherwise the number of days in the past.
+    days_back: i64,
+    /// The time the weather shown was requested at, reset on refresh.
+    now: DateTime<Utc>,
+    current: HashMap<(usize, usize), std::result::Result<Weather, String>>,
+    /// The hourly series of each day shown, the forecast for day 0.
+    series: HashMap<(usize, usize, i64), std::result::Result<Vec<Weather>, String>>,
+    /// The current weather and series requested but not received yet.
+    pending_current: HashSet<(usize, usize)>,
+    pending_series: HashSet<(usize, usize, i64)>,
+}
+
+impl State {
+    fn new(
+        places: Vec<(String, Location)>,
+        providers: Vec<&'static str>,
+        settings: UnitSettings,
+    ) -> Self {
+        Self {
+            places,
+            providers,
+            settings,
+            units: settings.resolve(),
+            system: settings.system.unwrap_or_default(),
+            provider: 0,
+            selected: 0,
+            days_back: 0,
+            now: Utc::now(),
+            current: HashMap::new(),
+            series: HashMap::new(),
+            pending_current: HashSet::new(),
+            pending_series: HashSet::new(),
+        }
+    }
+
+    /// Returns whether weather shown by the dashboard has not been received yet.
+    fn is_loading(&self) -> bool {
+        (0..self.places.len()).any(|place| !self.current.contains_key(&(self.provider, place)))
+            || !(self.series).contains_key(&(self.provider, self.selected, self.days_back))
+    }
+
+    /// Requests the weather shown by the dashboard that has been neither received nor requested
+    /// yet, each in a task sending what it receives to `sender`.
+    fn request(&mut self, providers: &Providers, sender: &UnboundedSender<Loaded>) {
+        let provider = &providers[self.provider].1;
+        let now = self.now;
+        for place in 0..self.places.len() {
+            let key = (self.provider, place);
+            if self.current.contains_key(&key) || !self.pending_current.insert(key) {
+                continue;
+            }
+            let (provider, sender) = (provider.clone(), sender.clone());
+            let location = self.places[place].1.clone();
+            tokio::spawn(async move {
+                let result = provider.get_weather(&location).await;
+                let result = result.map(Box::new).map_err(|e| e.to_string());
+                let _ = sender.send(Loaded::Current { key, now, result });
+            });
+        }
+
+        let key = (self.provider, self.selected, self.days_back);
+        if !self.series.contains_key(&key) && self.pending_series.insert(key) {
+            let (provider, sender) = (provider.clone(), sender.clone());
+            let location = self.places[self.selected].1.clone();
+            tokio::spawn(async move {
+                let result = fetch_series(provider.as_ref(), &location, key.2, now).await;
+                let result = result.map_err(|e| e.to_string());
+                let _ = sender.send(Loaded::Series { key, now, result });
+            });
+        }
+    }
+
+    /// Keeps the weather received, unless it was requested before a refresh.
+    fn receive(&mut self, loaded: Loaded) {
+        match loaded {
+            Loaded::Current { key, now, result } if now == self.now => {
+                self.pending_current.remove(&key);
+                self.current.insert(key, result.map(|weather| *weather));
+            }
+            Loaded::Series { key, now, result } if now == self.now => {
+                self.pending_series.remove(&key);
+                self.series.insert(key, result);
+            }
+            _ => {}
+        }
+    }
+
+    /// Applies a key press, returning `false` to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = (self.selected + self.places.len() - 1) % self.places.len();
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1) % self.places.len();
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                self.days_back = (self.days_back + 1).min(MAX_DAYS_BACK);
+            }
+            KeyCode::Right | KeyCode::Char('l') => self.days_back = (self.days_back - 1).max(0),
+            KeyCode::Char('t') => self.days_back = 0,
+            KeyCode::Char('p') => self.provider = (self.provider + 1) % self.providers.len(),
+            KeyCode::Char('u') => {
+                let index = SYSTEMS.iter().position(|x| *x == self.system).unwrap_or(0);
+                self.system = SYSTEMS[(index + 1) % SYSTEMS.len()];
+                let selected = UnitSettings {
+                    system: Some(self.system),
+                    ..UnitSettings::default()
+                };
+                self.units = self.settings.merge(selected).resolve();
+            }
+            KeyCode::Char('r') => {
+                self.current.clear();
+                self.series.clear();
+                self.pending_current.clear();
+                self.pending_series.clear();
+                self.now = Utc::now();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Returns the weather to show for the selected place: the current weather, or on a past
+    /// day the hour of the series closest to the current time of day.
+    fn weather(&self) -> Option<std::result::Result<&Weather, &str>> {
+        let key = (self.provider, self.selected);
+        if self.days_back == 0 {
+            return self.current.get(&key).map(as_ref);
+        }
+        let series = self.series.get(&(key.0, key.1, self.days_back))?;
+        let time = self.now - Duration::days(self.days_back);
+        Some(as_ref(series).and_then(|series| {
+            series
+                .iter()
+                .min_by_key(|x| (x.time - time).num_seconds().abs())
+                .ok_or("no weather history for this location and date")
+        }))
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [main, help] = split(
+            Direction::Vertical,
+            frame.size(),
+            [Constraint::Min(0), Constraint::Length(1)],
+        );
+        let [list, detail] = split(
+            Direction::Horizontal,
+            main,
+            [Constraint::Length(36), Constraint::Min(0)],
+        );
+        self.render_places(frame, list);
+        self.render_detail(frame, detail);
+        frame.render_widget(Paragraph::new(self.help()), help);
+    }
+
+    fn render_places(&self, frame: &mut Frame, area: Rect) {
+        let width = self
+            .places
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max();
+        let width = width.unwrap_or(0).min(12);
+        let items: Vec<ListItem> = self
+            .places
+            .iter()
+            .enumerate()
+            .map(|(place, (name, _))| {
+                let summary = match self.current.get(&(self.provider, place)) {
+                    None => "loading…".to_string(),
+                    Some(Err(_)) => "error".to_string(),
+                    Some(Ok(weather)) => {
+                        let weather = self.units.convert(weather);
+                        format!(
+                            "{:+5.1} {} {} {}",
+                            weather.temperature,
+                            self.units.temperature,
+                            wind_direction_symbol(weather.wind.deg),
+                            weather.description
+                        )
+                    }
+                };
+                ListItem::new(format!("{name:width$} {summary}"))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(" Places "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        let mut selection = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, area, &mut selection);
+    }
+
+    fn render_detail(&self, frame: &mut Frame, area: Rect) {
+        let [conditions, temperature, precipitation] = split(
+            Direction::Vertical,
+            area,
+            [
+                Constraint::Min(0),
+                Constraint::Length(4),
+                Constraint::Length(4),
+            ],
+        );
+        let [text, compass] = split(
+            Direction::Horizontal,
+            conditions,
+            [Constraint::Min(0), Constraint::Length(18)],
+        );
+
+        let (name, _) = &self.places[self.selected];
+        let day = match self.days_back {
+            0 => "now".to_string(),
+            days => (self.now - Duration::days(days))
+                .format("%a %Y-%m-%d UTC")
+                .to_string(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {name}, {day} "));
+        let weather = self.weather();
+        let lines = match weather {
+            None => vec![Line::from("Loading…")],
+            Some(Err(e)) => vec![Line::styled(
+                format!("Error: {e}"),
+                Style::default().fg(Color::Red),
+            )],
+            Some(Ok(weather)) => {
+                let mut buffer = Vec::new();
+                match write_weather(OutputFormat::Text, weather, &self.units, &mut buffer) {
+                    Ok(()) => String::from_utf8_lossy(&buffer)
+                        .lines()
+                        .map(|line| Line::from(line.to_string()))
+                        .collect(),
+                    Err(e) => vec![Line::from(format!("Error: {e}"))],
+                }
+            }
+        };
+        frame.render_widget(Paragraph::new(lines).block(block), text);
+
+        let lines = match weather {
+            Some(Ok(weather)) => {
+                let converted = self.units.convert(weather);
+                let mut lines: Vec<Line> = compass_rose(weather.wind.deg)
+                    .into_iter()
+                    .map(Line::from)
+                    .collect();
+                lines.push(Line::from(""));
+                lines.push(Line::from(format!(
+                    "from {} {}°",
+                    compass_point(weather.wind.deg),
+                    weather.wind.deg.rem_euclid(360)
+                )));
+                lines.push(Line::from(format!(
+                    "{:.1} {}",
+                    converted.wind.speed, self.units.wind_speed
+                )));
+                lines
+            }
+            _ => Vec::new(),
+        };
+        let block = Block::default().borders(Borders::ALL).title(" Wind ");
+        frame.render_widget(Paragraph::new(lines).block(block), compass);
+
+        let series = self
+            .series
+            .get(&(self.provider, self.selected, self.days_back));
+        let series: Vec<Weather> = match series {
+            Some(Ok(series)) => series.iter().map(|x| self.units.convert(x)).collect(),
+            _ => Vec::new(),
+        };
+        let hours = if self.days_back == 0 {
+            "next 24 h"
+        } else {
+            "hourly"
+        };
+
+        let values: Vec<f64> = series.iter().map(|x| x.temperature).collect();
+        let title = match (min(&values), max(&values)) {
+            (Some(low), Some(high)) => format!(
+                " Temperature, {hours}: {low:+.1} to {high:+.1} {} ",
+                self.units.temperature
+            ),
+            _ => format!(" Temperature, {hours} "),
+        };
+        let data = scale(&values, true);
+        let sparkline = Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .style(Style::default().fg(Color::Yellow))
+            .data(&data);
+        frame.render_widget(sparkline, temperature);
+
+        let values: Vec<f64> = series.iter().map(|x| x.rain_volume).collect();
+        let title = match max(&values) {
+            Some(high) => format!(
+                " Precipitation, {hours}: up to {high:.2} {} ",
+                self.units.precipitation
+            ),
+            None => format!(" Precipitation, {hours} "),
+        };
+        let data = scale(&values, false);
+        let sparkline = Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .style(Style::default().fg(Color::Cyan))
+            .data(&data);
+        frame.render_widget(sparkline, precipitation);
+    }
+
+    fn help(&self) -> Line<'static> {
+        let key =
+            |key: &'static str| Span::styled(key, Style::default().add_modifier(Modifier::BOLD));
+        let status = format!(
+            "{} · {}, {} · ",
+            self.providers[self.provider], self.units.temperature, self.units.wind_speed
+        );
+        Line::from(vec![
+            Span::raw(status),
+            key("↑↓"),
+            Span::raw(" place  "),
+            key("←→"),
+            Span::raw(" day  "),
+            key("t"),
+            Span::raw(" today  "),
+            key("p"),
+            Span::raw(" provider  "),
+            key("u"),
+            Span::raw(" units  "),
+            key("r"),
+            Span::raw(" refresh  "),
+            key("q"),
+            Span::raw(" quit"),
+        ])
+    }
+}
+
+/// Requests the hourly weather of a place shown on a day: the forecast of the next 24 hours for
+/// day 0, otherwise the history of the day in UTC.
+async fn fetch_series(
+    provider: &dyn WeatherProvider,
+    location: &Location,
+    days_back: i64,
+    now: DateTime<Utc>,
+) -> Result<Vec<Weather>> {
+    if days_back == 0 {
+        let forecast = provider.get_forecast(location, 2).await?;
+        let end = now + Duration::hours(24);
+        return Ok(forecast
+            .into_iter()
+            .filter(|x| x.time > now - Duration::hours(1) && x.time <= end)
+            .collect());
+    }
+    let day = (now - Duration::days(days_back)).date_naive();
+    let from = day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let to = from + Duration::hours(23);
+    fetch_history(provider, location, from, Some(to), Step::Hourly).await
+}
+
+fn as_ref<T>(result: &std::result::Result<T, String>) -> std::result::Result<&T, &str> {
+    result.as_ref().map_err(String::as_str)
+}
+
+fn split<const N: usize>(
+    direction: Direction,
+    area: Rect,
+    constraints: [Constraint; N],
+) -> [Rect; N] {
+    let areas = Layout::default()
+        .direction(direction)
+        .constraints(constraints)
+        .split(area);
+    std::array::from_fn(|i| areas[i])
+}
+
+/// Draws the arrow of `wind_direction_symbol` between the four cardinal points.
+fn compass_rose(degrees: i64) -> [String; 5] {
+    [
+        "       N".to_string(),
+        "       │".to_string(),
+        format!("W ────{}──── E", wind_direction_symbol(degrees)),
+        "       │".to_string(),
+        "       S".to_string(),
+    ]
+}
+
+/// Returns the point of the compass closest to a direction in degrees, e.g. "NE".
+fn compass_point(degrees: i64) -> &'static str {
+    let index = (degrees.rem_euclid(360) as f64 / 45.0).round() as usize % 8;
+    COMPASS_POINTS[index]
+}
+
+/// Scales values to the bars of a sparkline in tenths. If `relative`, the bars start at the
+/// lowest value, which still gets one bar, otherwise at 0.
+fn scale(values: &[f64], relative: bool) -> Vec<u64> {
+    let (floor, base) = match min(values) {
+        Some(low) if relative => (low, 1),
+        _ => (0.0, 0),
+    };
+    values
+        .iter()
+        .map(|x| ((x - floor) * 10.0).round().max(0.0) as u64 + base)
+        .collect()
+}
+
+fn min(values: &[f64]) -> Option<f64> {
+    values.iter().copied().reduce(f64::min)
+}
+
+fn max(values: &[f64]) -> Option<f64> {
+    values.iter().copied().reduce(f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use ratatui::backend::TestBackend;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::providers::sample_weather;
+
+    fn weather(hour: u32, temperature: f64, rain_volume: f64) -> Weather {
+        let mut weather = sample_weather();
+        weather.time = Utc.with_ymd_and_hms(2023, 3, 15, hour, 0, 0).unwrap();
+        weather.temperature = temperature;
+        weather.wind.speed = 5.0;
+        weather.rain_volume = rain_volume;
+        weather
+    }
+
+    fn state() -> State {
+        let places = vec![
+            ("hq".to_string(), "London".parse().unwrap()),
+            ("lab".to_string(), "Paris".parse().unwrap()),
+        ];
+        let mut state = State::new(
+            places,
+            vec!["openweather", "weatherapi"],
+            UnitSettings::default(),
+        );
+        state.now = Utc.with_ymd_and_hms(2023, 3, 15, 12, 0, 0).unwrap();
+        state
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    /// Renders the dashboard on a backend of the given size, and returns its text.
+    fn draw(state: &State, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        let frame = terminal.draw(|frame| state.render(frame)).unwrap();
+        let mut text = String::new();
+        for y in 0..height {
+            for x in 0..width {
+                text.push_str(&frame.buffer.get(x, y).symbol);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    #[rstest]
+    #[case(0, "N")]
+    #[case(44, "NE")]
+    #[case(270, "W")]
+    #[case(340, "N")]
+    #[case(-90, "W")]
+    fn test_compass_point(#[case] degrees: i64, #[case] expected: &str) {
+        assert_eq!(compass_point(degrees), expected);
+    }
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(scale(&[8.5, 7.0, 7.25], true), [16, 1, 4]);
+        assert_eq!(scale(&[0.0, 1.2, 0.3], false), [0, 12, 3]);
+        assert!(scale(&[], true).is_empty());
+    }
+
+    #[test]
+    fn test_handle_key() {
+        let mut state = state();
+        assert!(state.is_loading());
+        state.current.insert((0, 0), Ok(weather(12, 8.5, 0.0)));
+        state.current.insert((0, 1), Err("city not found".into()));
+        state.series.insert((0, 0, 0), Ok(vec![]));
+        assert!(!state.is_loading());
+
+        assert!(state.handle_key(key(KeyCode::Up)));
+        assert_eq!(state.selected, 1);
+        assert!(state.is_loading());
+        assert!(state.handle_key(key(KeyCode::Right)));
+        assert_eq!(state.days_back, 0);
+        state.handle_key(key(KeyCode::Left));
+        state.handle_key(key(KeyCode::Left));
+        assert_eq!(state.days_back, 2);
+        state.handle_key(key(KeyCode::Char('t')));
+        assert_eq!(state.days_back, 0);
+        state.handle_key(key(KeyCode::Char('p')));
+        state.handle_key(key(KeyCode::Char('p')));
+        assert_eq!(state.provider, 0);
+        state.handle_key(key(KeyCode::Char('u')));
+        assert_eq!(state.units.temperature.to_string(), "°F");
+        state.handle_key(key(KeyCode::Char('r')));
+        assert!(state.current.is_empty());
+
+        assert!(!state.handle_key(key(KeyCode::Char('q'))));
+        assert!(!state.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn test_receive() {
+        let mut state = state();
+        let requested = state.now;
+        state.pending_current.insert((0, 0));
+        state.receive(Loaded::Current {
+            key: (0, 0),
+            now: requested,
+            result: Ok(Box::new(weather(12, 8.5, 0.0))),
+        });
+        assert!(state.current.contains_key(&(0, 0)));
+        assert!(state.pending_current.is_empty());
+
+        // weather requested before a refresh is dropped
+        state.handle_key(key(KeyCode::Char('r')));
+        state.receive(Loaded::Series {
+            key: (0, 0, 0),
+            now: requested,
+            result: Ok(vec![]),
+        });
+        assert!(state.series.is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut state = state();
+        state.current.insert((0, 0), Ok(weather(12, 8.5, 0.0)));
+        state.current.insert((0, 1), Err("city not found".into()));
+        state.series.insert(
+            (0, 0, 0),
+            Ok(vec![weather(13, 8.5, 0.0), weather(14, 7.0, 1.2)]),
+        );
+        let text = draw(&state, 100, 24);
+        assert!(text.contains(" Places "));
+        assert!(text.contains("> hq   +8.5 °C → Light rain"));
+        assert!(text.contains("  lab error"));
+        assert!(text.contains(" hq, now "));
+        assert!(text.contains("+8.5 °C (feels like +11.0 °C)"));
+        assert!(text.contains("W ────→──── E"));
+        assert!(text.contains("from W 270°"));
+        assert!(text.contains("18.0 km/h"));
+        assert!(text.contains(" Temperature, next 24 h: +7.0 to +8.5 °C "));
+        assert!(text.contains(" Precipitation, next 24 h: up to 1.20 mm "));
+        assert!(text.contains("openweather · °C, km/h · ↑↓ place"));
+
+        // a past day shows the hour closest to the current time of day
+        state.days_back = 1;
+        state.series.insert(
+            (0, 0, 1),
+            Ok(vec![weather(11, 3.0, 0.0), weather(12, 4.0, 0.0)]),
+        );
+        let text = draw(&state, 100, 24);
+        assert!(text.contains(" hq, Tue 2023-03-14 UTC "));
+        assert!(text.contains("+4.0 °C"));
+        assert!(text.contains(" Temperature, hourly: +3.0 to +4.0 °C "));
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt::Display;
+
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::providers::Weather;
+
+/// Enum representing predefined systems of units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    /// °C, km/h, mm, m.
+    #[default]
+    Metric,
+    /// °F, mph, in, mi.
+    Imperial,
+    /// K, m/s, mm, m.
+    Si,
+}
+
+/// Enum representing units of temperature.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    /// Degrees Celsius.
+    Celsius,
+    /// Degrees Fahrenheit.
+    Fahrenheit,
+    /// Kelvin.
+    Kelvin,
+}
+
+/// Enum representing units of speed.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+pub enum SpeedUnit {
+    /// Meters per second.
+    #[value(name = "m/s")]
+    #[serde(rename = "m/s")]
+    MetersPerSecond,
+    /// Kilometers per hour.
+    #[value(name = "km/h")]
+    #[serde(rename = "km/h")]
+    KilometersPerHour,
+    /// Miles per hour.
+    #[value(name = "mph")]
+    #[serde(rename = "mph")]
+    MilesPerHour,
+    /// Knots.
+    #[value(name = "knots")]
+    #[serde(rename = "knots")]
+    Knots,
+}
+
+/// Enum representing units of precipitation.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+pub enum PrecipitationUnit {
+    /// Millimeters.
+    #[value(name = "mm")]
+    #[serde(rename = "mm")]
+    Millimeters,
+    /// Inches.
+    #[value(name = "in")]
+    #[serde(rename = "in")]
+    Inches,
+}
+
+/// Enum representing units of distance.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+pub enum DistanceUnit {
+    /// Meters.
+    #[value(name = "m")]
+    #[serde(rename = "m")]
+    Meters,
+    /// Kilometers.
+    #[value(name = "km")]
+    #[serde(rename = "km")]
+    Kilometers,
+    /// Miles.
+    #[value(name = "mi")]
+    #[serde(rename = "mi")]
+    Miles,
+}
+
+impl TemperatureUnit {
+    /// Converts a temperature in degrees Celsius to this unit.
+    pub fn from_celsius(self, celsius: f64) -> f64 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9. / 5. + 32.,
+            TemperatureUnit::Kelvin => celsius + 273.15,
+        }
+    }
+}
+
+impl SpeedUnit {
+    /// Converts a speed in meters per second to this unit.
+    pub fn from_mps(self, mps: f64) -> f64 {
+        match self {
+            SpeedUnit::MetersPerSecond => mps,
+            SpeedUnit::KilometersPerHour => mps * 3.6,
+            SpeedUnit::MilesPerHour => mps * 3600. / 1609.344,
+            SpeedUnit::Knots => mps * 3600. / 1852.,
+        }
+    }
+}
+
+impl PrecipitationUnit {
+    /// Converts a precipitation amount in millimeters to this unit.
+    pub fn from_mm(self, mm: f64) -> f64 {
+        match self {
+            PrecipitationUnit::Millimeters => mm,
+            PrecipitationUnit::Inches => mm / 25.4,
+        }
+    }
+}
+
+impl DistanceUnit {
+    /// Converts a distance in meters to this unit.
+    pub fn from_meters(self, meters: f64) -> f64 {
+        match self {
+            DistanceUnit::Meters => meters,
+            DistanceUnit::Kilometers => meters / 1000.,
+            DistanceUnit::Miles => meters / 1609.344,
+        }
+    }
+}
+
+impl Display for TemperatureUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+            TemperatureUnit::Kelvin => "K",
+        })
+    }
+}
+
+impl Display for SpeedUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SpeedUnit::MetersPerSecond => "m/s",
+            SpeedUnit::KilometersPerHour => "km/h",
+            SpeedUnit::MilesPerHour => "mph",
+            SpeedUnit::Knots => "kn",
+        })
+    }
+}
+
+impl Display for PrecipitationUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PrecipitationUnit::Millimeters => "mm",
+            PrecipitationUnit::Inches => "in",
+        })
+    }
+}
+
+impl Display for DistanceUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DistanceUnit::Meters => "m",
+            DistanceUnit::Kilometers => "km",
+            DistanceUnit::Miles => "mi",
+        })
+    }
+}
+
+/// A struct representing the units weather data is presented in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Units {
+    /// The unit of temperature.
+    pub temperature: TemperatureUnit,
+
+    /// The unit of wind speed.
+    pub wind_speed: SpeedUnit,
+
+    /// The unit of precipitation.
+    pub precipitation: PrecipitationUnit,
+
+    /// The unit of visibility.
+    pub visibility: DistanceUnit,
+}
+
+impl From<UnitSystem> for Units {
+    fn from(system: UnitSystem) -> Self {
+        match system {
+            UnitSystem::Metric => Units {
+                temperature: TemperatureUnit::Celsius,
+                wind_speed: SpeedUnit::KilometersPerHour,
+                precipitation: PrecipitationUnit::Millimeters,
+                visibility: DistanceUnit::Meters,
+            },
+            UnitSystem::Imperial => Units {
+                temperature: TemperatureUnit::Fahrenheit,
+                wind_speed: SpeedUnit::MilesPerHour,
+                precipitation: PrecipitationUnit::Inches,
+                visibility: DistanceUnit::Miles,
+            },
+            UnitSystem::Si => Units {
+                temperature: TemperatureUnit::Kelvin,
+                wind_speed: SpeedUnit::MetersPerSecond,
+                precipitation: PrecipitationUnit::Millimeters,
+                visibility: DistanceUnit::Meters,
+            },
+        }
+    }
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        UnitSystem::default().into()
+    }
+}
+
+impl Units {
+    /// Converts the normalized weather data to these units.
+    ///
+    /// The fields of the returned `Weather` are no longer in the units documented on `Weather`,
+    /// but in the units of `self`.
+    pub fn convert(&self, weather: &Weather) -> Weather {
+        let mut converted = weather.clone();
+        let temperature = |x| self.temperature.from_celsius(x);
+        converted.temperature = temperature(weather.temperature);
+        converted.feels_like = temperature(weather.feels_like);
+        converted.temperature_min = weather.temperature_min.map(temperature);
+        converted.temperature_max = weather.temperature_max.map(temperature);
+        converted.dew_point = weather.dew_point.map(temperature);
+        converted.wind.speed = self.wind_speed.from_mps(weather.wind.speed);
+        converted.wind.gust = weather.wind.gust.map(|x| self.wind_speed.from_mps(x));
+        converted.rain_volume = self.precipitation.from_mm(weather.rain_volume);
+        converted.visibility = weather.visibility.map(|x| self.visibility.from_meters(x));
+        converted
+    }
+}
+
+/// A struct representing the unit selection made by the user, either on the command line or
+/// in the configuration file.
+///
+/// Quantities that are not overridden take their unit from the unit system.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Args, Serialize, Deserialize)]
+pub struct UnitSettings {
+    /// Unit system
+    #[arg(long = "units", value_enum)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<UnitSystem>,
+
+    /// Temperature unit, overrides the unit system
+    #[arg(long = "temperature-unit", value_enum)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<TemperatureUnit>,
+
+    /// Wind speed unit, overrides the unit system
+    #[arg(long = "wind-unit", value_enum)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_speed: Option<SpeedUnit>,
+
+    /// Precipitation unit, overrides the unit system
+    #[arg(long = "precipitation-unit", value_enum)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precipitation: Option<PrecipitationUnit>,
+
+    /// Visibility unit, overrides the unit system
+    #[arg(long = "visibility-unit", value_enum)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<DistanceUnit>,
+}
+
+impl UnitSettings {
+    /// Returns whether no unit was selected.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merges `other` on top of these settings.
+    ///
+    /// If `other` selects a unit system, it replaces these settings entirely, otherwise only the
+    /// quantities selected by `other` are overridden.
+    pub fn merge(self, other: UnitSettings) -> UnitSettings {
+        if other.system.is_some() {
+            return other;
+        }
+        UnitSettings {
+            system: self.system,
+            temperature: other.temperature.or(self.temperature),
+            wind_speed: other.wind_speed.or(self.wind_speed),
+            precipitation: other.precipitation.or(self.precipitation),
+            visibility: other.visibility.or(self.visibility),
+        }
+    }
+
+    /// Resolves the settings into the units of each quantity.
+    pub fn resolve(&self) -> Units {
+        let base = Units::from(self.system.unwrap_or_default());
+        Units {
+            temperature: self.temperature.unwrap_or(base.temperature),
+            wind_speed: self.wind_speed.unwrap_or(base.wind_speed),
+            precipitation: self.precipitation.unwrap_or(base.precipitation),
+            visibility: self.visibility.unwrap_or(base.visibility),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(TemperatureUnit::Celsius, 20., 20.)]
+    #[case(TemperatureUnit::Fahrenheit, 100., 212.)]
+    #[case(TemperatureUnit::Fahrenheit, -40., -40.)]
+    #[case(TemperatureUnit::Kelvin, 0., 273.15)]
+    fn test_temperature(
+        #[case] unit: TemperatureUnit,
+        #[case] celsius: f64,
+        #[case] expected: f64,
+    ) {
+        assert!((unit.from_celsius(celsius) - expected).abs() < 1e-9);
+    }
+
+    #[rstest]
+    #[case(SpeedUnit::MetersPerSecond, 10., 10.)]
+    #[case(SpeedUnit::KilometersPerHour, 10., 36.)]
+    #[case(SpeedUnit::MilesPerHour, 10., 22.369)]
+    #[case(SpeedUnit::Knots, 10., 19.438)]
+    fn test_speed(#[case] unit: SpeedUnit, #[case] mps: f64, #[case] expected: f64) {
+        assert!((unit.from_mps(mps) - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_merge() {
+        let config = UnitSettings {
+            system: Some(UnitSystem::Imperial),
+            wind_speed: Some(SpeedUnit::Knots),
+            ..Default::default()
+        };
+        let cli = UnitSettings {
+            temperature: Some(TemperatureUnit::Kelvin),
+            ..Default::default()
+        };
+        let units = config.merge(cli).resolve();
+        assert_eq!(units.temperature, TemperatureUnit::Kelvin);
+        assert_eq!(units.wind_speed, SpeedUnit::Knots);
+        assert_eq!(units.precipitation, PrecipitationUnit::Inches);
+
+        let cli = UnitSettings {
+            system: Some(UnitSystem::Si),
+            ..Default::default()
+        };
+        assert_eq!(config.merge(cli).resolve(), Units::from(UnitSystem::Si));
+    }
+}
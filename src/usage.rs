@@ -0,0 +1,375 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Datelike, Duration, DurationRound, TimeZone, Utc};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::fnv1a,
+    config::{Config, Paths},
+    error::{Error, Result},
+    providers::ProviderUserInfo,
+};
+
+/// Serializes the reads and writes of the usage file within the process, e.g. by the providers
+/// asked concurrently by the aggregate provider. Other processes are kept out by `lock_file`.
+static USAGE_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A struct representing the most calls a key may make, per period. The periods are calendar
+/// periods in UTC, like the quotas of the providers. A limit of 0 is kept in the configuration
+/// files to override the limits of the files read before, and means no limit once loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Args, Serialize, Deserialize)]
+pub struct Limits {
+    /// Most calls per minute, further calls wait for the next minute
+    #[arg(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_minute: Option<u32>,
+
+    /// Most calls per day, further calls fail
+    #[arg(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_day: Option<u32>,
+
+    /// Most calls per month, further calls fail
+    #[arg(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_month: Option<u32>,
+}
+
+impl Limits {
+    /// Returns whether no limit was set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merges `other` on top of these limits, the limits set by `other`, including limits of 0,
+    /// are overridden.
+    pub fn merge(self, other: Limits) -> Limits {
+        Limits {
+            per_minute: other.per_minute.or(self.per_minute),
+            per_day: other.per_day.or(self.per_day),
+            per_month: other.per_month.or(self.per_month),
+        }
+    }
+
+    /// Returns the limits in effect, without the limits of 0.
+    pub fn effective(self) -> Limits {
+        let effective = |limit: Option<u32>| limit.filter(|x| *x > 0);
+        Limits {
+            per_minute: effective(self.per_minute),
+            per_day: effective(self.per_day),
+            per_month: effective(self.per_month),
+        }
+    }
+}
+
+/// The number of calls made in a period.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct Counter {
+    /// The start of the period.
+    start: DateTime<Utc>,
+    count: u32,
+}
+
+impl Counter {
+    fn count(&self, start: DateTime<Utc>) -> u32 {
+        if self.start == start {
+            self.count
+        } else {
+            0
+        }
+    }
+
+    fn increment(&mut self, start: DateTime<Utc>) {
+        *self = Counter {
+            start,
+            count: self.count(start) + 1,
+        };
+    }
+}
+
+/// The calls made with a key.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct KeyUsage {
+    provider: String,
+    minute: Counter,
+    day: Counter,
+    month: Counter,
+}
+
+/// The number of calls made with a key in the current minute, day and month.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub minute: u32,
+    pub day: u32,
+    pub month: u32,
+}
+
+impl KeyUsage {
+    fn usage(&self, now: DateTime<Utc>) -> Usage {
+        let (minute, day, month) = period_starts(now);
+        Usage {
+            minute: self.minute.count(minute),
+            day: self.day.count(day),
+            month: self.month.count(month),
+        }
+    }
+
+    fn record(&mut self, now: DateTime<Utc>) {
+        let (minute, day, month) = period_starts(now);
+        self.minute.increment(minute);
+        self.day.increment(day);
+        self.month.increment(month);
+    }
+}
+
+/// Returns the start of the minute, day and month of `now`.
+fn period_starts(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>, DateTime<Utc>) {
+    let minute = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+    let day = now.duration_trunc(Duration::days(1)).unwrap_or(now);
+    let month = Utc
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(day);
+    (minute, day, month)
+}
+
+/// Identifies a key in the usage file without storing the key itself.
+fn key_id(api_key: &str) -> String {
+    format!("{:016x}", fnv1a(api_key.as_bytes()))
+}
+
+/// Reads the counters of all keys. A corrupt file is reported and counted as empty, so that it
+/// doesn't fail every request.
+fn load(file: &Path) -> Result<BTreeMap<String, KeyUsage>> {
+    match std::fs::read(file) {
+        Ok(data) => Ok(serde_json::from_slice(&data).unwrap_or_else(|e| {
+            eprintln!(
+                "The usage counters in {} are corrupt, counting from zero: {e}",
+                file.display()
+            );
+            BTreeMap::new()
+        })),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Locks the usage file against other processes, e.g. `alerts check` run by cron while the
+/// exporter is running, until the returned file is dropped.
+fn lock_file(file: &Path) -> Result<File> {
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(file.with_extension("lock"))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+fn save(file: &Path, usage: &BTreeMap<String, KeyUsage>) -> Result<()> {
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = file.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&tmp, serde_json::to_vec_pretty(usage)?)?;
+    std::fs::rename(tmp, file)?;
+    Ok(())
+}
+
+/// Counts the calls made with a key in a file shared by all runs, and enforces its limits.
+pub struct UsageTracker {
+    file: PathBuf,
+    provider: &'static str,
+    key: String,
+    limits: Limits,
+}
+
+impl UsageTracker {
+    /// Creates a tracker for the key of the provider user info, counting in `file`.
+    pub fn new(file: &Path, info: &ProviderUserInfo) -> Self {
+        Self {
+            file: file.to_owned(),
+            provider: info.name(),
+            key: key_id(info.api_key()),
+            limits: info.limits(),
+        }
+    }
+
+    /// Records a call, waiting for the next minute first if the per minute limit is reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `verbose` - Whether to print when the call has to wait.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daily or monthly limit is reached, or the usage file cannot be
+    /// read or written.
+    pub async fn acquire(&self, verbose: bool) -> Result<()> {
+        loop {
+            let now = Utc::now();
+            let wait = {
+                let _lock = USAGE_FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+                let _file_lock = lock_file(&self.file)?;
+                let mut all = load(&self.file)?;
+                let entry = all.entry(self.key.clone()).or_default();
+                let usage = entry.usage(now);
+                for (period, count, limit) in [
+                    ("day", usage.day, self.limits.per_day),
+                    ("month", usage.month, self.limits.per_month),
+                ] {
+                    if let Some(limit) = limit.filter(|x| count >= *x) {
+                        return Err(Error::UsageLimitReached {
+                            provider: self.provider,
+                            period,
+                            limit,
+                        });
+                    }
+                }
+                if self.limits.per_minute.is_some_and(|x| usage.minute >= x) {
+                    let (minute, _, _) = period_starts(now);
+                    minute + Duration::minutes(1) - now
+                } else {
+                    entry.provider = self.provider.to_owned();
+                    entry.record(now);
+                    save(&self.file, &all)?;
+                    return Ok(());
+                }
+            };
+            if verbose {
+                eprintln!(
+                    "{}: per minute limit reached, waiting {:.1} s",
+                    self.provider,
+                    wait.num_milliseconds() as f64 / 1000.
+                );
+            }
+            tokio::time::sleep(wait.to_std().unwrap_or_default()).await;
+        }
+    }
+}
+
+/// Prints the calls made with the key of each configured provider this minute, day and month.
+///
+/// # Arguments
+///
+/// * `paths` - The `Paths` of the configuration files and the usage counters.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub fn print_usage(paths: &Paths) -> Result<()> {
+    let config = Config::load_or_default(paths)?;
+    let all = load(&paths.usage_file())?;
+    let now = Utc::now();
+    for info in &config.providers {
+        // the usage is recorded under the key actually sent
+        let info = info.clone().resolve_api_key()?;
+        let usage = all
+            .get(&key_id(info.api_key()))
+            .map(|x| x.usage(now))
+            .unwrap_or_default();
+        let limits = info.limits();
+        let format = |count: u32, limit: Option<u32>| match limit {
+            Some(limit) => format!("{count} of {limit}"),
+            None => count.to_string(),
+        };
+        println!("{} (key {})", info.name(), mask(info.api_key()));
+        println!("  this minute: {}", format(usage.minute, limits.per_minute));
+        println!("  today: {}", format(usage.day, limits.per_day));
+        println!("  this month: {}", format(usage.month, limits.per_month));
+    }
+    Ok(())
+}
+
+/// Masks all but the last 4 characters of an API key.
+pub fn mask(api_key: &str) -> String {
+    let visible = api_key.len().saturating_sub(4);
+    match api_key.get(visible..) {
+        Some(end) if visible > 0 => format!("{}{end}", "*".repeat(visible)),
+        _ => "*".repeat(api_key.chars().count()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(h: u32, m: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 3, 20, h, m, s).unwrap()
+    }
+
+    #[test]
+    fn test_record() {
+        let mut usage = KeyUsage::default();
+        usage.record(at(12, 0, 10));
+        usage.record(at(12, 0, 50));
+        assert_eq!(
+            usage.usage(at(12, 0, 59)),
+            Usage {
+                minute: 2,
+                day: 2,
+                month: 2
+            }
+        );
+
+        usage.record(at(12, 1, 0));
+        assert_eq!(usage.usage(at(12, 1, 0)).minute, 1);
+        assert_eq!(usage.usage(at(12, 1, 0)).day, 3);
+        let next_day = Utc.with_ymd_and_hms(2023, 3, 21, 0, 0, 0).unwrap();
+        assert_eq!(usage.usage(next_day).day, 0);
+        assert_eq!(usage.usage(next_day).month, 3);
+    }
+
+    #[test]
+    fn test_merge_limits() {
+        let saved = Limits {
+            per_minute: Some(60),
+            per_month: Some(1000),
+            ..Default::default()
+        };
+        let merged = saved.merge(Limits {
+            per_day: Some(100),
+            per_month: Some(0),
+            ..Default::default()
+        });
+        assert_eq!(
+            merged,
+            Limits {
+                per_minute: Some(60),
+                per_day: Some(100),
+                per_month: Some(0),
+            }
+        );
+        assert_eq!(
+            merged.effective(),
+            Limits {
+                per_month: None,
+                ..merged
+            }
+        );
+    }
+
+    #[test]
+    fn test_load_corrupt() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("usage.json");
+        assert!(load(&file).unwrap().is_empty());
+        std::fs::write(&file, "{\"abc\": ").unwrap();
+        assert!(load(&file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_mask() {
+        assert_eq!(mask("abcdef123456"), "********3456");
+        assert_eq!(mask("abc"), "***");
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    io::{IsTerminal, Write},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    cli::ProviderArgs,
+    config::{Config, Paths},
+    error::{Error, Result},
+    geocoding::{resolve, resolve_unknown},
+    get::wind_direction_symbol,
+    location::Location,
+    output::{write_weather, OutputFormat},
+    providers::Weather,
+    serve::shutdown_signal,
+    units::{UnitSettings, Units},
+};
+
+/// Clears the terminal and moves the cursor to the top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Retrieves the current weather of a location on an interval until interrupted, and prints it
+/// every time.
+///
+/// As text, the weather is redrawn in place on a terminal, followed by the changes since the
+/// previous poll. Other formats print one record per poll. Failed polls after the first are
+/// reported and retried at the next interval.
+///
+/// # Arguments
+///
+/// * `address` - A `Location` for which to retrieve weather data, the configured default
+///   location if `None`.
+/// * `interval` - The time between two polls.
+/// * `output` - The `OutputFormat` to print the weather data in, the configured one if `None`.
+/// * `pick` - Whether to pick among the places matching a city name: the number of the place, or
+///   `None` to ask. Without it, the places are only looked up if the provider reports the city
+///   unknown and the input is a terminal.
+/// * `units` - A `UnitSettings` representing the units selected on the command line, overriding the configured ones.
+/// * `provider` - A `ProviderArgs` representing the provider selection made on the command line.
+/// * `paths` - The `Paths` of the configuration file, the response cache and the usage counters.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Errors
+///
+/// Returns an error if the first poll fails.
+#[allow(clippy::too_many_arguments)]
+pub async fn watch_weather(
+    address: Option<&Location>,
+    interval: Duration,
+    output: Option<OutputFormat>,
+    pick: Option<Option<usize>>,
+    units: &UnitSettings,
+    provider: &ProviderArgs,
+    paths: &Paths,
+) -> Result<()> {
+    let config = Config::load(paths)?;
+    let address = config.location(address)?;
+    let output = config.output(output);
+    let units = config.units.merge(*units).resolve();
+    let weather_api = config.build_provider(provider, paths)?;
+    let mut address = resolve(weather_api.as_ref(), &address, pick).await?;
+    let first = match weather_api.get_weather(&address).await {
+        Err(e @ Error::UnknownLocation { .. }) if pick.is_none() => {
+            address = resolve_unknown(weather_api.as_ref(), &address, e).await?;
+            weather_api.get_weather(&address).await?
+        }
+        weather => weather?,
+    };
+
+    let terminal = std::io::stdout().is_terminal();
+    let mut screen = Screen {
+        output,
+        units,
+        interval,
+        terminal,
+        previous: None,
+        polls: 0,
+    };
+    let mut result = Ok(first);
+    let stop = shutdown_signal();
+    tokio::pin!(stop);
+    loop {
+        let mut frame = Vec::new();
+        screen.draw(result, Local::now(), &mut frame)?;
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&frame)?;
+        stdout.flush()?;
+        drop(stdout);
+
+        let next = async {
+            tokio::time::sleep(interval).await;
+            weather_api.get_weather(&address).await
+        };
+        result = tokio::select! {
+            result = next => result,
+            _ = &mut stop => return Ok(()),
+        };
+    }
+}
+
+/// The state of the watch output between two polls.
+struct Screen {
+    output: OutputFormat,
+    units: Units,
+    interval: Duration,
+    /// Whether the output is redrawn in place and highlighted.
+    terminal: bool,
+    /// The weather of the last successful poll and the time it was received.
+    previous: Option<(Weather, DateTime<Local>)>,
+    polls: usize,
+}
+
+impl Screen {
+    /// Writes the output of a poll received at `now`.
+    fn draw(
+        &mut self,
+        result: Result<Weather>,
+        now: DateTime<Local>,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        self.polls += 1;
+        if self.output != OutputFormat::Text {
+            return self.append(result, out);
+        }
+        if self.terminal {
+            write!(out, "{CLEAR_SCREEN}")?;
+        } else if self.polls > 1 {
+            writeln!(out)?;
+        }
+        writeln!(
+            out,
+            "Every {} s, updated at {}. Press Ctrl-C to stop.",
+            self.interval.as_secs(),
+            now.format("%H:%M:%S")
+        )?;
+        match result {
+            Ok(weather) => {
+                writeln!(out)?;
+                write_weather(self.output, &weather, &self.units, out)?;
+                if let Some((previous, at)) = &self.previous {
+                    writeln!(out)?;
+                    let changes = changes(previous, &weather, &self.units);
+                    if changes.is_empty() {
+                        writeln!(out, "No changes since {}.", at.format("%H:%M:%S"))?;
+                    } else {
+                        writeln!(out, "Changes since {}:", at.format("%H:%M:%S"))?;
+                        for change in changes {
+                            self.highlight(out, YELLOW, &format!("  {change}"))?;
+                        }
+                    }
+                }
+                self.previous = Some((weather, now));
+            }
+            Err(e) => {
+                self.highlight(out, RED, &format!("Error: {e}"))?;
+                // keep showing the last weather received
+                if let Some((previous, at)) = &self.previous {
+                    writeln!(out)?;
+                    writeln!(out, "As of {}:", at.format("%H:%M:%S"))?;
+                    write_weather(self.output, previous, &self.units, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes one record per poll for formats other than text, reporting failed polls on stderr.
+    fn append(&mut self, result: Result<Weather>, out: &mut impl Write) -> Result<()> {
+        let weather = match result {
+            Ok(weather) => weather,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return Ok(());
+            }
+        };
+        let mut record = Vec::new();
+        write_weather(self.output, &weather, &self.units, &mut record)?;
+        // the header row is only written with the first record
+        let skip = match (self.output, &self.previous) {
+            (OutputFormat::Csv, Some(_)) => record.iter().position(|&x| x == b'\n').map(|x| x + 1),
+            _ => None,
+        };
+        out.write_all(&record[skip.unwrap_or(0)..])?;
+        self.previous = Some((weather, Local::now()));
+        Ok(())
+    }
+
+    fn highlight(&self, out: &mut impl Write, color: &str, line: &str) -> Result<()> {
+        if self.terminal {
+            writeln!(out, "{color}{line}{RESET}")?;
+        } else {
+            writeln!(out, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Describes the changes between two reports of the weather, in the given units.
+fn changes(previous: &Weather, current: &Weather, units: &Units) -> Vec<String> {
+    let (before, after) = (units.convert(previous), units.convert(current));
+    let mut changes = Vec::new();
+    if before.description != after.description {
+        changes.push(format!("{} → {}", before.description, after.description));
+    }
+    let delta = after.temperature - before.temperature;
+    if delta.abs() >= 0.05 {
+        changes.push(format!("temperature {delta:+.1} {}", units.temperature));
+    }
+    let delta = after.wind.speed - before.wind.speed;
+    if delta.abs() >= 0.05 {
+        changes.push(format!("wind {delta:+.1} {}", units.wind_speed));
+    }
+    // the shortest turn, positive clockwise
+    let shift = (after.wind.deg - before.wind.deg + 180).rem_euclid(360) - 180;
+    if shift != 0 {
+        changes.push(format!(
+            "wind shifted {shift:+}° ({} to {})",
+            wind_direction_symbol(before.wind.deg),
+            wind_direction_symbol(after.wind.deg)
+        ));
+    }
+    let delta = after.rain_volume - before.rain_volume;
+    if delta.abs() >= 0.005 {
+        changes.push(format!("rain {delta:+.2} {}", units.precipitation));
+    }
+    let delta = after.humidity - before.humidity;
+    if delta != 0 {
+        changes.push(format!("humidity {delta:+} %"));
+    }
+    let delta = after.pressure - before.pressure;
+    if delta.abs() >= 0.5 {
+        changes.push(format!("pressure {delta:+.0} hPa"));
+    }
+    let delta = after.cloudiness - before.cloudiness;
+    if delta != 0 {
+        changes.push(format!("cloudiness {delta:+} %"));
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{providers::sample_weather, units::UnitSystem};
+
+    /// Returns the sample weather with a northerly wind.
+    fn weather() -> Weather {
+        let mut weather = sample_weather();
+        weather.wind.deg = 350;
+        weather
+    }
+
+    #[test]
+    fn test_changes() {
+        let units = UnitSystem::Si.into();
+        assert!(changes(&weather(), &weather(), &units).is_empty());
+
+        let mut current = weather();
+        current.description = "Overcast clouds".into();
+        current.temperature = 13.7;
+        current.wind.speed = 6.0;
+        current.wind.deg = 35;
+        current.rain_volume = 0.0;
+        assert_eq!(
+            changes(&weather(), &current, &units),
+            [
+                "Light rain → Overcast clouds",
+                "temperature +1.2 K",
+                "wind +1.5 m/s",
+                "wind shifted +45° (↑ to ↖)",
+                "rain -0.30 mm",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut screen = Screen {
+            output: OutputFormat::Text,
+            units: UnitSystem::Metric.into(),
+            interval: Duration::from_secs(60),
+            terminal: false,
+            previous: None,
+            polls: 0,
+        };
+        let now = Local.with_ymd_and_hms(2023, 3, 15, 12, 0, 0).unwrap();
+        let mut out = Vec::new();
+        screen.draw(Ok(weather()), now, &mut out).unwrap();
+        let mut current = weather();
+        current.temperature = 11.5;
+        screen
+            .draw(Ok(current), now + chrono::Duration::minutes(1), &mut out)
+            .unwrap();
+        screen
+            .draw(
+                Err(crate::error::Error::ProviderNotConfigured),
+                now + chrono::Duration::minutes(2),
+                &mut out,
+            )
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("Every 60 s, updated at 12:00:00. Press Ctrl-C to stop.\n"));
+        assert!(out.contains("Changes since 12:00:00:\n  temperature -1.0 °C\n"));
+        assert!(out.contains("\nEvery 60 s, updated at 12:02:00. Press Ctrl-C to stop.\nError: "));
+        assert!(out.ends_with("As of 12:01:00:\nLondon\n2023-03-15 12:00 +00:00\nLight rain\n+11.5 °C (feels like +11.0 °C)\n↑ 16.2 km/h\n0.3 mm\nhumidity 80 %\npressure 1012 hPa\nprovided by openweather\n"));
+    }
+}
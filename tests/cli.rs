@@ -0,0 +1,1456 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read};
+
+use assert_cmd::Command;
+use predicates::{prelude::*, str::contains};
+use tempfile::TempDir;
+use wiremock::MockServer;
+
+/// Runs the `weather` binary with its configuration, cache and usage counters isolated in a temporary directory
+/// and all providers pointed at the mock server.
+struct Weather {
+    home: TempDir,
+    server: MockServer,
+}
+
+/// A command running in the background, killed if the test fails before stopping it.
+struct Background {
+    child: std::process::Child,
+    stderr: BufReader<std::process::ChildStderr>,
+    /// The URL printed by the command.
+    url: String,
+}
+
+impl Background {
+    /// Stops the command with SIGTERM, checks that it exits successfully and returns the rest of
+    /// its stderr.
+    #[cfg(unix)]
+    fn stop(&mut self) -> String {
+        let status = std::process::Command::new("kill")
+            .args(["-TERM", &self.child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert!(self.child.wait().unwrap().success());
+        let mut log = String::new();
+        self.stderr.read_to_string(&mut log).unwrap();
+        log
+    }
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Weather {
+    async fn start() -> Self {
+        Self {
+            home: TempDir::new().unwrap(),
+            server: common::start().await,
+        }
+    }
+
+    fn cmd(&self) -> Command {
+        Command::from_std(self.std_cmd())
+    }
+
+    /// The command as a `std::process::Command`, for running the binary in the background.
+    fn std_cmd(&self) -> std::process::Command {
+        let mut cmd =
+            std::process::Command::new(assert_cmd::cargo::cargo_bin(assert_cmd::crate_name!()));
+        cmd.env("HOME", self.home.path())
+            .env("XDG_CONFIG_HOME", self.home.path().join("config"))
+            .env("XDG_CACHE_HOME", self.home.path().join("cache"))
+            .env("XDG_DATA_HOME", self.home.path().join("data"))
+            .env("WEATHER_OPENWEATHER_URL", self.server.uri())
+            .env("WEATHER_OPENWEATHER_HISTORY_URL", self.server.uri())
+            .env("WEATHER_WEATHERAPI_URL", self.server.uri())
+            .env_remove("WEATHER_OPENWEATHER_API_KEY")
+            .env_remove("WEATHER_WEATHERAPI_API_KEY")
+            .env_remove("WEATHER_PASSPHRASE");
+        cmd
+    }
+
+    fn configure(&self, provider: &str, api_key: &str) {
+        self.cmd()
+            .args(["configure", provider])
+            .write_stdin(api_key)
+            .assert()
+            .success();
+    }
+
+    /// Runs a server command in the background, returning once it prints the URL it listens on.
+    fn spawn(&self, args: &[&str]) -> Background {
+        let mut child = self
+            .std_cmd()
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stderr = BufReader::new(child.stderr.take().unwrap());
+        let mut line = String::new();
+        stderr.read_line(&mut line).unwrap();
+        let url = match line.split_whitespace().last() {
+            Some(url) if url.starts_with("http://") => url.to_owned(),
+            _ => panic!("unexpected output: {line}"),
+        };
+        Background { child, stderr, url }
+    }
+
+    /// Saves the key without the test request, for keys that would fail it.
+    fn configure_unchecked(&self, provider: &str, api_key: &str) {
+        self.cmd()
+            .args(["configure", provider, "--api-key", api_key, "--no-validate"])
+            .assert()
+            .success();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn get_text() {
+    let weather = Weather::start().await;
+    weather.configure("open-weather", common::API_KEY);
+
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache"])
+        .assert()
+        .success()
+        .stdout(contains("London, GB (51.5085,-0.1257)\n"))
+        .stdout(contains("light rain\n"))
+        .stdout(contains("+8.5 °C"))
+        .stdout(contains("→ 18.0 km/h (gusts 33.5 km/h)\n"))
+        .stdout(contains("10000 m\n"))
+        .stdout(contains("0.4 mm\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn get_units() {
+    let weather = Weather::start().await;
+    weather.configure("weather-api", common::API_KEY);
+
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache", "--units", "imperial"])
+        .assert()
+        .success()
+        .stdout(contains("+48.2 °F"))
+        .stdout(contains("↘ 11.9 mph"))
+        .stdout(contains("6.2 mi\n"));
+
+    // saved units apply unless overridden on the command line
+    weather
+        .cmd()
+        .args(["units", "--units", "si", "--wind-unit", "knots"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args([
+            "get",
+            "London",
+            "--no-cache",
+            "--temperature-unit",
+            "celsius",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("+9.0 °C"))
+        .stdout(contains("↘ 10.3 kn"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn get_history_json() {
+    let weather = Weather::start().await;
+    weather.configure("weather-api", common::API_KEY);
+
+    let output = weather
+        .cmd()
+        .args([
+            "get",
+            "London",
+            "2023-03-15 14:40:00",
+            "--no-cache",
+            "-o",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["time"], "2023-03-15T15:00:00Z");
+    assert_eq!(json["temperature"], 15.0);
+    assert_eq!(json["units"]["temperature"], "celsius");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn history_range() {
+    let weather = Weather::start().await;
+    weather.configure("weather-api", common::API_KEY);
+
+    let output = weather
+        .cmd()
+        .args([
+            "history",
+            "London",
+            "--from",
+            "2023-03-15 06:00:00",
+            "--to",
+            "2023-03-15 08:00:00",
+            "-o",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let temperatures = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["temperature"].as_f64().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(temperatures, [6., 7., 8.]);
+
+    let output = weather
+        .cmd()
+        .args([
+            "history",
+            "London",
+            "--from",
+            "2023-03-15 12:00:00",
+            "--to",
+            "2023-03-17 12:00:00",
+            "--step",
+            "daily",
+            "-o",
+            "csv",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // the fixture holds a single day, so only its noon is in the series
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 2);
+
+    weather
+        .cmd()
+        .args([
+            "history",
+            "London",
+            "--from",
+            "2023-03-15 12:00:00",
+            "--to",
+            "2023-03-14 12:00:00",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("invalid date range"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn forecast_csv() {
+    let weather = Weather::start().await;
+    weather.configure("open-weather", common::API_KEY);
+
+    let output = weather
+        .cmd()
+        .args([
+            "forecast",
+            "London",
+            "--days",
+            "1",
+            "--no-cache",
+            "-o",
+            "csv",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // header and 8 steps of 3 hours
+    assert_eq!(stdout.lines().count(), 9);
+    assert!(stdout.starts_with("time,"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cached_response() {
+    let weather = Weather::start().await;
+    weather.configure_unchecked("open-weather", common::API_KEY);
+
+    for _ in 0..2 {
+        weather.cmd().args(["get", "London"]).assert().success();
+    }
+    let requests = weather.server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+
+    weather.cmd().args(["cache", "clear"]).assert().success();
+    weather.cmd().args(["get", "London"]).assert().success();
+    let requests = weather.server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+
+    // responses of another server, e.g. a proxy, are cached apart
+    let other = MockServer::start().await;
+    weather
+        .cmd()
+        .args(["get", "London"])
+        .env("WEATHER_OPENWEATHER_URL", other.uri())
+        .assert()
+        .failure();
+    assert_eq!(other.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn errors() {
+    let weather = Weather::start().await;
+
+    // not configured yet
+    weather
+        .cmd()
+        .args(["get", "London"])
+        .assert()
+        .code(3)
+        .stderr(contains("no configuration file"))
+        .stderr(contains("Hint: run `weather configure <PROVIDER>`"));
+
+    weather.configure_unchecked("open-weather", common::INVALID_API_KEY);
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache"])
+        .assert()
+        .code(5)
+        .stderr(contains(
+            "openweather rejected the API key: Invalid API key.",
+        ));
+
+    weather.configure("open-weather", common::API_KEY);
+    weather
+        .cmd()
+        .args(["get", "Atlantis", "--no-cache"])
+        .assert()
+        .code(6)
+        .stderr(contains("city not found"));
+    weather
+        .cmd()
+        .args(["get", "Nowhere", "2023-03-15 12:00:00", "--no-cache"])
+        .assert()
+        .code(9)
+        .stderr(contains("no weather history"));
+
+    std::fs::write(
+        weather.home.path().join("config/weather/config.toml"),
+        "providers = [",
+    )
+    .unwrap();
+    weather
+        .cmd()
+        .args(["get", "London"])
+        .assert()
+        .code(4)
+        .stderr(contains("corrupt configuration file"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn verbose_retries() {
+    let weather = Weather::start().await;
+    weather.configure("weather-api", common::API_KEY);
+
+    weather
+        .cmd()
+        .args(["get", "Flaky", "--no-cache", "--verbose"])
+        .assert()
+        .success()
+        .stderr(contains("weatherapi: HTTP 503, retry 1 of 3 in 0.0 s\n"))
+        .stderr(contains("after 2 retries\n"));
+
+    weather
+        .cmd()
+        .args(["network", "--read-timeout", "1", "--retries", "0"])
+        .assert()
+        .success()
+        .stdout(contains("read timeout: 1 s\n"));
+    weather
+        .cmd()
+        .args(["get", "Slow", "--no-cache"])
+        .assert()
+        .code(8)
+        .stderr(contains("weatherapi didn't respond within 1 s"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fallback() {
+    let weather = Weather::start().await;
+    weather.configure_unchecked("open-weather", common::INVALID_API_KEY);
+    weather.configure("weather-api", common::API_KEY);
+
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache"])
+        .assert()
+        .failure();
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache", "--fallback"])
+        .assert()
+        .success()
+        .stderr(contains("openweather failed"))
+        .stdout(contains("provided by weatherapi\n"));
+
+    weather
+        .cmd()
+        .args(["providers", "fallback", "on"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache"])
+        .assert()
+        .success();
+    // an explicitly selected provider is never fallen back from
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache", "--provider", "open-weather"])
+        .assert()
+        .failure();
+
+    weather
+        .cmd()
+        .args(["providers", "order", "weather-api"])
+        .assert()
+        .success()
+        .stdout(contains("1. weatherapi\n2. openweather\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compare() {
+    let weather = Weather::start().await;
+    weather.configure("open-weather", common::API_KEY);
+    weather.configure("weather-api", common::API_KEY);
+
+    weather
+        .cmd()
+        .args(["compare", "London", "--no-cache"])
+        .assert()
+        .success()
+        .stdout(contains("openweather  weatherapi  consensus  spread\n"))
+        .stdout(contains(
+            "temperature     8.5 °C       9.0 °C      8.8 °C     0.5 °C\n",
+        ))
+        .stdout(contains(
+            "wind direction  → 270°       ↘ 240°      → 255°     30°\n",
+        ));
+
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache", "--aggregate"])
+        .assert()
+        .success()
+        .stdout(contains("+8.8 °C"))
+        .stdout(contains("provided by openweather+weatherapi\n"));
+
+    // a failing provider is left out
+    weather.configure_unchecked("weather-api", common::INVALID_API_KEY);
+    weather
+        .cmd()
+        .args(["compare", "London", "--no-cache"])
+        .assert()
+        .success()
+        .stderr(contains("weatherapi failed"))
+        .stdout(contains("openweather  consensus  spread\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn usage_limits() {
+    let weather = Weather::start().await;
+    // the key is checked with a request, which counts
+    weather.configure("open-weather", common::API_KEY);
+
+    weather
+        .cmd()
+        .args(["usage", "limit", "open-weather", "--per-day", "3"])
+        .assert()
+        .success()
+        .stdout(contains("per minute: none\n"))
+        .stdout(contains("per day: 3\n"));
+
+    for _ in 0..2 {
+        weather
+            .cmd()
+            .args(["get", "London", "--no-cache"])
+            .assert()
+            .success();
+    }
+    weather
+        .cmd()
+        .args(["usage"])
+        .assert()
+        .success()
+        .stdout(contains("openweather (key ****-key)\n"))
+        .stdout(contains("today: 3 of 3\n"));
+
+    // the hard stop comes before the request is sent
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache"])
+        .assert()
+        .code(7)
+        .stderr(contains(
+            "Error: openweather usage limit of 3 calls per day reached",
+        ));
+
+    // cached responses don't count
+    weather
+        .cmd()
+        .args(["usage", "limit", "open-weather", "--per-day", "0"])
+        .assert()
+        .success()
+        .stdout(contains("per day: none\n"));
+    for _ in 0..2 {
+        weather.cmd().args(["get", "London"]).assert().success();
+    }
+    weather
+        .cmd()
+        .args(["usage"])
+        .assert()
+        .success()
+        .stdout(contains("today: 4\n"));
+
+    // only the limits are written, not the key given in the environment
+    weather
+        .cmd()
+        .args(["usage", "limit", "open-weather", "--per-month", "100"])
+        .env("WEATHER_OPENWEATHER_API_KEY", "env-key")
+        .assert()
+        .success()
+        .stdout(contains("per month: 100\n"));
+    let config = std::fs::read_to_string(weather.home.path().join("config/weather/config.toml"));
+    let config = config.unwrap();
+    assert!(config.contains(common::API_KEY));
+    assert!(!config.contains("env-key"));
+
+    // a provider configured in another file only can't be limited in the written file
+    weather
+        .cmd()
+        .args(["usage", "limit", "weather-api", "--per-day", "3"])
+        .env("WEATHER_WEATHERAPI_API_KEY", "env-key")
+        .assert()
+        .code(2)
+        .stderr(contains("providers.weatherapi"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn usage_across_processes() {
+    let weather = Weather::start().await;
+    weather.configure("open-weather", common::API_KEY);
+
+    // every call is counted when several processes record them at once
+    let children: Vec<_> = (0..8)
+        .map(|_| {
+            weather
+                .std_cmd()
+                .args(["get", "London", "--no-cache"])
+                .stdout(std::process::Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+    weather
+        .cmd()
+        .args(["usage"])
+        .assert()
+        .success()
+        .stdout(contains("today: 9\n"));
+
+    // corrupt counters don't fail the requests, they start again from zero
+    let usage = weather.home.path().join("data/weather/usage.json");
+    std::fs::write(&usage, "{").unwrap();
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache"])
+        .assert()
+        .success()
+        .stderr(contains("are corrupt, counting from zero"));
+    weather
+        .cmd()
+        .args(["usage"])
+        .assert()
+        .success()
+        .stdout(contains("today: 1\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn configure_non_interactive() {
+    let weather = Weather::start().await;
+
+    // the key is checked before it is saved
+    weather
+        .cmd()
+        .args([
+            "configure",
+            "open-weather",
+            "--api-key",
+            common::INVALID_API_KEY,
+        ])
+        .assert()
+        .code(5)
+        .stderr(contains("openweather rejected the API key"));
+    weather.cmd().args(["get", "London"]).assert().code(3);
+
+    let key_file = weather.home.path().join("key");
+    std::fs::write(&key_file, format!("{}\n", common::API_KEY)).unwrap();
+    weather
+        .cmd()
+        .args(["configure", "open-weather", "--api-key-file"])
+        .arg(&key_file)
+        .assert()
+        .success()
+        .stdout(contains("Key checked successfully."));
+
+    weather
+        .cmd()
+        .args(["configure", "weather-api"])
+        .env("WEATHER_WEATHERAPI_API_KEY", common::API_KEY)
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["providers", "list"])
+        .assert()
+        .success()
+        .stdout("1. openweather\n2. weatherapi\n");
+
+    // without stdin nor any other source of the key
+    weather
+        .cmd()
+        .args(["configure", "weather-api"])
+        .assert()
+        .code(2)
+        .stderr(contains("no API key given for weatherapi"));
+
+    // the settings from other files and the environment are used to check the key but aren't
+    // copied into the written file
+    weather
+        .cmd()
+        .args(["usage", "limit", "open-weather", "--per-day", "100"])
+        .assert()
+        .success();
+    let extra = weather.home.path().join("extra.toml");
+    weather
+        .cmd()
+        .arg("--config")
+        .arg(&extra)
+        .args(["configure", "open-weather", "--api-key", "other-key"])
+        .assert()
+        .success()
+        .stdout(contains("Key checked successfully."));
+    let written = std::fs::read_to_string(&extra).unwrap();
+    assert!(written.contains("other-key"), "{written}");
+    assert!(!written.contains("per_day"), "{written}");
+    assert!(!written.contains(&weather.server.uri()), "{written}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn get_from_env() {
+    let weather = Weather::start().await;
+
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache"])
+        .env("WEATHER_WEATHERAPI_API_KEY", common::API_KEY)
+        .assert()
+        .success()
+        .stdout(contains("provided by weatherapi"));
+    assert!(!weather
+        .home
+        .path()
+        .join("config/weather/config.json")
+        .exists());
+
+    // the key in the environment takes precedence over the configured one
+    weather.configure_unchecked("weather-api", common::INVALID_API_KEY);
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache"])
+        .env("WEATHER_WEATHERAPI_API_KEY", common::API_KEY)
+        .assert()
+        .success();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_layers_and_profiles() {
+    let weather = Weather::start().await;
+    weather.configure_unchecked("weather-api", common::API_KEY);
+
+    // no location given nor configured
+    weather
+        .cmd()
+        .args(["get", "--no-cache"])
+        .assert()
+        .code(2)
+        .stderr(contains("no default location configured"));
+
+    let project = weather.home.path().join("project");
+    std::fs::create_dir_all(project.join("src")).unwrap();
+    std::fs::write(
+        project.join(".weather.toml"),
+        "location = \"London\"\noutput = \"kv\"\n\n[profiles.csv]\noutput = \"csv\"\n",
+    )
+    .unwrap();
+    weather
+        .cmd()
+        .current_dir(project.join("src"))
+        .args(["get", "--no-cache"])
+        .assert()
+        .success()
+        .stdout(contains("location=\"London, City of London"));
+    weather
+        .cmd()
+        .current_dir(&project)
+        .args(["get", "--no-cache", "--profile", "csv"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("time,"));
+    weather
+        .cmd()
+        .current_dir(&project)
+        .args(["get", "--no-cache", "--profile", "home"])
+        .assert()
+        .code(3)
+        .stderr(contains("no profile named home"));
+
+    // a project file can't run commands nor send the keys elsewhere
+    let marker = weather.home.path().join("pwned");
+    std::fs::write(
+        project.join(".weather.toml"),
+        format!(
+            "location = \"London\"\n\n[[providers]]\nprovider = \"weatherapi\"\n\
+             api_key_cmd = \"touch {}\"\nbase_url = \"http://attacker.invalid\"\n",
+            marker.display()
+        ),
+    )
+    .unwrap();
+    weather
+        .cmd()
+        .current_dir(&project)
+        .args(["get", "--no-cache"])
+        .assert()
+        .success()
+        .stderr(contains("Ignoring `providers.weatherapi.api_key_cmd`"))
+        .stderr(contains("Ignoring `providers.weatherapi.base_url`"));
+    assert!(!marker.exists());
+
+    // writes go to the file given with --config, into the selected profile
+    let extra = weather.home.path().join("extra.toml");
+    weather
+        .cmd()
+        .arg("--config")
+        .arg(&extra)
+        .args(["units", "--units", "imperial", "--profile", "us"])
+        .assert()
+        .success()
+        .stdout(contains("temperature: °F"));
+    let written = std::fs::read_to_string(&extra).unwrap();
+    assert!(written.contains("[profiles.us.units]"), "{written}");
+    weather
+        .cmd()
+        .args(["units"])
+        .assert()
+        .success()
+        .stdout(contains("temperature: °C"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn migrates_json_config() {
+    let weather = Weather::start().await;
+    let config_dir = weather.home.path().join("config/weather");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.json"),
+        format!(
+            r#"{{"provider":{{"WeatherApi":{{"api_key":"{}"}}}}}}"#,
+            common::API_KEY
+        ),
+    )
+    .unwrap();
+
+    weather
+        .cmd()
+        .args(["get", "London", "--no-cache"])
+        .assert()
+        .success()
+        .stderr(contains("Migrated the configuration"));
+    assert!(config_dir.join("config.toml").exists());
+    assert!(config_dir.join("config.json.bak").exists());
+    assert!(!config_dir.join("config.json").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_command() {
+    let weather = Weather::start().await;
+    weather.configure_unchecked("open-weather", common::API_KEY);
+
+    weather
+        .cmd()
+        .args(["config", "set", "location", "London"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["config", "set", "cache_ttl", "60"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args([
+            "config",
+            "set",
+            "providers.weatherapi.api_key",
+            common::API_KEY,
+        ])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["config", "get", "cache_ttl"])
+        .assert()
+        .success()
+        .stdout("60\n");
+    weather
+        .cmd()
+        .args(["config", "get", "providers.openweather.api_key"])
+        .assert()
+        .success()
+        .stdout("****-key\n");
+    weather
+        .cmd()
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(contains("location = \"London\"\n"))
+        .stdout(contains("api_key = \"****-key\"\n"))
+        .stdout(contains(common::API_KEY).not());
+    weather
+        .cmd()
+        .args(["config", "list-providers"])
+        .assert()
+        .success()
+        .stdout("1. openweather (key ****-key)\n2. weatherapi (key ****-key)\n");
+
+    // invalid values and unknown settings are rejected
+    for (key, value, message) in [
+        ("cache_ttl", "soon", "invalid type"),
+        ("units.system", "furlongs", "unknown variant"),
+        ("colour", "blue", "no such setting"),
+        ("providers.darksky.api_key", "key", "unknown variant"),
+        ("location", "zip:", "invalid location"),
+    ] {
+        weather
+            .cmd()
+            .args(["config", "set", key, value])
+            .assert()
+            .code(2)
+            .stderr(contains(message));
+    }
+
+    weather
+        .cmd()
+        .args(["config", "unset", "location"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["config", "get", "location"])
+        .assert()
+        .code(2)
+        .stderr(contains("setting location: not set"));
+    weather
+        .cmd()
+        .args(["config", "remove-provider", "open-weather"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["providers", "list"])
+        .assert()
+        .success()
+        .stdout("1. weatherapi\n");
+    weather
+        .cmd()
+        .args(["config", "path"])
+        .assert()
+        .success()
+        .stdout(contains("written: "))
+        .stdout(contains("config.toml\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn api_key_storage() {
+    let weather = Weather::start().await;
+    let config_file = weather.home.path().join("config/weather/config.toml");
+    weather.configure_unchecked("open-weather", common::API_KEY);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&config_file)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // keys printed by a command
+    weather
+        .cmd()
+        .args([
+            "config",
+            "set",
+            "providers.weatherapi.api_key_cmd",
+            &format!("echo {}", common::API_KEY),
+        ])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["get", "London", "--provider", "weather-api", "--no-cache"])
+        .assert()
+        .success()
+        .stdout(contains("provided by weatherapi"));
+    weather
+        .cmd()
+        .args(["config", "list-providers"])
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "weatherapi (key from `echo {}`)",
+            common::API_KEY
+        )));
+
+    // encrypted keys
+    weather
+        .cmd()
+        .args(["config", "encrypt-keys"])
+        .env("WEATHER_PASSPHRASE", "correct horse")
+        .assert()
+        .success()
+        .stdout("Encrypted 1 API keys.\n");
+    let written = std::fs::read_to_string(&config_file).unwrap();
+    assert!(written.contains("api_key = \"enc:"));
+    assert!(!written.contains(&format!("api_key = \"{}\"", common::API_KEY)));
+    // keys printed by a command don't need the passphrase
+    weather
+        .cmd()
+        .args(["config", "encrypt-keys"])
+        .env_remove("WEATHER_PASSPHRASE")
+        .assert()
+        .success()
+        .stdout("Encrypted 0 API keys.\n");
+    weather
+        .cmd()
+        .args(["get", "London", "--provider", "open-weather", "--no-cache"])
+        .env("WEATHER_PASSPHRASE", "correct horse")
+        .assert()
+        .success()
+        .stdout(contains("provided by openweather"));
+    weather
+        .cmd()
+        .args(["get", "London", "--provider", "open-weather", "--no-cache"])
+        .env("WEATHER_PASSPHRASE", "wrong")
+        .assert()
+        .code(5)
+        .stderr(contains(
+            "couldn't get the API key of openweather: wrong passphrase",
+        ));
+    // a key that cannot be resolved only fails its provider
+    weather
+        .cmd()
+        .args(["get", "London", "--fallback", "--no-cache"])
+        .env("WEATHER_PASSPHRASE", "wrong")
+        .assert()
+        .success()
+        .stderr(contains("openweather failed: couldn't get the API key"))
+        .stdout(contains("provided by weatherapi"));
+
+    // keys set directly are encrypted like the stored ones
+    let set_key = ["config", "set", "providers.weatherapi.api_key", "other-key"];
+    weather
+        .cmd()
+        .args(set_key)
+        .env("WEATHER_PASSPHRASE", "wrong")
+        .assert()
+        .code(5);
+    weather
+        .cmd()
+        .args(set_key)
+        .env("WEATHER_PASSPHRASE", "correct horse")
+        .assert()
+        .success();
+    let written = std::fs::read_to_string(&config_file).unwrap();
+    assert_eq!(written.matches("api_key = \"enc:").count(), 2, "{written}");
+    assert!(!written.contains("other-key"), "{written}");
+
+    weather
+        .cmd()
+        .args(["config", "decrypt-keys"])
+        .env("WEATHER_PASSPHRASE", "correct horse")
+        .assert()
+        .success()
+        .stdout("Decrypted 2 API keys.\n");
+    let written = std::fs::read_to_string(&config_file).unwrap();
+    assert!(written.contains(&format!("api_key = \"{}\"", common::API_KEY)));
+    assert!(written.contains("api_key = \"other-key\""));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn locate_and_pick() {
+    let weather = Weather::start().await;
+    weather.configure_unchecked("open-weather", common::API_KEY);
+    weather.configure_unchecked("weather-api", common::API_KEY);
+
+    weather
+        .cmd()
+        .args(["locate", "London", "--provider", "open-weather"])
+        .assert()
+        .success()
+        .stdout(
+            "1. London, England, GB (51.5073219,-0.1276474)\n\
+             2. London, Ontario, CA (42.9832406,-81.243372)\n\
+             3. London, Kentucky, US (37.1289771,-84.0832646)\n",
+        );
+    weather
+        .cmd()
+        .args(["locate", "London", "--provider", "weather-api"])
+        .assert()
+        .success()
+        .stdout(contains("2. London, Ontario, Canada (42.98,-81.25)\n"));
+    weather
+        .cmd()
+        .args(["locate", "Atlantis", "--provider", "open-weather"])
+        .assert()
+        .code(6)
+        .stderr(contains("no place matches \"Atlantis\""));
+
+    // the places are cached, so picking one asks for the weather only
+    let before = weather.server.received_requests().await.unwrap().len();
+    for provider in ["open-weather", "weather-api"] {
+        weather
+            .cmd()
+            .args(["get", "London", "--pick", "2", "--provider", provider])
+            .assert()
+            .success();
+    }
+    let requests = weather.server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), before + 2);
+    assert!(requests[before]
+        .url
+        .query()
+        .unwrap()
+        .contains("lat=42.9832406"));
+    assert!(requests[before + 1]
+        .url
+        .query_pairs()
+        .any(|(key, value)| key == "q" && value == "42.98,-81.25"));
+
+    weather
+        .cmd()
+        .args(["get", "London", "--pick", "4", "--provider", "open-weather"])
+        .assert()
+        .code(2)
+        .stderr(contains("no place 4 among the 3 places matching London"));
+
+    // without --pick, nothing is looked up
+    weather.cmd().arg("cache").arg("clear").assert().success();
+    let before = weather.server.received_requests().await.unwrap().len();
+    weather
+        .cmd()
+        .args(["get", "London", "--provider", "open-weather"])
+        .assert()
+        .success();
+    let requests = weather.server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), before + 1);
+    assert_eq!(requests[before].url.path(), "/data/2.5/weather");
+
+    // --pick without a number asks which place is meant
+    weather
+        .cmd()
+        .args(["get", "London", "--pick", "--provider", "weather-api"])
+        .write_stdin("2\n")
+        .assert()
+        .success()
+        .stderr(contains("Pick a place [1-2, default 1]:"));
+
+    // no matching place leaves the name to the provider
+    weather
+        .cmd()
+        .args([
+            "get",
+            "Atlantis",
+            "--pick",
+            "1",
+            "--provider",
+            "open-weather",
+        ])
+        .assert()
+        .code(6)
+        .stderr(contains("openweather doesn't know the location"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn saved_places() {
+    let weather = Weather::start().await;
+    weather.configure_unchecked("open-weather", common::API_KEY);
+
+    weather
+        .cmd()
+        .args(["places", "add", "hq", "London"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["places", "add", "lab", "51.5,-0.12"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["places", "add", "the.lab", "Paris"])
+        .assert()
+        .code(2)
+        .stderr(contains(
+            "setting places.the.lab: the name may only contain",
+        ));
+    weather
+        .cmd()
+        .args(["places", "default", "hq"])
+        .assert()
+        .success()
+        .stdout("Default location saved successfully.\nhq\n");
+    weather
+        .cmd()
+        .args(["places", "list"])
+        .assert()
+        .success()
+        .stdout("hq: London (default)\nlab: 51.5,-0.12\n");
+
+    // saved places are accepted wherever a location is, and as the default location
+    weather
+        .cmd()
+        .args(["get", "--no-cache"])
+        .assert()
+        .success()
+        .stdout(contains("London"));
+    weather
+        .cmd()
+        .args(["forecast", "hq", "--no-cache"])
+        .assert()
+        .success();
+
+    weather
+        .cmd()
+        .args(["places", "remove", "hq"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["places", "remove", "hq"])
+        .assert()
+        .code(2);
+    weather
+        .cmd()
+        .args(["places", "default"])
+        .assert()
+        .success()
+        .stdout("none\n");
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn serve() {
+    let weather = Weather::start().await;
+    weather.configure_unchecked("open-weather", common::API_KEY);
+    weather.configure_unchecked("weather-api", common::API_KEY);
+    weather
+        .cmd()
+        .args(["places", "add", "hq", "London"])
+        .assert()
+        .success();
+
+    let mut server = weather.spawn(&["serve", "--bind", "127.0.0.1:0", "--no-cache"]);
+    let get = |path: &str| reqwest::get(format!("{}{path}", server.url));
+    let response = get("/current?location=hq&units=imperial").await.unwrap();
+    assert_eq!(response.status(), 200);
+    let json: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(json["provider"], "openweather");
+    assert_eq!(json["units"]["temperature"], "fahrenheit");
+
+    let response = get("/forecast?location=London&days=1").await.unwrap();
+    assert_eq!(response.status(), 200);
+    let json: serde_json::Value = response.json().await.unwrap();
+    assert!(!json.as_array().unwrap().is_empty());
+
+    let response = get("/current?location=Atlantis").await.unwrap();
+    assert_eq!(response.status(), 404);
+    let json: serde_json::Value = response.json().await.unwrap();
+    assert!(json["error"]
+        .as_str()
+        .unwrap()
+        .contains("doesn't know the location"));
+
+    let response = get("/history?location=London&from=2023-03-16 00:00:00&to=2023-03-15 00:00:00")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let before = weather.server.received_requests().await.unwrap().len();
+    let response = get("/history?location=London&from=2023-01-01 00:00:00&to=2023-03-15 00:00:00")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let json: serde_json::Value = response.json().await.unwrap();
+    assert!(json["error"]
+        .as_str()
+        .unwrap()
+        .contains("longer than 31 days"));
+    assert_eq!(
+        weather.server.received_requests().await.unwrap().len(),
+        before
+    );
+
+    // stops on SIGTERM
+    let log = server.stop();
+    assert!(log.contains("GET /current?location=hq&units=imperial 200"));
+    assert!(log.contains("GET /current?location=Atlantis 404"));
+    assert!(log.contains("Server stopped"));
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn exporter() {
+    let weather = Weather::start().await;
+    weather.configure_unchecked("open-weather", common::API_KEY);
+    weather
+        .cmd()
+        .args(["places", "add", "hq", "London"])
+        .assert()
+        .success();
+    weather
+        .cmd()
+        .args(["places", "add", "lost", "Atlantis"])
+        .assert()
+        .success();
+
+    let mut exporter = weather.spawn(&["exporter", "--bind", "127.0.0.1:0", "--interval", "1"]);
+    // the second poll is answered from the cache
+    let mut text = String::new();
+    for _ in 0..50 {
+        text = reqwest::get(&exporter.url)
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        if text.contains(r#"weather_cache_requests_total{provider="openweather",result="hit"}"#) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(text.contains(r#"weather_temperature_celsius{location="hq",provider="openweather"}"#));
+    assert!(
+        text.contains(r#"weather_wind_direction_degrees{location="hq",provider="openweather"}"#)
+    );
+    assert!(text.contains(r#"weather_cache_requests_total{provider="openweather",result="hit"}"#));
+    assert!(text.contains(r#"weather_poll_errors_total{location="lost"}"#));
+    assert!(text.contains(r#"weather_provider_errors_total{provider="openweather",reason="404"}"#));
+    assert!(
+        text.contains(r#"weather_provider_request_duration_seconds_count{provider="openweather"}"#)
+    );
+
+    let log = exporter.stop();
+    assert!(log.contains("Polling lost failed"));
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn watch() {
+    let weather = Weather::start().await;
+    weather.configure("open-weather", common::API_KEY);
+
+    let child = weather
+        .std_cmd()
+        .args(["get", "London", "--watch", "1", "--no-cache"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+    // Ctrl-C
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.matches("Every 1 s, updated at").count() >= 2);
+    assert!(stdout.contains("London, GB (51.5085,-0.1257)\n"));
+    assert!(stdout.contains("No changes since"));
+
+    // Ctrl-C also stops a request in progress
+    let mut child = weather
+        .std_cmd()
+        .args(["get", "Slow", "--watch", "1", "--no-cache"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    // the first response takes 3 s, and the next request is sent 1 s after it
+    tokio::time::sleep(std::time::Duration::from_millis(5000)).await;
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let stopped = std::time::Instant::now();
+    assert!(child.wait().unwrap().success());
+    assert!(stopped.elapsed() < std::time::Duration::from_secs(1));
+
+    weather
+        .cmd()
+        .args(["get", "London", "2023-03-15 12:00:00", "--watch", "1"])
+        .assert()
+        .failure()
+        .code(2);
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn alerts() {
+    use wiremock::{
+        matchers::{method, path},
+        Mock, ResponseTemplate,
+    };
+
+    let weather = Weather::start().await;
+    weather.configure("open-weather", common::API_KEY);
+    Mock::given(method("POST"))
+        .and(path("/hook"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(2)
+        .mount(&weather.server)
+        .await;
+    let hook = weather.home.path().join("hook.log");
+    for args in [
+        vec!["places", "add", "hq", "London"],
+        vec!["alerts", "add", "wind.speed > 10 km/h"],
+        vec!["alerts", "add", "rain_volume > 100mm"],
+        vec![
+            "config",
+            "set",
+            "alerts.command",
+            &format!(
+                "echo \"$WEATHER_ALERT_PLACE|$WEATHER_ALERT_RULE|$WEATHER_ALERT_FORECAST\" >> {}",
+                hook.display()
+            ),
+        ],
+        vec![
+            "config",
+            "set",
+            "alerts.webhook",
+            &format!("{}/hook", weather.server.uri()),
+        ],
+    ] {
+        weather.cmd().args(args).assert().success();
+    }
+    weather
+        .cmd()
+        .args(["alerts", "add", "wind > 10"])
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(contains("unknown field \"wind\""));
+    weather
+        .cmd()
+        .args(["alerts", "list"])
+        .assert()
+        .success()
+        .stdout(contains("wind.speed > 10 km/h\nrain_volume > 100 mm\n"))
+        .stdout(contains("Sent to stdout, command `echo"));
+
+    weather
+        .cmd()
+        .args(["alerts", "check", "--no-cache"])
+        .assert()
+        .success()
+        .stdout(contains("hq: wind.speed > 10 km/h (18.0 km/h now)\n"))
+        .stdout(contains("hq: wind.speed > 10 km/h (").and(contains("expected at")))
+        .stdout(contains("rain_volume").not());
+    assert_eq!(
+        std::fs::read_to_string(&hook).unwrap(),
+        "hq|wind.speed > 10 km/h|false\nhq|wind.speed > 10 km/h|true\n"
+    );
+
+    // the same alerts are not sent again
+    weather
+        .cmd()
+        .args(["alerts", "check", "--no-cache"])
+        .assert()
+        .success()
+        .stdout("");
+
+    // Ctrl-C also stops a check in progress
+    let mut child = weather
+        .std_cmd()
+        .args(["alerts", "check", "Slow", "--watch", "60", "--no-cache"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    // the response takes 3 s
+    tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let stopped = std::time::Instant::now();
+    assert!(child.wait().unwrap().success());
+    assert!(stopped.elapsed() < std::time::Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn tui() {
+    let weather = Weather::start().await;
+    weather.configure("open-weather", common::API_KEY);
+
+    // the output of the tests is not a terminal
+    weather
+        .cmd()
+        .args(["tui", "London"])
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(contains("Error: the dashboard needs a terminal"))
+        .stderr(contains("weather get --watch"));
+}
+
+#[tokio::test]
+async fn unwritable_cache() {
+    let weather = Weather::start().await;
+    weather.configure("open-weather", common::API_KEY);
+    // the cache directory cannot be created below a file
+    std::fs::write(weather.home.path().join("cache"), "").unwrap();
+
+    weather
+        .cmd()
+        .args(["get", "London"])
+        .assert()
+        .success()
+        .stdout(contains("London"))
+        .stderr(contains("Couldn't write the response cache"));
+}
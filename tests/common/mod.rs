@@ -0,0 +1,156 @@
+//! A local stand-in for the OpenWeather and WeatherAPI services, serving recorded responses from
+//! `tests/fixtures`.
+//!
+//! Both providers are served from the same server: OpenWeather under `/data/2.5/` and WeatherAPI
+//! under `/v1/`, and OpenWeather's geocoding under `/geo/1.0/`. The recorded responses are for
+//! London, whose name matches places in several countries, and for the coordinates of London,
+//! Ontario, which are answered like London. "Atlantis" is an unknown location,
+//! "Nowhere" has no history, "Outage" fails with a server error, "Flaky" fails twice before
+//! answering like London and "Slow" answers after 3 seconds. The API key "invalid" is
+//! rejected and the API key "exhausted" has exceeded its quota.
+#![allow(dead_code)]
+
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+pub const API_KEY: &str = "test-key";
+pub const INVALID_API_KEY: &str = "invalid";
+pub const EXHAUSTED_API_KEY: &str = "exhausted";
+
+pub fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("can't read {path}: {e}"))
+}
+
+fn json(status: u16, name: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_raw(fixture(name), "application/json")
+}
+
+pub async fn start() -> MockServer {
+    let server = MockServer::start().await;
+
+    // invalid keys are rejected before anything else
+    Mock::given(query_param("appid", INVALID_API_KEY))
+        .respond_with(json(401, "openweather/error_401.json"))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(query_param("key", INVALID_API_KEY))
+        .respond_with(json(401, "weatherapi/error_401.json"))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(query_param("appid", EXHAUSTED_API_KEY))
+        .respond_with(json(429, "openweather/error_429.json"))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(query_param("key", EXHAUSTED_API_KEY))
+        .respond_with(json(403, "weatherapi/error_403.json"))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(query_param("q", "Outage"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("Service Unavailable"))
+        .mount(&server)
+        .await;
+
+    for (endpoint, fixture) in [
+        ("/data/2.5/weather", "openweather/current.json"),
+        ("/v1/current.json", "weatherapi/current.json"),
+    ] {
+        Mock::given(path(endpoint))
+            .and(query_param("q", "Flaky"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "0"))
+            .up_to_n_times(2)
+            .with_priority(2)
+            .mount(&server)
+            .await;
+        Mock::given(path(endpoint))
+            .and(query_param("q", "Flaky"))
+            .respond_with(json(200, fixture))
+            .mount(&server)
+            .await;
+        Mock::given(path(endpoint))
+            .and(query_param("q", "Slow"))
+            .respond_with(json(200, fixture).set_delay(std::time::Duration::from_secs(3)))
+            .mount(&server)
+            .await;
+    }
+
+    for (endpoint, q, fixture) in [
+        ("/data/2.5/weather", "London", "openweather/current.json"),
+        ("/data/2.5/forecast", "London", "openweather/forecast.json"),
+        (
+            "/data/2.5/history/city",
+            "London",
+            "openweather/history.json",
+        ),
+        (
+            "/data/2.5/history/city",
+            "Nowhere",
+            "openweather/history_empty.json",
+        ),
+        ("/v1/current.json", "London", "weatherapi/current.json"),
+        ("/v1/forecast.json", "London", "weatherapi/forecast.json"),
+        ("/v1/history.json", "London", "weatherapi/history.json"),
+        (
+            "/v1/history.json",
+            "Nowhere",
+            "weatherapi/history_empty.json",
+        ),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(endpoint))
+            .and(query_param("q", q))
+            .respond_with(json(200, fixture))
+            .mount(&server)
+            .await;
+    }
+
+    for (endpoint, fixture) in [
+        ("/geo/1.0/direct", "openweather/geo.json"),
+        ("/v1/search.json", "weatherapi/search.json"),
+    ] {
+        Mock::given(path(endpoint))
+            .and(query_param("q", "London"))
+            .respond_with(json(200, fixture))
+            .mount(&server)
+            .await;
+        Mock::given(path(endpoint))
+            .and(query_param("q", "Atlantis"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("[]", "application/json"))
+            .mount(&server)
+            .await;
+    }
+    Mock::given(path("/data/2.5/weather"))
+        .and(query_param("lat", "42.9832406"))
+        .and(query_param("lon", "-81.243372"))
+        .respond_with(json(200, "openweather/current.json"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/v1/current.json"))
+        .and(query_param("q", "42.98,-81.25"))
+        .respond_with(json(200, "weatherapi/current.json"))
+        .mount(&server)
+        .await;
+
+    for endpoint in ["/data/2.5/weather", "/data/2.5/forecast"] {
+        Mock::given(path(endpoint))
+            .and(query_param("q", "Atlantis"))
+            .respond_with(json(404, "openweather/error_404.json"))
+            .mount(&server)
+            .await;
+    }
+    for endpoint in ["/v1/current.json", "/v1/forecast.json", "/v1/history.json"] {
+        Mock::given(path(endpoint))
+            .and(query_param("q", "Atlantis"))
+            .respond_with(json(400, "weatherapi/error_400.json"))
+            .mount(&server)
+            .await;
+    }
+
+    server
+}
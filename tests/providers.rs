@@ -0,0 +1,418 @@
+mod common;
+
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use rstest::rstest;
+use weather_lib::{
+    error::Error,
+    location::{Coordinates, Location},
+    network::RequestPolicy,
+    providers::{ProviderUserInfo, WeatherProvider},
+    usage::Limits,
+};
+
+/// Doesn't retry, so that failing requests fail fast.
+const NO_RETRIES: RequestPolicy = RequestPolicy {
+    connect_timeout: Duration::from_secs(10),
+    read_timeout: Duration::from_secs(30),
+    retries: 0,
+    verbose: false,
+};
+
+fn openweather(server: &wiremock::MockServer, api_key: &str) -> Box<dyn WeatherProvider> {
+    openweather_with(server, api_key, NO_RETRIES)
+}
+
+fn openweather_with(
+    server: &wiremock::MockServer,
+    api_key: &str,
+    policy: RequestPolicy,
+) -> Box<dyn WeatherProvider> {
+    ProviderUserInfo::OpenWeather {
+        api_key: api_key.into(),
+        api_key_cmd: None,
+        base_url: Some(server.uri()),
+        history_base_url: Some(server.uri()),
+        limits: Limits::default(),
+    }
+    .build_provider(policy, None)
+}
+
+fn weatherapi(server: &wiremock::MockServer, api_key: &str) -> Box<dyn WeatherProvider> {
+    ProviderUserInfo::WeatherApi {
+        api_key: api_key.into(),
+        api_key_cmd: None,
+        base_url: Some(server.uri()),
+        limits: Limits::default(),
+    }
+    .build_provider(NO_RETRIES, None)
+}
+
+fn london() -> Location {
+    Location::City("London".into())
+}
+
+#[tokio::test]
+async fn openweather_current() {
+    let server = common::start().await;
+    let weather = openweather(&server, common::API_KEY)
+        .get_weather(&london())
+        .await
+        .unwrap();
+
+    assert_eq!(weather.location, "London, GB");
+    assert_eq!(
+        weather.coordinates,
+        Some(Coordinates {
+            lat: 51.5085,
+            lon: -0.1257
+        })
+    );
+    assert_eq!(
+        weather.time,
+        Utc.with_ymd_and_hms(2023, 3, 20, 12, 0, 0).unwrap()
+    );
+    assert_eq!(weather.description, "light rain");
+    assert_eq!(weather.temperature, 8.5);
+    assert_eq!(weather.wind.speed, 5.0);
+    assert_eq!(weather.wind.deg, 270);
+    assert_eq!(weather.wind.gust, Some(9.3));
+    assert_eq!(weather.rain_volume, 0.42);
+    assert_eq!(weather.visibility, Some(10000.));
+    assert_eq!(weather.humidity, 81);
+    assert_eq!(weather.utc_offset, Some(0));
+}
+
+#[tokio::test]
+async fn openweather_history() {
+    let server = common::start().await;
+    let date = Utc.with_ymd_and_hms(2023, 3, 15, 15, 0, 0).unwrap();
+    let weather = openweather(&server, common::API_KEY)
+        .get_history_weather(&london(), date)
+        .await
+        .unwrap();
+
+    assert_eq!(weather.time, date);
+    assert_eq!(weather.location, "London");
+    assert_eq!(weather.coordinates, None);
+    assert_eq!(weather.temperature, 11.3);
+    assert_eq!(weather.description, "few clouds");
+    assert_eq!(weather.rain_volume, 0.);
+}
+
+#[tokio::test]
+async fn openweather_forecast() {
+    let server = common::start().await;
+    let forecast = openweather(&server, common::API_KEY)
+        .get_forecast(&london(), 1)
+        .await
+        .unwrap();
+
+    assert_eq!(forecast.len(), 8);
+    assert!(forecast
+        .windows(2)
+        .all(|x| x[1].time - x[0].time == chrono::Duration::hours(3)));
+    assert_eq!(forecast[0].rain_volume, 1.2);
+    assert_eq!(forecast[1].rain_volume, 0.);
+    assert_eq!(forecast[7].temperature, 11.5);
+}
+
+#[tokio::test]
+async fn weatherapi_current() {
+    let server = common::start().await;
+    let weather = weatherapi(&server, common::API_KEY)
+        .get_weather(&london())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        weather.location,
+        "London, City of London, Greater London, United Kingdom"
+    );
+    assert_eq!(weather.description, "Partly cloudy");
+    assert_eq!(weather.temperature, 9.0);
+    // km/h are normalized to m/s
+    assert!((weather.wind.speed - 19.1 / 3.6).abs() < 1e-9);
+    assert_eq!(weather.visibility, Some(10000.));
+    assert_eq!(weather.utc_offset, Some(0));
+    assert_eq!(weather.timezone.as_deref(), Some("Europe/London"));
+}
+
+#[rstest]
+#[case(Utc.with_ymd_and_hms(2023, 3, 15, 14, 20, 0).unwrap(), 14)]
+#[case(Utc.with_ymd_and_hms(2023, 3, 15, 14, 40, 0).unwrap(), 15)]
+#[case(Utc.with_ymd_and_hms(2023, 3, 15, 0, 0, 0).unwrap(), 0)]
+#[case(Utc.with_ymd_and_hms(2023, 3, 15, 23, 59, 59).unwrap(), 23)]
+#[tokio::test]
+async fn weatherapi_history_nearest_hour(
+    #[case] date: chrono::DateTime<Utc>,
+    #[case] expected_hour: u32,
+) {
+    let server = common::start().await;
+    let weather = weatherapi(&server, common::API_KEY)
+        .get_history_weather(&london(), date)
+        .await
+        .unwrap();
+
+    // the temperature of each hour in the fixture equals the hour
+    assert_eq!(weather.temperature, expected_hour as f64);
+    assert_eq!(
+        weather.time,
+        Utc.with_ymd_and_hms(2023, 3, 15, expected_hour, 0, 0)
+            .unwrap()
+    );
+    assert_eq!(weather.temperature_min, Some(0.));
+    assert_eq!(weather.temperature_max, Some(23.));
+    assert_eq!(
+        weather.sunrise,
+        Some(Utc.with_ymd_and_hms(2023, 3, 15, 6, 3, 0).unwrap())
+    );
+}
+
+#[tokio::test]
+async fn weatherapi_forecast_skips_past_hours() {
+    let server = common::start().await;
+    let forecast = weatherapi(&server, common::API_KEY)
+        .get_forecast(&london(), 2)
+        .await
+        .unwrap();
+
+    // the local time of the fixture is 12:05 on the first day
+    assert_eq!(forecast.len(), 12 + 24);
+    assert_eq!(
+        forecast[0].time,
+        Utc.with_ymd_and_hms(2023, 3, 20, 12, 0, 0).unwrap()
+    );
+    assert_eq!(forecast[12].temperature_min, Some(6.));
+}
+
+#[tokio::test]
+async fn openweather_history_range_chunks() {
+    let server = common::start().await;
+    let from = Utc.with_ymd_and_hms(2023, 3, 10, 0, 0, 0).unwrap();
+    let to = from + chrono::Duration::days(10);
+    let series = openweather(&server, common::API_KEY)
+        .get_history_range(&london(), from, to)
+        .await
+        .unwrap();
+
+    // the fixture holds a single hour
+    assert_eq!(series.len(), 1);
+    // 241 hours are requested as a week and the rest
+    let counts = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter_map(|x| x.url.query_pairs().find(|(k, _)| k == "cnt"))
+        .map(|(_, v)| v.into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(counts, ["168", "73"]);
+}
+
+#[tokio::test]
+async fn weatherapi_history_range() {
+    let server = common::start().await;
+    let from = Utc.with_ymd_and_hms(2023, 3, 15, 6, 0, 0).unwrap();
+    let to = Utc.with_ymd_and_hms(2023, 3, 15, 18, 0, 0).unwrap();
+    let series = weatherapi(&server, common::API_KEY)
+        .get_history_range(&london(), from, to)
+        .await
+        .unwrap();
+
+    assert_eq!(series.len(), 13);
+    assert_eq!(series[0].time, from);
+    assert_eq!(series[12].temperature, 18.);
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn weatherapi_history_range_offset() {
+    // answers like WeatherAPI for a place 9 hours ahead of UTC, with the hours of the local day
+    // asked for
+    let server = wiremock::MockServer::start().await;
+    let fixture: serde_json::Value =
+        serde_json::from_str(&common::fixture("weatherapi/history.json")).unwrap();
+    wiremock::Mock::given(wiremock::matchers::path("/v1/history.json"))
+        .respond_with(move |request: &wiremock::Request| {
+            let (_, date) = request.url.query_pairs().find(|(k, _)| k == "dt").unwrap();
+            let day = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").unwrap();
+            let midnight = day.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() - 9 * 3600;
+            let mut body = fixture.clone();
+            body["location"]["localtime"] = "2023-03-20 21:05".into();
+            let forecastday = &mut body["forecast"]["forecastday"][0];
+            forecastday["date"] = date.as_ref().into();
+            for (i, hour) in forecastday["hour"]
+                .as_array_mut()
+                .unwrap()
+                .iter_mut()
+                .enumerate()
+            {
+                hour["time_epoch"] = (midnight + i as i64 * 3600).into();
+            }
+            wiremock::ResponseTemplate::new(200).set_body_json(body)
+        })
+        .mount(&server)
+        .await;
+
+    // 05:00 to 11:00 on the 16th in local time
+    let from = Utc.with_ymd_and_hms(2023, 3, 15, 20, 0, 0).unwrap();
+    let to = Utc.with_ymd_and_hms(2023, 3, 16, 2, 0, 0).unwrap();
+    let series = weatherapi(&server, common::API_KEY)
+        .get_history_range(&london(), from, to)
+        .await
+        .unwrap();
+
+    assert_eq!(series.len(), 7);
+    assert_eq!(series[0].time, from);
+    assert_eq!(series[6].time, to);
+    let dates = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter_map(|x| x.url.query_pairs().find(|(k, _)| k == "dt"))
+        .map(|(_, v)| v.into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(dates, ["2023-03-15", "2023-03-16"]);
+}
+
+#[rstest]
+#[case::openweather(true)]
+#[case::weatherapi(false)]
+#[tokio::test]
+async fn no_history(#[case] is_openweather: bool) {
+    let server = common::start().await;
+    let provider = if is_openweather {
+        openweather(&server, common::API_KEY)
+    } else {
+        weatherapi(&server, common::API_KEY)
+    };
+    let date = Utc.with_ymd_and_hms(2023, 3, 15, 12, 0, 0).unwrap();
+    let err = provider
+        .get_history_weather(&Location::City("Nowhere".into()), date)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::WeatherNoHistory));
+}
+
+#[rstest]
+#[case::openweather_invalid_key(true, common::INVALID_API_KEY, "London", 5)]
+#[case::openweather_unknown_location(true, common::API_KEY, "Atlantis", 6)]
+#[case::openweather_quota(true, common::EXHAUSTED_API_KEY, "London", 7)]
+#[case::openweather_outage(true, common::API_KEY, "Outage", 8)]
+#[case::weatherapi_invalid_key(false, common::INVALID_API_KEY, "London", 5)]
+#[case::weatherapi_unknown_location(false, common::API_KEY, "Atlantis", 6)]
+#[case::weatherapi_quota(false, common::EXHAUSTED_API_KEY, "London", 7)]
+#[case::weatherapi_outage(false, common::API_KEY, "Outage", 8)]
+#[tokio::test]
+async fn errors(
+    #[case] is_openweather: bool,
+    #[case] api_key: &str,
+    #[case] city: &str,
+    #[case] exit_code: u8,
+) {
+    let server = common::start().await;
+    let provider = if is_openweather {
+        openweather(&server, api_key)
+    } else {
+        weatherapi(&server, api_key)
+    };
+    let err = provider
+        .get_weather(&Location::City(city.into()))
+        .await
+        .unwrap_err();
+    assert_eq!(err.exit_code(), exit_code, "{err:?}");
+}
+
+#[tokio::test]
+async fn error_message() {
+    let server = common::start().await;
+    let err = weatherapi(&server, common::API_KEY)
+        .get_weather(&Location::City("Atlantis".into()))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "weatherapi doesn't know the location: No matching location found."
+    );
+
+    let err = openweather(&server, common::API_KEY)
+        .get_weather(&Location::City("Outage".into()))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "openweather is unavailable (HTTP 503): Service Unavailable"
+    );
+}
+
+#[tokio::test]
+async fn request_error_hides_api_key() {
+    let provider = ProviderUserInfo::OpenWeather {
+        api_key: common::API_KEY.into(),
+        api_key_cmd: None,
+        // nothing listens on port 1
+        base_url: Some("http://127.0.0.1:1".into()),
+        history_base_url: None,
+        limits: Limits::default(),
+    }
+    .build_provider(NO_RETRIES, None);
+    let err = provider.get_weather(&london()).await.unwrap_err();
+
+    assert!(matches!(err, Error::Request(_)));
+    let message = err.to_string();
+    assert!(message.contains("appid=***"), "{message}");
+    assert!(!message.contains(common::API_KEY), "{message}");
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let server = common::start().await;
+    let policy = RequestPolicy {
+        retries: 2,
+        ..NO_RETRIES
+    };
+    let weather = openweather_with(&server, common::API_KEY, policy)
+        .get_weather(&Location::City("Flaky".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(weather.temperature, 8.5);
+    assert_eq!(server.received_requests().await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn gives_up_after_retries() {
+    let server = common::start().await;
+    let policy = RequestPolicy {
+        retries: 1,
+        ..NO_RETRIES
+    };
+    let err = openweather_with(&server, common::API_KEY, policy)
+        .get_weather(&Location::City("Flaky".into()))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        err,
+        Error::ProviderUnavailable { status: 503, .. }
+    ));
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn read_timeout() {
+    let server = common::start().await;
+    let policy = RequestPolicy {
+        read_timeout: Duration::from_secs(1),
+        ..NO_RETRIES
+    };
+    let err = openweather_with(&server, common::API_KEY, policy)
+        .get_weather(&Location::City("Slow".into()))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, Error::Timeout { .. }), "{err:?}");
+}